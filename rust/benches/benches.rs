@@ -5,9 +5,16 @@ use std::time::Duration;
 
 fn bench_cfr(c: &mut Criterion) {
     let nodes: Nodes = Nodes::new(&CONFIG.bet_abstraction);
+    // Train for a while first, so that there are negative regrets to prune
+    for _ in 0..1000 {
+        cfr_iteration(&deck(), &ActionHistory::new(), &nodes, -1, false);
+    }
     let mut group = c.benchmark_group("cfr");
     group.bench_function("cfr", |b| {
-        b.iter(|| cfr_iteration(&deck(), &ActionHistory::new(), &nodes, -1))
+        b.iter(|| cfr_iteration(&deck(), &ActionHistory::new(), &nodes, -1, false))
+    });
+    group.bench_function("cfr_pruned", |b| {
+        b.iter(|| cfr_iteration(&deck(), &ActionHistory::new(), &nodes, -1, true))
     });
     group.finish();
 }
@@ -38,6 +45,25 @@ fn bench_isomorphic_hand(c: &mut Criterion) {
     });
 }
 
+fn bench_hand_strength(c: &mut Criterion) {
+    let mut deck = deck();
+    deck.shuffle(&mut rand::thread_rng());
+    let cards = &deck[..7];
+    let board = &deck[..5];
+    let holes: Vec<[Card; 2]> = (5..deck.len())
+        .flat_map(|i| (i + 1..deck.len()).map(move |j| (i, j)))
+        .map(|(i, j)| [deck[i], deck[j]])
+        .collect();
+    let mut group = c.benchmark_group("hand_strength");
+    group.bench_function("hand_strength", |b| {
+        b.iter(|| FAST_HAND_TABLE.hand_strength(cards))
+    });
+    group.bench_function("hole_strengths", |b| {
+        b.iter(|| FAST_HAND_TABLE.hole_strengths(&holes, board))
+    });
+    group.finish();
+}
+
 fn bench_win_probability_rollout(c: &mut Criterion) {
     let mut opp_range = Range::new();
     let exploiter_hole = str2cards("8dTd");
@@ -73,7 +99,8 @@ fn bench_terminal_utility_vectorized(c: &mut Criterion) {
     for hand_index in 0..range.hands.len() {
         let prob = range.probs[hand_index];
         if prob > 0.0 {
-            preflop_hands.push(range.hands[hand_index]);
+            let hand = &range.hands[hand_index];
+            preflop_hands.push([hand[0], hand[1]]);
         }
     }
     let opp_reach_probs = vec![1.0; preflop_hands.len()];
@@ -111,6 +138,6 @@ fn bench_terminal_utility_vectorized(c: &mut Criterion) {
 criterion_group!(
     name=benches;
     config=Criterion::default().configure_from_args();
-    targets=bench_cfr, bench_subgame_solving, bench_isomorphic_hand, bench_win_probability_rollout, bench_play_hand, bench_terminal_utility_vectorized
+    targets=bench_cfr, bench_subgame_solving, bench_isomorphic_hand, bench_hand_strength, bench_win_probability_rollout, bench_play_hand, bench_terminal_utility_vectorized
 );
 criterion_main!(benches);
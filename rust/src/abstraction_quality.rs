@@ -0,0 +1,213 @@
+// Tools for telling whether one card abstraction is better than another. report() measures
+// how evenly an abstraction's buckets are filled and how well they group together hands of
+// similar strength, and compare() trains a small blueprint with each of two abstractions,
+// then measures their LBR exploitability and plays them against each other.
+
+use crate::card_abstraction::*;
+use crate::card_utils::*;
+use crate::config::CONFIG;
+use crate::exploiter::*;
+use crate::hand_indexer::street_indexer;
+use crate::nodes::*;
+use crate::trainer::{cfr_iteration, should_prune};
+use crate::trainer_utils::*;
+use ahash::AHashMap as HashMap;
+use itertools::Itertools;
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::sync::Arc;
+
+// Number of buckets per street whose hands are sampled for the equity statistics, and the
+// number of hands sampled from each one
+const SAMPLED_BUCKETS: usize = 100;
+const HANDS_PER_BUCKET: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct StreetQuality {
+    pub street: usize,
+    pub num_buckets: usize,
+    pub empty_buckets: usize,
+    // Isomorphic hands per bucket
+    pub min_bucket_size: usize,
+    pub median_bucket_size: usize,
+    pub max_bucket_size: usize,
+    // Standard deviation of E[HS] and E[HS^2] across the sampled hands, and within their
+    // buckets. A good abstraction has a small within-bucket spread compared to the total.
+    pub ehs_std: f64,
+    pub ehs_within_bucket_std: f64,
+    pub ehs2_std: f64,
+    pub ehs2_within_bucket_std: f64,
+}
+
+pub fn abstraction_quality(abstraction: &Abstraction) -> Vec<StreetQuality> {
+    [FLOP, TURN, RIVER]
+        .iter()
+        .map(|&street| street_quality(abstraction, street))
+        .collect()
+}
+
+pub fn print_abstraction_quality(abstraction: &Abstraction) {
+    for quality in abstraction_quality(abstraction) {
+        let street = ["Preflop", "Flop", "Turn", "River"][quality.street];
+        println!("{street}:");
+        println!(
+            "  Buckets: {} ({} empty)",
+            quality.num_buckets, quality.empty_buckets
+        );
+        println!(
+            "  Hands per bucket: min {}, median {}, max {}",
+            quality.min_bucket_size, quality.median_bucket_size, quality.max_bucket_size
+        );
+        println!(
+            "  E[HS] std: {:.4} overall, {:.4} within buckets",
+            quality.ehs_std, quality.ehs_within_bucket_std
+        );
+        println!(
+            "  E[HS^2] std: {:.4} overall, {:.4} within buckets",
+            quality.ehs2_std, quality.ehs2_within_bucket_std
+        );
+    }
+}
+
+fn street_quality(abstraction: &Abstraction, street: usize) -> StreetQuality {
+    let table = abstraction.table(street);
+    let num_buckets = abstraction.num_hand_buckets(street);
+    let mut sizes = vec![0; num_buckets];
+    for &bucket in table {
+        sizes[bucket as usize] += 1;
+    }
+    let empty_buckets = sizes.iter().filter(|&&s| s == 0).count();
+    let mut sorted_sizes = sizes.clone();
+    sorted_sizes.sort();
+
+    // Reservoir sample a few hands from each of a random set of buckets
+    let mut rng = thread_rng();
+    let sampled_buckets: Vec<i32> = (0..num_buckets as i32)
+        .filter(|&b| sizes[b as usize] > 0)
+        .choose_multiple(&mut rng, SAMPLED_BUCKETS);
+    let mut samples: HashMap<i32, (usize, Vec<u64>)> = sampled_buckets
+        .iter()
+        .map(|&b| (b, (0, Vec::with_capacity(HANDS_PER_BUCKET))))
+        .collect();
+    for (index, &bucket) in table.iter().enumerate() {
+        if let Some((seen, hands)) = samples.get_mut(&(bucket as i32)) {
+            *seen += 1;
+            if hands.len() < HANDS_PER_BUCKET {
+                hands.push(index as u64);
+            } else {
+                let i = rng.gen_range(0..*seen);
+                if i < HANDS_PER_BUCKET {
+                    hands[i] = index as u64;
+                }
+            }
+        }
+    }
+
+    let hands: Vec<(i32, u64)> = samples
+        .into_iter()
+        .flat_map(|(bucket, (_, hands))| hands.into_iter().map(move |h| (bucket, h)))
+        .collect();
+    let bar = pbar(hands.len());
+    let moments: Vec<(i32, f64, f64)> = hands
+        .par_iter()
+        .map(|&(bucket, index)| {
+            let hand = cards2hand(&street_indexer(street).unindex(index));
+            let (ehs, ehs2) = ehs_moments(hand);
+            bar.inc(1);
+            (bucket, ehs, ehs2)
+        })
+        .collect();
+    bar.finish();
+
+    let ehs: Vec<(i32, f64)> = moments.iter().map(|m| (m.0, m.1)).collect();
+    let ehs2: Vec<(i32, f64)> = moments.iter().map(|m| (m.0, m.2)).collect();
+    StreetQuality {
+        street,
+        num_buckets,
+        empty_buckets,
+        min_bucket_size: sorted_sizes[empty_buckets.min(num_buckets - 1)],
+        median_bucket_size: sorted_sizes[num_buckets / 2],
+        max_bucket_size: sorted_sizes[num_buckets - 1],
+        ehs_std: std_dev(&ehs),
+        ehs_within_bucket_std: within_bucket_std(&ehs),
+        ehs2_std: std_dev(&ehs2),
+        ehs2_within_bucket_std: within_bucket_std(&ehs2),
+    }
+}
+
+// E[HS] and E[HS^2] of a hand over all the ways the board can run out
+fn ehs_moments(hand: u64) -> (f64, f64) {
+    let cards = hand2cards(hand);
+    let mut deck = deck();
+    deck.retain(|c| !cards.contains(c));
+    let mut sum = 0.0;
+    let mut sum_squares = 0.0;
+    let mut count = 0.0;
+    for rollout in deck.iter().combinations(7 - cards.len()) {
+        let full_hand = [cards.clone(), deepcopy(&rollout)].concat();
+        let equity = river_equity(&full_hand);
+        sum += equity;
+        sum_squares += equity * equity;
+        count += 1.0;
+    }
+    (sum / count, sum_squares / count)
+}
+
+fn std_dev(values: &[(i32, f64)]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().map(|v| v.1).sum::<f64>() / n;
+    (values.iter().map(|v| (v.1 - mean).powi(2)).sum::<f64>() / n).sqrt()
+}
+
+// Root mean squared distance of each value from the mean of its bucket
+fn within_bucket_std(values: &[(i32, f64)]) -> f64 {
+    let mut sums: HashMap<i32, (f64, f64)> = HashMap::new();
+    for &(bucket, value) in values {
+        let entry = sums.entry(bucket).or_insert((0.0, 0.0));
+        entry.0 += value;
+        entry.1 += 1.0;
+    }
+    let squared_error: f64 = values
+        .iter()
+        .map(|&(bucket, value)| {
+            let (sum, count) = sums[&bucket];
+            (value - sum / count).powi(2)
+        })
+        .sum();
+    (squared_error / values.len() as f64).sqrt()
+}
+
+// Trains a small blueprint with each abstraction, and reports the LBR exploitability of each
+// and the result of playing them against each other.
+pub fn compare_abstractions(a: Arc<Abstraction>, b: Arc<Abstraction>, iters: usize, hands: usize) {
+    println!("[INFO] Training a blueprint with abstraction A.");
+    let nodes_a = train_blueprint(a, iters);
+    println!("[INFO] Training a blueprint with abstraction B.");
+    let nodes_b = train_blueprint(b, iters);
+
+    println!("Abstraction A:");
+    blueprint_exploitability(&nodes_a, CONFIG.lbr_iters);
+    println!("Abstraction B:");
+    blueprint_exploitability(&nodes_b, CONFIG.lbr_iters);
+
+    let strategy_a = |hole: &[Card], board: &[Card], history: &ActionHistory| {
+        nodes_a.get_strategy(hole, board, history)
+    };
+    let strategy_b = |hole: &[Card], board: &[Card], history: &ActionHistory| {
+        nodes_b.get_strategy(hole, board, history)
+    };
+    let (mean, confidence) = head_to_head(&strategy_a, &strategy_b, hands);
+    println!("A vs B: {mean} +/- {confidence} BB/h");
+}
+
+fn train_blueprint(abstraction: Arc<Abstraction>, iters: usize) -> Nodes {
+    let nodes = Nodes::with_abstraction(&CONFIG.bet_abstraction, abstraction);
+    let deck = deck();
+    let bar = pbar(iters);
+    (0..iters).into_par_iter().for_each(|i| {
+        cfr_iteration(&deck, &ActionHistory::new(), &nodes, -1, should_prune(i));
+        bar.inc(1);
+    });
+    bar.finish();
+    nodes
+}
@@ -1,14 +1,20 @@
+use crate::board_texture::BoardTexture;
 use crate::bot::Bot;
 use crate::config::CONFIG;
+use crate::equity::*;
+use crate::telemetry::read_metrics;
 use crate::trainer::load_nodes;
 use crate::trainer_utils::*;
 use crate::{card_utils::*, OPPONENT};
 use actix_cors::Cors;
 use actix_files as fs;
-use actix_web::{web, App, HttpServer, Responder};
+use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+// Largest number of showdowns an equity request enumerates before it samples instead
+const MAX_REQUEST_EXACT_SHOWDOWNS: usize = 10_000_000;
+
 static BOT: Lazy<Bot> = Lazy::new(|| {
     Bot::new(
         load_nodes(&CONFIG.nodes_path),
@@ -126,6 +132,79 @@ async fn get_history_info(json: web::Json<HistoryAndCardsJSON>) -> impl Responde
     serde_json::to_string(&history_info).unwrap()
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct EquityRequestJSON {
+    hero: String,
+    villain: String,
+    board: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EquityJSON {
+    equity: f64,
+    win: f64,
+    tie: f64,
+    lose: f64,
+    exact: bool,
+}
+
+// The hero and villain are ranges in the format of parse_range, like "AhKd" or "random".
+// Bad ranges or boards get a 400 response. The equity is computed on the blocking thread pool,
+// with enumeration capped so that one request can't hold a thread for long.
+async fn get_equity(json: web::Json<EquityRequestJSON>) -> HttpResponse {
+    let inputs = parse_range(&json.hero).and_then(|hero| {
+        let villain = parse_range(&json.villain)?;
+        let board = try_parse_cards(&json.board)?;
+        check_equity_inputs(&hero, &villain, &board)?;
+        Ok((hero, villain, board))
+    });
+    let (hero, villain, board) = match inputs {
+        Ok(inputs) => inputs,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let equity = web::block(move || {
+        range_vs_range_capped(
+            &hero,
+            &villain,
+            &board,
+            EQUITY_TIME_LIMIT,
+            MAX_REQUEST_EXACT_SHOWDOWNS,
+        )
+    })
+    .await;
+    match equity {
+        Ok(equity) => {
+            let equity_json = EquityJSON {
+                equity: equity.equity(),
+                win: equity.win,
+                tie: equity.tie,
+                lose: equity.lose,
+                exact: equity.exact,
+            };
+            HttpResponse::Ok().body(serde_json::to_string(&equity_json).unwrap())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BoardTextureRequestJSON {
+    board: Vec<String>,
+}
+
+// The training metrics so far, one entry per epoch, for a live training chart
+async fn get_metrics() -> impl Responder {
+    serde_json::to_string(&read_metrics(&CONFIG.metrics_path)).unwrap()
+}
+
+// Bad cards, or boards that aren't a flop, turn or river, get a 400 response
+async fn get_board_texture(json: web::Json<BoardTextureRequestJSON>) -> HttpResponse {
+    match try_parse_cards(&json.board).and_then(|board| BoardTexture::try_new(&board)) {
+        Ok(texture) => HttpResponse::Ok().body(serde_json::to_string(&texture).unwrap()),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
 fn parse_history(h: &[ActionJSON]) -> ActionHistory {
     let mut history = ActionHistory::new();
     for action_json in h {
@@ -146,10 +225,15 @@ fn parse_history(h: &[ActionJSON]) -> ActionHistory {
 
 // Converts from the list ["5d", "7c", "Jh", "back", "back"] to the Vec<Card> representation
 fn parse_cards(cards: &[String]) -> Vec<Card> {
-    let mut cards: Vec<String> = cards.to_vec();
-    cards.retain(|c| c != "back");
-    let cardvec = cards.iter().map(|card| Card::new(card)).collect();
-    cardvec
+    try_parse_cards(cards).unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn try_parse_cards(cards: &[String]) -> Result<Vec<Card>, String> {
+    cards
+        .iter()
+        .filter(|c| *c != "back")
+        .map(|card| Card::parse(card))
+        .collect()
 }
 
 #[actix_rt::main]
@@ -159,6 +243,13 @@ pub async fn start_server() -> std::io::Result<()> {
         App::new()
             .route("/api/bot", web::post().to(get_cpu_action))
             .route("/api/historyInfo", web::post().to(get_history_info))
+            .route("/api/equity", web::post().to(get_equity))
+            .route("/api/boardTexture", web::post().to(get_board_texture))
+            .configure(|cfg| {
+                if CONFIG.metrics_endpoint {
+                    cfg.route("/api/metrics", web::get().to(get_metrics));
+                }
+            })
             .service(fs::Files::new("/", "../gui/build").index_file("index.html"))
             .wrap(Cors::permissive())
     })
@@ -0,0 +1,84 @@
+use optimus::*;
+
+// Builds the card abstraction in abstraction_dir from params.toml. Every step caches its
+// results there, so rerunning a command after a crash picks up where it left off, and steps
+// whose output already exists are skipped.
+//
+//   abstraction build [potential|emd|ehs2]
+//   abstraction distributions <flop|turn|river>
+//   abstraction cluster [potential|emd|ehs2]
+//   abstraction expand-keys <flop|turn>
+//   abstraction validate
+//
+// The potential-aware abstraction (the default) is built backwards: the river first, then the
+// turn over river buckets, then the flop over turn buckets. emd clusters each street by its
+// equity distribution, and ehs2 buckets each street by E[HS^2] percentile.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let method = args.get(1).map_or("potential", String::as_str);
+    match args.first().map(String::as_str) {
+        Some("build") => {
+            compute_distributions(method);
+            cluster(method);
+            validate();
+        }
+        Some("distributions") => match args.get(1).map(String::as_str) {
+            Some(street @ ("flop" | "turn")) => {
+                get_equity_distributions(street);
+            }
+            Some("river") => {
+                get_ochs_distributions();
+            }
+            _ => usage(),
+        },
+        Some("cluster") => cluster(method),
+        Some("expand-keys") => match args.get(1).map(String::as_str) {
+            Some("flop") => expand_abstraction_keys(5),
+            Some("turn") => expand_abstraction_keys(6),
+            _ => usage(),
+        },
+        Some("validate") => validate(),
+        _ => usage(),
+    }
+}
+
+fn compute_distributions(method: &str) {
+    match method {
+        "potential" => {
+            get_ochs_distributions();
+        }
+        "emd" => {
+            get_equity_distributions("flop");
+            get_equity_distributions("turn");
+            get_ochs_distributions();
+        }
+        // The E[HS^2] values are computed and cached as part of clustering
+        "ehs2" => {}
+        _ => usage(),
+    }
+}
+
+fn cluster(method: &str) {
+    match method {
+        "potential" => create_potential_aware_abstraction(),
+        "emd" => create_abstraction_clusters(),
+        "ehs2" => create_ehs2_abstraction(),
+        _ => usage(),
+    }
+}
+
+fn validate() {
+    if !validate_abstraction() {
+        std::process::exit(1);
+    }
+}
+
+fn usage() {
+    println!("Usage:");
+    println!("  abstraction build [potential|emd|ehs2]");
+    println!("  abstraction distributions <flop|turn|river>");
+    println!("  abstraction cluster [potential|emd|ehs2]");
+    println!("  abstraction expand-keys <flop|turn>");
+    println!("  abstraction validate");
+    std::process::exit(1);
+}
@@ -0,0 +1,30 @@
+use optimus::*;
+use std::sync::Arc;
+
+// Measures the quality of the card abstraction in a directory of *_abstraction.bin files, or
+// compares two abstractions by training a small blueprint with each.
+//
+//   abstraction-quality report [dir]
+//   abstraction-quality compare <dir_a> <dir_b> [iters] [hands]
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let default_dir = abstraction_file("");
+    match args.first().map(String::as_str) {
+        Some("report") => {
+            let dir = args.get(1).unwrap_or(&default_dir);
+            print_abstraction_quality(&Abstraction::load(dir));
+        }
+        Some("compare") if args.len() >= 3 => {
+            let a = Arc::new(Abstraction::load(&args[1]));
+            let b = Arc::new(Abstraction::load(&args[2]));
+            let iters = args.get(3).map_or(10_000, |s| s.parse().unwrap());
+            let hands = args.get(4).map_or(100_000, |s| s.parse().unwrap());
+            compare_abstractions(a, b, iters, hands);
+        }
+        _ => {
+            println!("Usage:");
+            println!("  abstraction-quality report [dir]");
+            println!("  abstraction-quality compare <dir_a> <dir_b> [iters] [hands]");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use optimus::*;
+
+// Compares or merges two blueprints.
+//
+//   blueprint diff products/nodes.bin other/nodes.bin [top]
+//   blueprint merge products/nodes.bin other/nodes.bin 0.5 merged/nodes.bin
+//   blueprint prune products/nodes.bin pruned/nodes.bin 1.0
+//
+// diff prints the distance between the average strategies on each street, the top histories
+// and spots with the biggest changes, and the histories only one blueprint has. merge weights
+// the first blueprint by the given weight and the second by 1 - weight. prune drops the
+// histories with less reach than the given threshold.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("diff") if args.len() >= 3 => {
+            let top = args.get(3).map_or(20, |n| n.parse().unwrap());
+            diff(&args[1], &args[2], top);
+        }
+        Some("merge") if args.len() == 5 => {
+            let a = load_nodes(&args[1]);
+            let b = load_nodes(&args[2]);
+            let merged = merge_blueprints(&a, &b, args[3].parse().unwrap());
+            save_nodes(&merged, &args[4]);
+            println!("{} nodes in the merged blueprint.", merged.len());
+        }
+        Some("prune") if args.len() == 4 => {
+            let nodes = load_nodes(&args[1]);
+            let min_reach: f32 = args[3].parse().unwrap();
+            let stats = prune_blueprint(&nodes, min_reach);
+            println!(
+                "Dropped {} histories. {} nodes left of {}.",
+                stats.dropped, stats.nodes_after, stats.nodes_before
+            );
+            save_nodes(&nodes, &args[2]);
+        }
+        _ => {
+            println!("Usage: blueprint diff <a> <b> [top]");
+            println!("       blueprint merge <a> <b> <weight of a> <output>");
+            println!("       blueprint prune <input> <output> <min reach>");
+        }
+    }
+}
+
+fn diff(a_path: &str, b_path: &str, top: usize) {
+    let a = load_nodes(a_path);
+    let b = load_nodes(b_path);
+    let diff = diff_blueprints(&a, &b, top);
+
+    println!("Street     Nodes        L1      TV");
+    for (street, name) in ["preflop", "flop", "turn", "river"].iter().enumerate() {
+        let distance = &diff.streets[street];
+        println!(
+            "{name:<8} {:>7} {:>9.4} {:>7.4}",
+            distance.nodes,
+            distance.l1(),
+            distance.total_variation()
+        );
+    }
+
+    println!("\nHistories with the biggest changes:");
+    for (history, distance) in diff.histories.iter().take(top) {
+        println!(
+            "  L1 {:.4}  TV {:.4}  {history}",
+            distance.l1(),
+            distance.total_variation()
+        );
+    }
+
+    println!("\nSpots with the biggest changes:");
+    for spot in &diff.biggest_changes {
+        let actions = spot.history.next_actions(&a.bet_abstraction);
+        let actions: Vec<String> = actions.iter().map(|a| a.to_string()).collect();
+        println!(
+            "  L1 {:.4}  bucket {} of {}",
+            spot.l1, spot.bucket, spot.history
+        );
+        println!("    {actions:?}\n    {:?}\n    {:?}", spot.a, spot.b);
+    }
+
+    for (name, histories) in [(a_path, &diff.only_in_a), (b_path, &diff.only_in_b)] {
+        println!("\n{} histories only in {name}", histories.len());
+        for history in histories.iter().take(top) {
+            println!("  {history}");
+        }
+    }
+    if !diff.mismatched.is_empty() {
+        println!(
+            "\n{} histories have different actions in each blueprint",
+            diff.mismatched.len()
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use optimus::*;
+use std::io::{self, BufRead, Write};
+
+// Browses the blueprint tree in the terminal. Walk down a history by picking its actions, set
+// the hole cards and board, and see the node those reach, or the strategy of every hand.
+//
+//   browse [nodes.bin]
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or(CONFIG.nodes_path.clone());
+    let nodes = load_nodes(&path);
+    println!(
+        "Loaded {} nodes from {path}. Type help for the commands.",
+        nodes.len()
+    );
+
+    let mut history = ActionHistory::new();
+    let mut hole: Vec<Card> = Vec::new();
+    let mut board: Vec<Card> = Vec::new();
+    show(&nodes, &history, &hole, &board);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            return;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => continue,
+            ["quit" | "exit"] => return,
+            ["help"] => {
+                help();
+                continue;
+            }
+            ["back"] if !history.is_empty() => history = history.without_last_action(),
+            ["reset"] => history = ActionHistory::new(),
+            ["hole", cards] => match parse_cards(cards, 2) {
+                Some(cards) => hole = cards,
+                None => println!("Hole cards are two cards, like AhKd"),
+            },
+            ["board"] => board = Vec::new(),
+            ["board", cards] => match parse_cards(cards, cards.len() / 2) {
+                Some(cards) if cards.len() >= 3 && cards.len() <= 5 => board = cards,
+                _ => println!("The board is three to five cards, like 2c7d9h"),
+            },
+            ["table"] if history.hand_over() => println!("The hand is over."),
+            ["table"] if history.street == PREFLOP => {
+                print!("{}", format_preflop_grid(&nodes, &history));
+                continue;
+            }
+            ["table"] => {
+                print!("{}", format_bucket_table(&nodes, &history));
+                continue;
+            }
+            [n] if n.parse::<usize>().is_ok() && !history.hand_over() => {
+                let actions = history.next_actions(&nodes.bet_abstraction);
+                match actions.get(n.parse::<usize>().unwrap()) {
+                    Some(action) => history.add(action),
+                    _ => println!("There's no action {n} here."),
+                }
+            }
+            _ => {
+                println!("Unknown command. Type help for the commands.");
+                continue;
+            }
+        }
+        show(&nodes, &history, &hole, &board);
+    }
+}
+
+fn help() {
+    println!("  <number>       take that action");
+    println!("  back           undo the last action");
+    println!("  reset          go back to the start of the hand");
+    println!("  hole AhKd      set the hole cards");
+    println!("  board 2c7d9h   set the board, or clear it with just board");
+    println!("  table          show the 13x13 grid preflop or every card bucket postflop");
+    println!("  quit");
+}
+
+fn parse_cards(cards: &str, n: usize) -> Option<Vec<Card>> {
+    let valid = cards.len() == 2 * n
+        && cards.as_bytes().chunks(2).all(|card| {
+            "23456789TJQKA".contains(card[0] as char) && "cdhs".contains(card[1] as char)
+        });
+    if valid {
+        Some(str2cards(cards))
+    } else {
+        None
+    }
+}
+
+fn show(nodes: &Nodes, history: &ActionHistory, hole: &[Card], board: &[Card]) {
+    println!("\nHistory: {history}");
+    if history.hand_over() {
+        println!("The hand is over.");
+        return;
+    }
+    for (i, action) in history
+        .next_actions(&nodes.bet_abstraction)
+        .iter()
+        .enumerate()
+    {
+        println!("  {i}: {action}");
+    }
+
+    let board_len = board_length(history.street);
+    if hole.is_empty() {
+        println!("Set the hole cards with hole to see a node.");
+    } else if board.len() < board_len {
+        println!("Set a board of at least {board_len} cards to see a node.");
+    } else {
+        let board = &board[..board_len];
+        let infoset = nodes.infoset(hole, board, history);
+        println!(
+            "{} {}, bucket {}",
+            cards2str(hole),
+            cards2str(board),
+            infoset.card_bucket
+        );
+        match nodes.get(&infoset) {
+            Some(node) => print!(
+                "{}",
+                format_node(&infoset.next_actions(&nodes.bet_abstraction), &node)
+            ),
+            None => println!("Training never reached this node."),
+        }
+    }
+}
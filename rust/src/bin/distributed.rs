@@ -0,0 +1,72 @@
+use optimus::*;
+use std::net::TcpListener;
+use std::time::Instant;
+
+// Trains the blueprint across several processes, which can be on different machines. Start
+// the coordinator, then the workers pointing at it:
+//
+//   distributed coordinator 0.0.0.0:7878 4
+//   distributed worker 192.168.1.10:7878
+//
+// The coordinator trains for train_iters iterations, merging the workers' regrets every
+// sync_every iterations, and saves the blueprint to nodes_path every eval_every iterations.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("coordinator"), Some(address)) => {
+            let n_workers = args.get(2).map_or(1, |n| n.parse().unwrap());
+            coordinator(address, n_workers);
+        }
+        (Some("worker"), Some(address)) => {
+            load_abstraction_or_exit();
+            let deck = deck();
+            run_worker(address.as_str(), |nodes: &Nodes, i| {
+                cfr_iteration(&deck, &ActionHistory::new(), nodes, -1, should_prune(i));
+            });
+        }
+        _ => println!("Usage: distributed coordinator <address> [workers] | worker <address>"),
+    }
+}
+
+fn load_abstraction_or_exit() {
+    if let Err(error) = load_global_abstraction() {
+        eprintln!("[ERROR] {error}");
+        std::process::exit(1);
+    }
+}
+
+fn coordinator(address: &str, n_workers: usize) {
+    assert!(
+        CONFIG.num_players == 2 && CONFIG.variant() != Variant::Omaha,
+        "Distributed training only supports heads-up hold'em"
+    );
+    load_abstraction_or_exit();
+    let nodes = if CONFIG.warm_start {
+        load_nodes(&CONFIG.nodes_path)
+    } else {
+        let _ = std::fs::remove_file(&CONFIG.metrics_path);
+        let _ = std::fs::remove_file(&CONFIG.watchlist_path);
+        Nodes::new(&CONFIG.bet_abstraction)
+    };
+    let listener = TcpListener::bind(address).unwrap();
+    println!("[INFO] Waiting for {n_workers} workers on {address}.");
+    let mut coordinator = Coordinator::new(listener, n_workers, nodes);
+
+    let num_epochs = CONFIG.train_iters / CONFIG.eval_every;
+    for epoch in 0..num_epochs {
+        println!("[INFO] Training epoch {}/{}", epoch + 1, num_epochs);
+        let epoch_start = Instant::now();
+        coordinator.train(CONFIG.eval_every, CONFIG.sync_every);
+        let elapsed = epoch_start.elapsed();
+        serialize_nodes(&coordinator.nodes);
+        record_epoch(
+            &coordinator.nodes,
+            epoch,
+            coordinator.iterations,
+            CONFIG.eval_every,
+            elapsed,
+        );
+    }
+    let nodes = coordinator.finish();
+    println!("{} nodes reached.", nodes.len());
+}
@@ -0,0 +1,33 @@
+use optimus::*;
+use std::time::Duration;
+
+// Computes the equity of one hand or range against another. Ranges are "random" or comma
+// separated holes, and the board is optional.
+//
+//   equity AhKd QsQc
+//   equity AhKd,AsKs random Td7c2h
+//   equity random random Td7c2h 5
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 2 {
+        println!("Usage: equity <hero> <villain> [board] [seconds]");
+        return;
+    }
+    let hero = parse_range(&args[0]).unwrap_or_else(|e| panic!("{e}"));
+    let villain = parse_range(&args[1]).unwrap_or_else(|e| panic!("{e}"));
+    let board = args.get(2).map_or(Ok(Vec::new()), |b| try_str2cards(b));
+    let board = board.unwrap_or_else(|e| panic!("{e}"));
+    let time_limit = args.get(3).map_or(EQUITY_TIME_LIMIT, |s| {
+        Duration::from_secs_f64(s.parse().unwrap())
+    });
+    if let Err(e) = check_equity_inputs(&hero, &villain, &board) {
+        panic!("{e}");
+    }
+    let equity = range_vs_range(&hero, &villain, &board, time_limit);
+    let method = if equity.exact { "exact" } else { "sampled" };
+    println!("Equity: {:.4}", equity.equity());
+    println!(
+        "Win {:.4}, tie {:.4}, lose {:.4} ({} showdowns, {method})",
+        equity.win, equity.tie, equity.lose, equity.samples
+    );
+}
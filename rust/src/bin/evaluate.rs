@@ -0,0 +1,21 @@
+use optimus::*;
+
+// Plays the bot against each scripted opponent and prints how much it wins against each.
+//
+//   evaluate [hands] [nodes.bin]
+fn main() {
+    let hands = std::env::args()
+        .nth(1)
+        .map_or(10_000, |n| n.parse().unwrap());
+    let path = std::env::args().nth(2).unwrap_or(CONFIG.nodes_path.clone());
+    let bot = Bot::new(
+        load_nodes(&path),
+        CONFIG.subgame_solving,
+        CONFIG.depth_limit,
+    );
+    let get_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
+        bot.get_strategy(hole, board, history)
+    };
+    let results = evaluate_against_opponents(&get_strategy, hands);
+    print!("{}", format_evaluation(&results));
+}
@@ -3,6 +3,11 @@ use optimus::*;
 fn main() {
     // let bot = Bot::new();
     // bot_exploitability(&bot, CONFIG.lbr_iters);
+    if CONFIG.variant() == Variant::Omaha {
+        let blueprint = load_multiway_nodes(&CONFIG.nodes_path);
+        multiway_exploitability(&blueprint, CONFIG.lbr_iters);
+        return;
+    }
     let blueprint = load_nodes(&CONFIG.nodes_path);
     blueprint_exploitability(&blueprint, CONFIG.lbr_iters);
 }
@@ -0,0 +1,229 @@
+// Compares and merges blueprints, like two nodes.bin files from different runs or configs, and
+// prunes the histories a blueprint rarely reaches.
+
+use crate::nodes::{Node, Nodes};
+use crate::trainer_utils::*;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default)]
+pub struct StrategyDistance {
+    // The sum and mean over the compared nodes of the L1 distance between average strategies.
+    // The total variation distance is half the L1 distance.
+    pub total_l1: f64,
+    pub nodes: usize,
+}
+
+impl StrategyDistance {
+    pub fn l1(&self) -> f64 {
+        self.total_l1 / self.nodes.max(1) as f64
+    }
+
+    pub fn total_variation(&self) -> f64 {
+        self.l1() / 2.0
+    }
+
+    fn add(&mut self, l1: f64) {
+        self.total_l1 += l1;
+        self.nodes += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpotDiff {
+    pub history: ActionHistory,
+    pub bucket: usize,
+    pub a: Vec<f32>,
+    pub b: Vec<f32>,
+    pub l1: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlueprintDiff {
+    pub streets: [StrategyDistance; 4],
+    // Sorted from the biggest distance to the smallest
+    pub histories: Vec<(ActionHistory, StrategyDistance)>,
+    pub only_in_a: Vec<ActionHistory>,
+    pub only_in_b: Vec<ActionHistory>,
+    // Histories in both whose nodes have different actions, so they can't be compared
+    pub mismatched: Vec<ActionHistory>,
+    // The top_spots nodes whose average strategy changed the most
+    pub biggest_changes: Vec<SpotDiff>,
+}
+
+pub fn diff_blueprints(a: &Nodes, b: &Nodes, top_spots: usize) -> BlueprintDiff {
+    let mut diff = BlueprintDiff {
+        streets: Default::default(),
+        histories: Vec::new(),
+        only_in_a: Vec::new(),
+        only_in_b: Vec::new(),
+        mismatched: Vec::new(),
+        biggest_changes: Vec::new(),
+    };
+    for elem in &a.dashmap {
+        let history = elem.key();
+        let b_nodes = match b.dashmap.get(history) {
+            Some(b_nodes) => b_nodes,
+            None => {
+                diff.only_in_a.push(history.clone());
+                continue;
+            }
+        };
+        let a_nodes = elem.value();
+        if a_nodes[0].lock().unwrap().num_actions != b_nodes[0].lock().unwrap().num_actions {
+            diff.mismatched.push(history.clone());
+            continue;
+        }
+
+        // If the blueprints have different numbers of buckets at this history, neighbouring
+        // buckets of the one with fewer share a node
+        let n_buckets = a_nodes.len().max(b_nodes.len());
+        let mut distance = StrategyDistance::default();
+        for bucket in 0..n_buckets {
+            let a_node = a_nodes[bucket * a_nodes.len() / n_buckets].lock().unwrap();
+            let b_node = b_nodes[bucket * b_nodes.len() / n_buckets].lock().unwrap();
+            let a_strategy = a_node.cumulative_strategy();
+            let b_strategy = b_node.cumulative_strategy();
+            let l1 = a_strategy
+                .iter()
+                .zip(b_strategy.iter())
+                .map(|(p, q)| (p - q).abs() as f64)
+                .sum();
+            distance.add(l1);
+            diff.streets[history.street.min(RIVER)].add(l1);
+            diff.biggest_changes.push(SpotDiff {
+                history: history.clone(),
+                bucket,
+                a: a_strategy.to_vec(),
+                b: b_strategy.to_vec(),
+                l1,
+            });
+        }
+        diff.histories.push((history.clone(), distance));
+
+        // Only keep the biggest changes so far, so that this doesn't hold a copy of every node
+        if diff.biggest_changes.len() > 2 * top_spots {
+            keep_biggest(&mut diff.biggest_changes, top_spots);
+        }
+    }
+    for elem in &b.dashmap {
+        if !a.dashmap.contains_key(elem.key()) {
+            diff.only_in_b.push(elem.key().clone());
+        }
+    }
+    keep_biggest(&mut diff.biggest_changes, top_spots);
+    diff.histories
+        .sort_by(|x, y| y.1.l1().partial_cmp(&x.1.l1()).unwrap());
+    diff
+}
+
+fn keep_biggest(spots: &mut Vec<SpotDiff>, n: usize) {
+    spots.sort_by(|x, y| y.l1.partial_cmp(&x.l1).unwrap());
+    spots.truncate(n);
+}
+
+// Merges two blueprints trained with the same bet and card abstraction. The merged average
+// strategy of each node is weight * a's plus (1 - weight) * b's, and its regrets and t are
+// weighted the same way. Histories that only one blueprint has are copied over.
+pub fn merge_blueprints(a: &Nodes, b: &Nodes, weight: f32) -> Nodes {
+    assert!(
+        (0.0..=1.0).contains(&weight),
+        "The weight must be between 0 and 1"
+    );
+    assert_eq!(
+        a.bet_abstraction, b.bet_abstraction,
+        "The blueprints have different bet abstractions"
+    );
+    let merged = Nodes::new(&a.bet_abstraction);
+    for elem in &a.dashmap {
+        let a_nodes = elem.value();
+        let history_nodes: Vec<Mutex<Node>> = match b.dashmap.get(elem.key()) {
+            Some(b_nodes) => {
+                assert_eq!(
+                    a_nodes.len(),
+                    b_nodes.len(),
+                    "The blueprints have different card abstractions at {}",
+                    elem.key()
+                );
+                a_nodes
+                    .iter()
+                    .zip(b_nodes.iter())
+                    .map(|(a_node, b_node)| {
+                        let a_node = a_node.lock().unwrap();
+                        let b_node = b_node.lock().unwrap();
+                        Mutex::new(merge_nodes(&a_node, &b_node, weight))
+                    })
+                    .collect()
+            }
+            None => copy_nodes(a_nodes),
+        };
+        merged.dashmap.insert(elem.key().clone(), history_nodes);
+    }
+    for elem in &b.dashmap {
+        if !merged.dashmap.contains_key(elem.key()) {
+            merged
+                .dashmap
+                .insert(elem.key().clone(), copy_nodes(elem.value()));
+        }
+    }
+    merged
+}
+
+fn merge_nodes(a: &Node, b: &Node, weight: f32) -> Node {
+    assert_eq!(a.num_actions, b.num_actions);
+    let mut node = Node::new(a.num_actions);
+    // Normalize the strategy sums first, so that a blueprint trained for longer doesn't
+    // outweigh the other, then scale the merged strategy back up
+    let a_sum: f32 = a.strategy_sum.iter().sum();
+    let b_sum: f32 = b.strategy_sum.iter().sum();
+    let a_strategy = a.cumulative_strategy();
+    let b_strategy = b.cumulative_strategy();
+    let scale = weight * a_sum + (1.0 - weight) * b_sum;
+    for i in 0..a.num_actions {
+        let strategy = weight * a_strategy[i] + (1.0 - weight) * b_strategy[i];
+        node.strategy_sum[i] = strategy * scale;
+        node.regrets[i] = weight * a.regrets[i] + (1.0 - weight) * b.regrets[i];
+    }
+    node.t = (weight * a.t as f32 + (1.0 - weight) * b.t as f32).round() as i32;
+    node
+}
+
+fn copy_nodes(nodes: &[Mutex<Node>]) -> Vec<Mutex<Node>> {
+    nodes
+        .iter()
+        .map(|n| Mutex::new(n.lock().unwrap().clone()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PruneStats {
+    pub dropped: usize,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+}
+
+// The reach of a history is the sum of its nodes' strategy sums, which count every visit
+// weighted by the probability that the player to act played to it. Histories with reach below
+// min_reach are dropped, and Nodes::get_strategy falls back to another strategy for them. To
+// give rarely reached histories fewer buckets, train with bucket_granularity instead.
+pub fn prune_blueprint(nodes: &Nodes, min_reach: f32) -> PruneStats {
+    let mut stats = PruneStats {
+        nodes_before: nodes.len(),
+        ..Default::default()
+    };
+    let reach = |history_nodes: &[Mutex<Node>]| -> f32 {
+        history_nodes
+            .iter()
+            .map(|n| n.lock().unwrap().strategy_sum.iter().sum::<f32>())
+            .sum()
+    };
+    // The root is always kept, so that every history has an ancestor to fall back to
+    nodes.dashmap.retain(|history, history_nodes| {
+        let keep = history.is_empty() || reach(history_nodes) >= min_reach;
+        if !keep {
+            stats.dropped += 1;
+        }
+        keep
+    });
+    stats.nodes_after = nodes.len();
+    stats
+}
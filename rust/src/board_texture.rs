@@ -0,0 +1,129 @@
+// Public features of the board that the card buckets don't capture, since those only
+// describe the distribution of the private hand's strength. Two hands in the same bucket can
+// be on a dry K72 rainbow board or a wet JT9 monotone one, and the right strategy there is
+// usually different. With board_texture set in params.toml, the texture becomes part of the
+// card bucket of every postflop infoset.
+
+use crate::card_utils::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub const NUM_BOARD_TEXTURES: usize = 81;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Suitedness {
+    Rainbow,
+    // Two cards of one suit, so a flush draw is possible
+    TwoTone,
+    // Three or more cards of one suit, so a flush is possible
+    Monotone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pairing {
+    Unpaired,
+    Paired,
+    // Two pair, trips or a full house on the board
+    DoublePaired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Connectivity {
+    Disconnected,
+    // Three ranks fit in a straight, so a straight is possible
+    Connected,
+    // Four ranks fit in a straight, so a single card makes one
+    HighlyConnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HighCard {
+    // Nine or lower
+    Low,
+    // Ten to king
+    Broadway,
+    Ace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BoardTexture {
+    pub suitedness: Suitedness,
+    pub pairing: Pairing,
+    pub connectivity: Connectivity,
+    pub high_card: HighCard,
+}
+
+impl BoardTexture {
+    // An error instead of a panic for boards that aren't a flop, turn or river, like boards
+    // from the GUI
+    pub fn try_new(board: &[Card]) -> Result<BoardTexture, String> {
+        if !(3..=5).contains(&board.len()) || !board.iter().all_unique() {
+            return Err(format!("Bad board {}", cards2str(board)));
+        }
+        Ok(BoardTexture::new(board))
+    }
+
+    pub fn new(board: &[Card]) -> BoardTexture {
+        assert!(board.len() >= 3, "Board textures start on the flop");
+        let mut suit_counts = [0; 4];
+        let mut rank_counts = [0; 15];
+        for card in board {
+            suit_counts[card.suit as usize] += 1;
+            rank_counts[card.rank as usize] += 1;
+        }
+
+        let suitedness = match *suit_counts.iter().max().unwrap() {
+            1 => Suitedness::Rainbow,
+            2 => Suitedness::TwoTone,
+            _ => Suitedness::Monotone,
+        };
+
+        let pairs = rank_counts.iter().filter(|&&n| n == 2).count();
+        let trips = rank_counts.iter().any(|&n| n >= 3);
+        let pairing = if trips || pairs >= 2 {
+            Pairing::DoublePaired
+        } else if pairs == 1 {
+            Pairing::Paired
+        } else {
+            Pairing::Unpaired
+        };
+
+        // The ace also plays below the lowest rank of the deck, as in A2345 or A6789
+        let mut ranks: u32 = 0;
+        for card in board {
+            ranks |= 1 << card.rank;
+            if card.rank == 14 {
+                ranks |= 1 << (lowest_rank() - 1);
+            }
+        }
+        let most_in_a_straight = (lowest_rank() - 1..=10)
+            .map(|low| (ranks >> low & 0x1F).count_ones())
+            .max()
+            .unwrap();
+        let connectivity = match most_in_a_straight {
+            0..=2 => Connectivity::Disconnected,
+            3 => Connectivity::Connected,
+            _ => Connectivity::HighlyConnected,
+        };
+
+        let high_card = match board.iter().map(|c| c.rank).max().unwrap() {
+            14 => HighCard::Ace,
+            10..=13 => HighCard::Broadway,
+            _ => HighCard::Low,
+        };
+
+        BoardTexture {
+            suitedness,
+            pairing,
+            connectivity,
+            high_card,
+        }
+    }
+
+    // A distinct number in 0..NUM_BOARD_TEXTURES for each texture
+    pub fn id(&self) -> usize {
+        ((self.suitedness as usize * 3 + self.pairing as usize) * 3 + self.connectivity as usize)
+            * 3
+            + self.high_card as usize
+    }
+}
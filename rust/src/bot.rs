@@ -1,5 +1,6 @@
 use crate::card_utils::*;
 use crate::config::CONFIG;
+use crate::fallback::fallback_strategy;
 use crate::nodes::*;
 use crate::trainer::*;
 use crate::trainer_utils::*;
@@ -21,6 +22,7 @@ pub struct Bot {
 
 impl Bot {
     pub fn new(blueprint: Nodes, subgame_solving: bool, depth_limit: i32) -> Bot {
+        CONFIG.require_two_hole_cards("The bot");
         Bot {
             blueprint,
             preflop_cache: Cache::new(10_000),
@@ -74,12 +76,31 @@ impl Bot {
         hole: &[Card],
         board: &[Card],
         history: &ActionHistory,
+    ) -> Strategy {
+        self.translated_strategy(hole, board, history, self.subgame_solving)
+    }
+
+    // Falls back to solving the subgame for infosets missing from the blueprint only if
+    // allow_subgame is set, which it isn't within subgame solving itself
+    fn translated_strategy(
+        &self,
+        hole: &[Card],
+        board: &[Card],
+        history: &ActionHistory,
+        allow_subgame: bool,
     ) -> Strategy {
         debug_assert!(hole.len() == 2);
         // Only look at board cards for this street
         let board = &board[..board_length(history.street)];
         let translated = history.translate(&CONFIG.bet_abstraction);
-        let node_strategy = self.blueprint.get_strategy(hole, board, &translated);
+        let node_strategy = match self.blueprint.try_get_strategy(hole, board, &translated) {
+            Some(strategy) => strategy,
+            None => {
+                let subgame = || self.unsafe_nested_subgame_solving(hole, board, history);
+                let subgame: Option<&dyn Fn() -> Strategy> = allow_subgame.then_some(&subgame);
+                fallback_strategy(&self.blueprint, hole, board, &translated, subgame)
+            }
+        };
         let adjusted_strategy: Strategy = node_strategy
             .iter()
             .map(|(action, prob)| (history.adjust_action(&action), prob.clone()))
@@ -110,7 +131,7 @@ impl Bot {
         // (2) Solve the subgame given the beliefs from (1)
 
         let get_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
-            self.get_strategy_action_translation(hole, board, history)
+            self.translated_strategy(hole, board, history, false)
         };
         let nodes = Nodes::new(&CONFIG.bet_abstraction);
         let infoset = InfoSet::from_hand(&hole, &board, history);
@@ -124,8 +145,7 @@ impl Bot {
             for (i, preflop_hand) in preflop_hands.iter().enumerate() {
                 // TODO: Don't need to call action translation multiple times. Just a single
                 // translated history will do.
-                let strat =
-                    self.get_strategy_action_translation(preflop_hand, &board, &history_iter);
+                let strat = self.translated_strategy(preflop_hand, &board, &history_iter, false);
                 let prob = strat
                     .get(&action)
                     .expect(format!("Action {} not in strategy {:?}", action, strat).as_str());
@@ -216,6 +236,7 @@ impl Bot {
                         &nodes,
                         self.depth_limit,
                         Some(&self.blueprint),
+                        false,
                     );
                 }
                 bar.inc(1);
@@ -0,0 +1,115 @@
+// Text views of a blueprint for the browse CLI: one node in detail, the preflop strategy as a
+// 13x13 grid, and the strategy of every card bucket at a postflop history.
+
+use crate::card_utils::*;
+use crate::nodes::*;
+use crate::trainer_utils::*;
+use std::fmt::Write;
+
+// The hands of the preflop grid, from aces in the top left down to the lowest pair in the
+// bottom right. Suited hands are above the diagonal and offsuit hands below it.
+pub fn preflop_grid_hands() -> Vec<Vec<(String, [Card; 2])>> {
+    let ranks: Vec<u8> = (lowest_rank()..=14).rev().collect();
+    ranks
+        .iter()
+        .enumerate()
+        .map(|(row, &row_rank)| {
+            ranks
+                .iter()
+                .enumerate()
+                .map(|(col, &col_rank)| {
+                    let (high, low) = (row_rank.max(col_rank), row_rank.min(col_rank));
+                    let (name, low_suit) = match row.cmp(&col) {
+                        std::cmp::Ordering::Equal => ("", "d"),
+                        std::cmp::Ordering::Less => ("s", "s"),
+                        std::cmp::Ordering::Greater => ("o", "d"),
+                    };
+                    let high_card = Card::new(&format!("{}s", rank_str(high)));
+                    let low_card = Card::new(&format!("{}{low_suit}", rank_str(low)));
+                    let name = format!("{}{}{name}", rank_str(high), rank_str(low));
+                    (name, [high_card, low_card])
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The node's actions with their average and current strategy and regret
+pub fn format_node(actions: &[Action], node: &Node) -> String {
+    let average = node.cumulative_strategy();
+    let current = node.current_strategy();
+    let mut table = format!("t = {}\n", node.t);
+    writeln!(
+        table,
+        "{:<12} {:>8} {:>8} {:>12}",
+        "Action", "Average", "Current", "Regret"
+    )
+    .unwrap();
+    for (i, action) in actions.iter().enumerate() {
+        writeln!(
+            table,
+            "{:<12} {:>8.3} {:>8.3} {:>12.1}",
+            action.to_string(),
+            average[i],
+            current[i],
+            node.regrets[i]
+        )
+        .unwrap();
+    }
+    table
+}
+
+// One grid per action with the percent of the time each hand takes it
+pub fn format_preflop_grid(nodes: &Nodes, history: &ActionHistory) -> String {
+    let actions = history.next_actions(&nodes.bet_abstraction);
+    let strategies: Vec<Vec<(String, Option<SmallVecFloats>)>> = preflop_grid_hands()
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(name, hole)| {
+                    let node = nodes.get(&nodes.infoset(&hole, &[], history));
+                    (name, node.map(|n| n.cumulative_strategy()))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut grids = String::new();
+    for (i, action) in actions.iter().enumerate() {
+        writeln!(grids, "{action}").unwrap();
+        for row in &strategies {
+            for (name, strategy) in row {
+                match strategy {
+                    Some(strategy) => write!(grids, " {name:>3} {:>3.0}", strategy[i] * 100.0),
+                    None => write!(grids, " {name:>3}   -"),
+                }
+                .unwrap();
+            }
+            writeln!(grids).unwrap();
+        }
+        writeln!(grids).unwrap();
+    }
+    grids
+}
+
+// The visits and average strategy of each card bucket at a history
+pub fn format_bucket_table(nodes: &Nodes, history: &ActionHistory) -> String {
+    let history_nodes = match nodes.dashmap.get(history) {
+        Some(history_nodes) => history_nodes,
+        None => return "Training never reached this history.\n".to_string(),
+    };
+    let mut table = format!("{:>6} {:>8}", "Bucket", "t");
+    for action in history.next_actions(&nodes.bet_abstraction) {
+        write!(table, " {:>10}", action.to_string()).unwrap();
+    }
+    writeln!(table).unwrap();
+    for (bucket, node) in history_nodes.iter().enumerate() {
+        let node = node.lock().unwrap();
+        write!(table, "{bucket:>6} {:>8}", node.t).unwrap();
+        for p in node.cumulative_strategy() {
+            write!(table, " {p:>10.3}").unwrap();
+        }
+        writeln!(table).unwrap();
+    }
+    table
+}
@@ -4,53 +4,230 @@
 // abstraction id number, so we can treat similar hands as the same to reduce
 // the number of possibilities in the game.
 
-use crate::config::CONFIG;
+use crate::board_texture::*;
+use crate::clustering::*;
+use crate::config::{PreflopAbstraction, Variant, CONFIG};
+use crate::hand_indexer::*;
+use crate::trainer_utils::{FLOP, PREFLOP, RIVER, TURN};
 use crate::{card_utils::*, ABSTRACTION};
 use ahash::AHashMap as HashMap;
 use dashmap::DashMap;
 use itertools::Itertools;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use rand::prelude::*;
 use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
 use smallvec::ToSmallVec;
-use std::sync::Mutex;
 use std::{
-    fs::File,
-    io::{BufReader, BufWriter},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
     path::Path,
 };
 
 pub static RIVER_EQUITY_CACHE: Lazy<DashMap<SmallVecHand, f64>> = Lazy::new(DashMap::new);
 
-pub const FLOP_ABSTRACTION_PATH: &str = "products/flop_abstraction.bin";
-pub const TURN_ABSTRACTION_PATH: &str = "products/turn_abstraction.bin";
-pub const RIVER_ABSTRACTION_PATH: &str = "products/river_abstraction.bin";
+const STREET_NAMES: [&str; 4] = ["preflop", "flop", "turn", "river"];
 
+// The 8 preflop clusters of OCHS, from
+// https://webdocs.cs.ualberta.ca/~bowling/papers/13aamas-abstraction.pdf Table 1
+const OCHS_PREFLOP_CLUSTERS: [&[&str]; 8] = [
+    &[
+        "23s", "24s", "25s", "26s", "27s", "32o", "34s", "35s", "36s", "37s", "42o", "43o", "45s",
+        "46s", "52o", "53o", "54o", "62o", "63o", "64o", "65o", "72o", "73o", "74o", "82o", "83o",
+    ],
+    &[
+        "82s", "92s", "T2s", "83s", "93s", "74s", "84s", "94s", "75o", "84o", "85o", "92o", "93o",
+        "94o", "95o", "T2o", "T3o", "T4o", "T5o", "J2o", "J3o",
+    ],
+    &[
+        "56s", "57s", "58s", "59s", "5Ts", "3Ts", "4Ts", "67s", "68s", "69s", "6Ts", "78s", "79s",
+        "89s", "76o", "86o", "87o", "96o", "97o", "98o", "T6o", "T7o", "T8o",
+    ],
+    &[
+        "J2s", "Q2s", "K2s", "J3s", "Q3s", "J4s", "Q4s", "J5s", "Q5s", "J6s", "J4o", "J5o", "J6o",
+        "J7o", "Q2o", "Q3o", "Q4o", "Q5o", "Q6o", "Q7o", "K2o", "K3o", "K4o",
+    ],
+    &[
+        "Q6s", "T7s", "J7s", "Q7s", "T8s", "J8s", "Q8s", "T9s", "J9s", "Q9s", "JTs", "9To", "J8o",
+        "J9o", "JTo", "Q8o", "QTo", "Q9o", "QJo", "22o",
+    ],
+    &[
+        "A2s", "K3s", "A3s", "K4s", "A4s", "K5s", "A5s", "K6s", "K7s", "K8s", "A6s", "33o", "44o",
+        "55o", "A2o", "A3o", "A4o", "A5o", "A6o", "A7o", "A8o", "K5o", "K6o", "K7o", "K8o", "K9o",
+    ],
+    &[
+        "66o", "77o", "A7s", "K9s", "A9s", "A8s", "ATs", "AJs", "AQs", "AKs", "KTs", "KJs", "KQs",
+        "AKo", "QTs", "QJs", "QKo", "AQo", "KJo", "AJo", "KTo", "ATo", "A9o",
+    ],
+    &["88o", "99o", "TTo", "JJo", "QQo", "KKo", "AAo"],
+];
+
+// The OCHS cluster of each lossless preflop bucket
+static PREFLOP_CLUSTERS: Lazy<Vec<i32>> = Lazy::new(|| {
+    let mut clusters = vec![-1; 169];
+    for (i, cluster) in OCHS_PREFLOP_CLUSTERS.iter().enumerate() {
+        for hand in cluster.iter() {
+            let chars: Vec<char> = hand.chars().collect();
+            let second_suit = if chars[2] == 's' { 'c' } else { 'd' };
+            let cards = str2cards(&format!("{}c{}{second_suit}", chars[0], chars[1]));
+            clusters[Abstraction::lossless_preflop_bin(&cards) as usize] = i as i32;
+        }
+    }
+    assert!(
+        !clusters.contains(&-1),
+        "A preflop hand is missing from the OCHS clusters"
+    );
+    clusters
+});
+
+// The buckets of each street are stored in a flat array indexed by the street's hand indexer
+#[derive(Debug)]
 pub struct Abstraction {
-    flop: HashMap<u64, i32>,
-    turn: HashMap<u64, i32>,
-    river: HashMap<u64, i32>,
+    flop: Vec<u16>,
+    turn: Vec<u16>,
+    river: Vec<u16>,
+    // Number of buckets in each street's table, which is too slow to count on every lookup
+    hand_buckets: [usize; 4],
 }
 
 impl Abstraction {
     pub fn new() -> Abstraction {
+        Abstraction::try_new().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    // Loads the abstraction in abstraction_dir, or says why it can't be used
+    pub fn try_new() -> Result<Abstraction, String> {
+        if CONFIG.variant() == Variant::Omaha {
+            // Omaha hands are bucketed directly, so there are no tables to load
+            return Ok(Abstraction::from_tables(Vec::new(), Vec::new(), Vec::new()));
+        }
+        // Streets after last_street are never dealt, so their tables aren't needed
+        let load = |street| {
+            if street <= CONFIG.last_street() {
+                load_abstraction(street)
+            } else {
+                Ok(Vec::new())
+            }
+        };
+        Ok(Abstraction::from_tables(
+            load(FLOP)?,
+            load(TURN)?,
+            load(RIVER)?,
+        ))
+    }
+
+    fn from_tables(flop: Vec<u16>, turn: Vec<u16>, river: Vec<u16>) -> Abstraction {
+        let count = |table: &[u16]| table.iter().max().map_or(0, |&b| b as usize + 1);
+        let hand_buckets = [0, count(&flop), count(&turn), count(&river)];
         Abstraction {
-            flop: load_abstraction(FLOP_ABSTRACTION_PATH, 5, CONFIG.flop_buckets),
-            turn: load_abstraction(TURN_ABSTRACTION_PATH, 6, CONFIG.turn_buckets),
-            river: load_abstraction(RIVER_ABSTRACTION_PATH, 7, CONFIG.river_buckets),
+            flop,
+            turn,
+            river,
+            hand_buckets,
+        }
+    }
+
+    // Loads the flop, turn and river abstraction files from a directory, so that an
+    // abstraction other than the one in abstraction_dir can be evaluated.
+    pub fn load(dir: &str) -> Abstraction {
+        let load = |name: &str, street: usize| {
+            let path = format!("{dir}/{name}_abstraction.bin");
+            assert!(
+                Path::new(&path).exists(),
+                "Abstraction file {path} not found"
+            );
+            read_abstraction(&path, street)
+        };
+        Abstraction::from_tables(load("flop", FLOP), load("turn", TURN), load("river", RIVER))
+    }
+
+    // The bucket of every isomorphic hand on a postflop street, by its hand index
+    pub fn table(&self, street: usize) -> &[u16] {
+        match street {
+            FLOP => &self.flop,
+            TURN => &self.turn,
+            RIVER => &self.river,
+            _ => panic!("Bad street"),
         }
     }
 
+    // Number of buckets in the infosets of a street, including the board texture
+    pub fn num_buckets(&self, street: usize) -> usize {
+        if street == PREFLOP {
+            num_preflop_buckets()
+        } else {
+            with_board_texture(self.num_hand_buckets(street))
+        }
+    }
+
+    // Number of buckets the hands of a postflop street are clustered into
+    pub fn num_hand_buckets(&self, street: usize) -> usize {
+        self.hand_buckets[street]
+    }
+
     pub fn bin(&self, cards: &[Card]) -> i32 {
-        if cards.len() == 2 {
-            Abstraction::preflop_bin(cards)
+        let bin = if CONFIG.variant() == Variant::Omaha {
+            Abstraction::omaha_bin(&cards[..4], &cards[4..])
+        } else if cards.len() == 2 {
+            Abstraction::preflop_bin(cards, CONFIG.preflop_abstraction())
         } else {
             self.postflop_bin(cards)
+        };
+        let hole_cards = CONFIG.hole_cards();
+        if CONFIG.board_texture && cards.len() > hole_cards {
+            let texture = BoardTexture::new(&cards[hole_cards..]).id();
+            bin * NUM_BOARD_TEXTURES as i32 + texture as i32
+        } else {
+            bin
+        }
+    }
+
+    // There are far too many Omaha hands for the hold'em abstractions, so they are bucketed
+    // coarsely. Preflop hands go into one of 169 buckets by their two highest cards and
+    // whether any two cards are suited, like the hold'em preflop buckets. After the flop, hands
+    // are bucketed by their made hand category and its highest rank.
+    fn omaha_bin(hole: &[Card], board: &[Card]) -> i32 {
+        if board.is_empty() {
+            let mut ranks: Vec<i32> = hole.iter().map(|c| c.rank as i32 - 2).collect();
+            ranks.sort_by(|a, b| b.cmp(a));
+            let suited = hole
+                .iter()
+                .enumerate()
+                .any(|(i, a)| hole[i + 1..].iter().any(|b| a.suit == b.suit));
+            if suited {
+                ranks[1] * 13 + ranks[0]
+            } else {
+                ranks[0] * 13 + ranks[1]
+            }
+        } else {
+            let strength = omaha_hand_strength(hole, board);
+            let category = strength / 100_000_000;
+            let mut top_rank = strength % 100_000_000;
+            while top_rank >= 16 {
+                top_rank >>= 4;
+            }
+            category * 13 + top_rank - 2
+        }
+    }
+
+    pub fn preflop_bin(cards: &[Card], preflop_abstraction: PreflopAbstraction) -> i32 {
+        match preflop_abstraction {
+            PreflopAbstraction::Lossless => Abstraction::lossless_preflop_bin(cards),
+            PreflopAbstraction::SuitAware => {
+                // Index the pair of cards among all 1326 pairs from the full deck
+                let id = |card: &Card| (card.rank as i32 - 2) * 4 + card.suit as i32;
+                let (a, b) = (id(&cards[0]), id(&cards[1]));
+                let (low, high) = (a.min(b), a.max(b));
+                high * (high - 1) / 2 + low
+            }
+            PreflopAbstraction::Clustered => {
+                PREFLOP_CLUSTERS[Abstraction::lossless_preflop_bin(cards) as usize]
+            }
         }
     }
 
     // Map each possible preflop hand to an integer in (0..169)
-    fn preflop_bin(cards: &[Card]) -> i32 {
+    fn lossless_preflop_bin(cards: &[Card]) -> i32 {
         let mut cards = cards.to_vec();
         cards.sort_by_key(|c| c.rank);
         // Ranks start at 2, so shift it to start at 0
@@ -65,45 +242,209 @@ impl Abstraction {
     }
 
     fn postflop_bin(&self, cards: &[Card]) -> i32 {
-        let isomorphic = isomorphic_hand(cards);
-        let hand = cards2hand(&isomorphic);
-        let bin_result = match cards.len() {
-            5 => self.flop.get(&hand),
-            6 => self.turn.get(&hand),
-            // TODO: Something's wrong here, AHashMap shouldn't be so slow.
-            // Try a different hashmap library?
-            7 => self.river.get(&hand),
+        let bin = match cards.len() {
+            5 => self.flop[FLOP_INDEXER.index(cards) as usize],
+            6 => self.turn[TURN_INDEXER.index(cards) as usize],
+            7 => self.river[RIVER_INDEXER.index(cards) as usize],
             _ => panic!("Bad number of cards"),
         };
-        *bin_result.unwrap()
+        bin as i32
     }
 }
 
-fn load_abstraction(path: &str, n_cards: usize, n_buckets: i32) -> HashMap<u64, i32> {
-    match File::open(path) {
-        Err(_error) => make_abstraction(n_cards, n_buckets),
-        Ok(_) => {
-            let abstraction = read_serialized(path);
-            assert!(
-                {
-                    let max_bucket = abstraction.values().max().unwrap().clone();
-                    let min_bucket = abstraction.values().min().unwrap().clone();
-                    max_bucket >= n_buckets - 10 && min_bucket == 0
-                },
-                "Number of {n_cards} abstraction buckets in params.toml does not match the abstraction file."
-            );
-            abstraction
+// Omaha preflop hands are always bucketed into the 169 lossless-style buckets of omaha_bin
+pub fn num_preflop_buckets() -> usize {
+    if CONFIG.variant() == Variant::Omaha {
+        return num_omaha_buckets(PREFLOP);
+    }
+    match CONFIG.preflop_abstraction() {
+        PreflopAbstraction::Lossless => 169,
+        PreflopAbstraction::SuitAware => 1326,
+        PreflopAbstraction::Clustered => OCHS_PREFLOP_CLUSTERS.len(),
+    }
+}
+
+// Postflop buckets are split up by board texture when board_texture is set in params.toml
+// Number of buckets omaha_bin puts the hands of a street into, including the board texture.
+// Postflop there's one for each of the 9 hand categories and 13 top ranks, whatever the
+// configured bucket counts are.
+pub fn num_omaha_buckets(street: usize) -> usize {
+    if street == PREFLOP {
+        169
+    } else {
+        with_board_texture(9 * 13)
+    }
+}
+
+pub fn with_board_texture(hand_buckets: usize) -> usize {
+    if CONFIG.board_texture {
+        hand_buckets * NUM_BOARD_TEXTURES
+    } else {
+        hand_buckets
+    }
+}
+
+static GLOBAL_ABSTRACTION: OnceCell<Abstraction> = OnceCell::new();
+
+// Loads the abstraction behind ABSTRACTION. Binaries call this before they start, so that a
+// missing or mismatched abstraction is reported as an error instead of a panic in whichever
+// thread first buckets a hand.
+pub fn load_global_abstraction() -> Result<&'static Abstraction, String> {
+    GLOBAL_ABSTRACTION.get_or_try_init(Abstraction::try_new)
+}
+
+// The abstraction is built ahead of time by the abstraction binary. Building it here, inside
+// the ABSTRACTION initializer, would block every thread waiting on the Lazy cell.
+fn load_abstraction(street: usize) -> Result<Vec<u16>, String> {
+    let path = abstraction_path(street);
+    if !Path::new(&path).exists() {
+        return Err(format!(
+            "{path} not found. Build it with `cargo run --release --bin abstraction -- build`."
+        ));
+    }
+    let abstraction = read_abstraction(&path, street);
+    let problems = validate_street_abstraction(&abstraction, street);
+    if !problems.is_empty() {
+        return Err(format!("{path}: {}", problems.join("\n")));
+    }
+    Ok(abstraction)
+}
+
+// Every abstraction file and the intermediate results used to build it live in
+// abstraction_dir from params.toml, in a subdirectory for variants other than hold'em.
+pub fn abstraction_file(name: &str) -> String {
+    variant_path(&format!("products/{name}")).replacen("products", &CONFIG.abstraction_dir, 1)
+}
+
+pub fn abstraction_path(street: usize) -> String {
+    abstraction_file(&format!("{}_abstraction.bin", STREET_NAMES[street]))
+}
+
+pub fn configured_buckets(street: usize) -> usize {
+    match street {
+        FLOP => CONFIG.flop_buckets as usize,
+        TURN => CONFIG.turn_buckets as usize,
+        RIVER => CONFIG.river_buckets as usize,
+        _ => panic!("Bad street"),
+    }
+}
+
+// k-means can leave a few clusters empty, so the highest bucket of an abstraction may be up to
+// this many short of the number of buckets in params.toml
+const MAX_MISSING_BUCKETS: usize = 10;
+
+// Checks that a street's abstraction has a bucket for every hand index, and about as many
+// buckets as params.toml asks for.
+pub fn validate_street_abstraction(table: &[u16], street: usize) -> Vec<String> {
+    let name = STREET_NAMES[street];
+    let n_buckets = configured_buckets(street);
+    let size = street_indexer(street).size() as usize;
+    if table.len() != size {
+        return vec![format!(
+            "The {name} abstraction has {} hands instead of {size}",
+            table.len()
+        )];
+    }
+    let mut problems = Vec::new();
+    let missing = table.iter().filter(|&&b| b == u16::MAX).count();
+    if missing > 0 {
+        problems.push(format!("The {name} abstraction is missing {missing} hands"));
+    }
+    let num_buckets = table
+        .iter()
+        .filter(|&&b| b != u16::MAX)
+        .max()
+        .map_or(0, |&b| b as usize + 1);
+    if num_buckets > n_buckets || num_buckets + MAX_MISSING_BUCKETS < n_buckets {
+        problems.push(format!(
+            "The {name} abstraction has {num_buckets} buckets, but params.toml has {n_buckets}"
+        ));
+    }
+    problems
+}
+
+// Checks the flop, turn and river abstraction files, and prints what's wrong with them.
+// Returns whether they are all usable.
+pub fn validate_abstraction() -> bool {
+    let mut valid = true;
+    for street in [FLOP, TURN, RIVER] {
+        let path = abstraction_path(street);
+        if !Path::new(&path).exists() {
+            println!("{path}: not built yet");
+            valid = false;
+            continue;
+        }
+        let table = read_abstraction(&path, street);
+        let problems = validate_street_abstraction(&table, street);
+        if problems.is_empty() {
+            let mut used = vec![false; configured_buckets(street)];
+            for &bucket in &table {
+                used[bucket as usize] = true;
+            }
+            let empty = used.iter().filter(|&&u| !u).count();
+            println!("{path}: OK ({} buckets, {empty} empty)", used.len());
+        } else {
+            for problem in problems {
+                println!("{path}: {problem}");
+            }
+            valid = false;
         }
     }
+    valid
+}
+
+// Writes to a temporary file first, so that a run that crashes partway through a write
+// doesn't leave behind a truncated file that a resumed run would take for finished work.
+pub fn write_atomically<T: Serialize>(value: &T, path: &str) {
+    let tmp_path = format!("{path}.tmp");
+    let mut buffer = BufWriter::new(create_file(&tmp_path));
+    bincode::serialize_into(&mut buffer, value).unwrap();
+    buffer.flush().unwrap();
+    drop(buffer);
+    fs::rename(&tmp_path, path).unwrap();
+}
+
+fn read_cached<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let file = File::open(path).ok()?;
+    Some(bincode::deserialize_from(BufReader::new(file)).unwrap())
+}
+
+// Reads a street's abstraction. Abstraction files written before the hand indexer are hash
+// maps from isomorphic hands to buckets, so those are converted and rewritten.
+pub fn read_abstraction(path: &str, street: usize) -> Vec<u16> {
+    let size = street_indexer(street).size();
+    let file_size = std::fs::metadata(path).unwrap().len();
+    // bincode writes the length of the vector followed by its elements
+    if file_size == 8 + 2 * size {
+        let reader = BufReader::new(File::open(path).unwrap());
+        return bincode::deserialize_from(reader).unwrap();
+    }
+    println!("[INFO] Converting {path} to the hand indexer.");
+    let table = read_serialized(path);
+    save_abstraction(&table, street, path)
+}
+
+// Converts an abstraction from isomorphic hands to buckets into a flat array by hand index,
+// and writes it to disk.
+pub fn save_abstraction(table: &HashMap<u64, i32>, street: usize, path: &str) -> Vec<u16> {
+    let indexer = street_indexer(street);
+    let mut abstraction: Vec<u16> = vec![u16::MAX; indexer.size() as usize];
+    for (&hand, &bucket) in table.iter() {
+        let index = indexer.index(&hand2cards(hand));
+        abstraction[index as usize] = u16::try_from(bucket).expect("Too many buckets");
+    }
+    assert!(
+        !abstraction.contains(&u16::MAX),
+        "Abstraction is missing some hands"
+    );
+    write_atomically(&abstraction, path);
+    abstraction
 }
 
 // Returns all isomorphic hands in sorted order by E[HS^2]
 pub fn get_sorted_hand_ehs2(n_cards: usize) -> Vec<u64> {
-    let path = format!("products/ehs2_{n_cards}.bin");
-    if Path::new(&path).exists() {
-        let file = File::open(path.as_str()).unwrap();
-        let reader = BufReader::new(file);
-        let ehs2: Vec<u64> = bincode::deserialize_from(reader).unwrap();
+    let path = abstraction_file(&format!("ehs2_{n_cards}.bin"));
+    if let Some(ehs2) = read_cached(&path) {
         return ehs2;
     }
 
@@ -130,28 +471,20 @@ pub fn get_sorted_hand_ehs2(n_cards: usize) -> Vec<u64> {
     hand_ehs2.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
     let sorted_hands: Vec<u64> = hand_ehs2.iter().map(|(hand, _ehs2)| *hand).collect();
 
-    let buffer = File::create(path.as_str()).unwrap();
-    bincode::serialize_into(buffer, &sorted_hands).unwrap();
+    write_atomically(&sorted_hands, &path);
     sorted_hands
 }
 
 pub fn get_hand_counts(n_cards: usize) -> HashMap<u64, i32> {
-    let path = format!("products/hand_counts_{n_cards}.bin");
-
-    if Path::new(&path).exists() {
-        let hand_counts = read_serialized(&path);
+    let path = abstraction_file(&format!("hand_counts_{n_cards}.bin"));
+    if let Some(hand_counts) = read_cached(&path) {
         return hand_counts;
     }
 
     println!("[INFO] Getting {n_cards} hand counts...");
     let deck = deck();
     let mut hand_counts: HashMap<u64, i32> = HashMap::new();
-    let bar = pbar(match n_cards {
-        5 => 25_989_600,
-        6 => 305_377_800,
-        7 => 2_809_475_760,
-        _ => 0,
-    });
+    let bar = pbar(n_deals(n_cards));
     for preflop in deck.iter().combinations(2) {
         let mut rest_of_deck = deck.clone();
         rest_of_deck.retain(|c| !preflop.contains(&c));
@@ -168,11 +501,11 @@ pub fn get_hand_counts(n_cards: usize) -> HashMap<u64, i32> {
     }
     bar.finish();
 
-    serialize(hand_counts.clone(), path.as_str());
+    write_atomically(&hand_counts, &path);
     hand_counts
 }
 
-pub fn make_abstraction(n_cards: usize, n_buckets: i32) -> HashMap<u64, i32> {
+pub fn make_abstraction(n_cards: usize, n_buckets: i32) -> Vec<u16> {
     match n_cards {
         5 => println!("[INFO] Preparing the flop abstraction."),
         6 => println!("[INFO] Preparing the turn abstraction."),
@@ -182,15 +515,7 @@ pub fn make_abstraction(n_cards: usize, n_buckets: i32) -> HashMap<u64, i32> {
     let hand_ehs2 = get_sorted_hand_ehs2(n_cards);
     let hand_counts = get_hand_counts(n_cards);
     let total_hands: u64 = hand_counts.values().map(|n| n.clone() as u64).sum();
-    debug_assert!(
-        total_hands
-            == match n_cards {
-                5 => 25_989_600,
-                6 => 305_377_800,
-                7 => 2_809_475_760,
-                _ => 0,
-            }
-    );
+    debug_assert!(total_hands == n_deals(n_cards) as u64);
     let mut clusters = HashMap::new();
     let mut sum: u64 = 0;
     let bar = pbar(hand_ehs2.len());
@@ -210,14 +535,18 @@ pub fn make_abstraction(n_cards: usize, n_buckets: i32) -> HashMap<u64, i32> {
         bar.inc(1);
     }
     bar.finish();
-    let path = match n_cards {
-        5 => FLOP_ABSTRACTION_PATH,
-        6 => TURN_ABSTRACTION_PATH,
-        7 => RIVER_ABSTRACTION_PATH,
-        _ => panic!("Bad hand length"),
-    };
-    serialize(clusters.clone(), path);
-    clusters
+    save_abstraction(&clusters, n_cards - 4, &abstraction_path(n_cards - 4))
+}
+
+// Buckets each street by E[HS^2] percentile. Streets that are already built are skipped.
+pub fn create_ehs2_abstraction() {
+    for street in [FLOP, TURN, RIVER] {
+        if Path::new(&abstraction_path(street)).exists() {
+            println!("[INFO] Using the existing {}.", abstraction_path(street));
+        } else {
+            make_abstraction(street + 4, configured_buckets(street) as i32);
+        }
+    }
 }
 
 // Returns the second moment of the hand's equity distribution.
@@ -328,7 +657,7 @@ pub fn river_equity(hand: &[Card]) -> f64 {
 pub fn bucket_sizes() {
     let abs = Abstraction::new();
     let mut lens: Vec<i32> = vec![0; CONFIG.flop_buckets as usize];
-    for (hand, bucket) in abs.turn {
+    for bucket in abs.turn {
         lens[bucket as usize] += 1;
     }
     println!("Hands per bucket: {:?}", lens);
@@ -341,12 +670,12 @@ pub fn print_abstraction() {
     for bucket in 0..CONFIG.turn_buckets {
         println!("\nBucket {bucket}");
         for sample in 0..10 {
-            let mut hands: Vec<&u64> = abs.turn.keys().collect();
+            let mut hands: Vec<usize> = (0..abs.turn.len()).collect();
             hands.shuffle(&mut rand::thread_rng());
             for hand in hands {
-                let b = abs.turn.get(hand).unwrap().clone();
-                if b == bucket {
-                    println!("{}", hand2str(hand.clone()));
+                if abs.turn[hand] as i32 == bucket {
+                    let cards = TURN_INDEXER.unindex(hand as u64);
+                    println!("{}", hand2str(cards2hand(&cards)));
                     break;
                 }
             }
@@ -354,50 +683,90 @@ pub fn print_abstraction() {
     }
 }
 
+// Clusters the flop and turn by their equity distributions, and the river by OCHS.
+// Each street's buckets are numbered in order of their mean equity.
 pub fn create_abstraction_clusters() {
-    let dists = get_equity_distributions("flop");
-    let buckets = k_means_cluster(dists, CONFIG.flop_buckets, true);
-    let hands = load_flop_isomorphic();
-    let abstraction: HashMap<u64, i32> = hands
-        .iter()
-        .zip(buckets.iter())
-        .map(|(&hand, &bucket)| (hand, bucket))
-        .collect();
-    serialize(abstraction, "products/flop_abstraction.bin");
+    for street in [FLOP, TURN] {
+        cached_clusters(street, || {
+            let dists = get_equity_distributions(STREET_NAMES[street]);
+            let (mut buckets, centers) = k_means(
+                &dists,
+                configured_buckets(street),
+                &Emd1d,
+                CONFIG.k_means_iters,
+            );
+            let centers = sort_clusters(&mut buckets, centers, histogram_equity);
+            let hands = if street == FLOP {
+                load_flop_isomorphic()
+            } else {
+                load_turn_isomorphic()
+            };
+            (hands.into_iter().zip(buckets).collect(), centers)
+        });
+    }
+    cached_clusters(RIVER, || {
+        let dists = get_ochs_distributions();
+        let (mut buckets, centers) =
+            k_means(&dists, configured_buckets(RIVER), &L2, CONFIG.k_means_iters);
+        let centers = sort_clusters(&mut buckets, centers, mean_equity);
+        (
+            load_river_isomorphic().into_iter().zip(buckets).collect(),
+            centers,
+        )
+    });
+}
 
-    let dists = get_equity_distributions("turn");
-    let buckets = k_means_cluster(dists, CONFIG.turn_buckets, true);
-    let hands = load_turn_isomorphic();
-    let abstraction: HashMap<u64, i32> = hands
+// Mean equity of an equity histogram, with each bin at its midpoint
+fn histogram_equity(histogram: &[f32]) -> f32 {
+    let bins = histogram.len() as f32;
+    histogram
         .iter()
-        .zip(buckets.iter())
-        .map(|(&hand, &bucket)| (hand, bucket))
-        .collect();
-    serialize(abstraction, "products/turn_abstraction.bin");
-
-    // let dists = get_ochs_distributions();
+        .enumerate()
+        .map(|(i, p)| p * (i as f32 + 0.5) / bins)
+        .sum()
+}
 
-    let reader = BufReader::new(File::open("products/ochs_distributions.bin").unwrap());
-    let dists = bincode::deserialize_from(reader).unwrap();
+// Mean of the OCHS equities against each preflop cluster
+fn mean_equity(ochs: &[f32]) -> f32 {
+    ochs.iter().sum::<f32>() / ochs.len() as f32
+}
 
-    let buckets = k_means_cluster(dists, CONFIG.river_buckets, false);
-    let hands = load_river_isomorphic();
-    let abstraction: HashMap<u64, i32> = hands
+// Expected equity of a histogram over the next street's buckets
+fn next_street_equity(histogram: &[f32], next_equities: &[f32]) -> f32 {
+    histogram
         .iter()
-        .zip(buckets.iter())
-        .map(|(&hand, &bucket)| (hand, bucket))
-        .collect();
-    serialize(abstraction, "products/river_abstraction.bin");
+        .zip(next_equities)
+        .map(|(p, e)| p * e)
+        .sum()
+}
+
+// Loads a street's abstraction and cluster centers if an earlier run already built them, and
+// otherwise clusters the street and saves both, so a crashed build resumes from the last
+// finished street.
+fn cached_clusters<F>(street: usize, cluster: F) -> (Vec<u16>, Vec<Vec<f32>>)
+where
+    F: FnOnce() -> (HashMap<u64, i32>, Vec<Vec<f32>>),
+{
+    let path = abstraction_path(street);
+    let centers_path = abstraction_file(&format!("{}_centers.bin", STREET_NAMES[street]));
+    if Path::new(&path).exists() {
+        if let Some(centers) = read_cached(&centers_path) {
+            println!("[INFO] Using the existing {path}.");
+            return (read_abstraction(&path, street), centers);
+        }
+    }
+    let (abstraction, centers) = cluster();
+    // The centers are written first, since a street only counts as finished once its
+    // abstraction file exists
+    write_atomically(&centers, &centers_path);
+    (save_abstraction(&abstraction, street, &path), centers)
 }
 
 pub fn expand_abstraction_keys(n_cards: usize) {
     let deck = deck();
     let mut table: HashMap<u64, i32> = HashMap::new();
-    let bar = pbar(match n_cards {
-        5 => 25989600,
-        6 => 305377800,
-        _ => panic!(),
-    });
+    assert!(n_cards == 5 || n_cards == 6);
+    let bar = pbar(n_deals(n_cards));
     for preflop in deck.iter().combinations(2) {
         let mut sorted_preflop: SmallVecHand = preflop.iter().cloned().cloned().collect();
         sorted_preflop.sort_unstable();
@@ -417,61 +786,27 @@ pub fn expand_abstraction_keys(n_cards: usize) {
     }
     bar.finish_with_message("Done.");
     let path = match n_cards {
-        5 => "products/flop_abstraction_large.bin",
-        6 => "products/turn_abstraction_large.bin",
+        5 => "flop_abstraction_large.bin",
+        6 => "turn_abstraction_large.bin",
         _ => panic!(),
     };
-    serialize(table, path);
+    write_atomically(&table, &abstraction_file(path));
 }
 
 pub fn get_ochs_distributions() -> Vec<Vec<f32>> {
+    let path = abstraction_file("ochs_distributions.bin");
+    if let Some(ochs) = read_cached(&path) {
+        return ochs;
+    }
+    println!("[INFO] Computing river OCHS distributions...");
     let iso = load_river_isomorphic();
-    // from https://webdocs.cs.ualberta.ca/~bowling/papers/13aamas-abstraction.pdf Table 1
-    let abstract_preflop_clusters: Vec<Vec<&str>> = vec![
-        vec![
-            "23s", "24s", "25s", "26s", "27s", "32o", "34s", "35s", "36s", "37s", "42o", "43o",
-            "45s", "46s", "52o", "53o", "54o", "62o", "63o", "64o", "65o", "72o", "73o", "74o",
-            "82o", "83o",
-        ],
-        vec![
-            "82s", "92s", "T2s", "83s", "93s", "74s", "84s", "94s", "75o", "84o", "85o", "92o",
-            "93o", "94o", "95o", "T2o", "T3o", "T4o", "T5o", "J2o", "J3o",
-        ],
-        vec![
-            "56s", "57s", "58s", "59s", "5Ts", "3Ts", "34s", "67s", "68s", "69s", "6Ts", "78s",
-            "79s", "89s", "76o", "86o", "87o", "96o", "97o", "98o", "T6o", "T7o", "T8o",
-        ],
-        vec![
-            "J2s", "Q2s", "K2s", "J3s", "Q3s", "J4s", "Q4s", "J5s", "Q5s", "J6s", "J4o", "J5o",
-            "J6o", "J7o", "Q2o", "Q3o", "Q4o", "Q5o", "Q6o", "Q7o", "K2o", "K3o", "K4o",
-        ],
-        vec![
-            "Q6s", "T7s", "J7s", "Q7s", "T8s", "J8s", "Q8s", "T9s", "J9s", "Q9s", "JTs", "9To",
-            "J8o", "J9o", "JTo", "Q8o", "QTo", "Q9o", "QJo", "22o",
-        ],
-        vec![
-            "A2s", "K3s", "A3s", "K4s", "A4s", "K5s", "A5s", "K6s", "K7s", "K8s", "A6s", "33o",
-            "44o", "55o", "A2o", "A3o", "A4o", "A5o", "A6o", "A7o", "A8o", "K5o", "K6o", "K7o",
-            "K8o", "K9o",
-        ],
-        vec![
-            "66o", "77o", "A7s", "K9s", "A9s", "A8s", "ATs", "AJs", "AQs", "AKs", "KTs", "KJs",
-            "KQs", "AKo", "QTs", "QJs", "QKo", "AQo", "KJo", "AJo", "KTo", "ATo", "A9o",
-        ],
-        vec!["88o", "99o", "TTo", "JJo", "QQo", "KKo", "AAo"],
-    ];
-    assert_eq!(
-        abstract_preflop_clusters
-            .iter()
-            .map(|cluster| cluster.len())
-            .sum::<usize>(),
-        169
-    );
-    // Expand the preflop clusters to incorporate all suits, instead of categories
+    // Expand the preflop clusters to incorporate all suits, instead of categories. Hands with
+    // ranks that aren't in the deck (for short deck) are left out.
+    let deck = deck();
     let mut expanded_preflop_clusters: Vec<Vec<[Card; 2]>> = Vec::new();
-    for cluster in abstract_preflop_clusters.iter() {
+    for cluster in OCHS_PREFLOP_CLUSTERS.iter() {
         let mut expanded_cluster: Vec<String> = Vec::new();
-        for hand in cluster {
+        for hand in cluster.iter() {
             let cards = hand.chars().collect::<Vec<char>>();
             let rank1 = cards[0];
             let rank2 = cards[1];
@@ -505,6 +840,7 @@ pub fn get_ochs_distributions() -> Vec<Vec<f32>> {
                 let cards = str2cards(hand_str);
                 [cards[0], cards[1]]
             })
+            .filter(|cards| deck.contains(&cards[0]) && deck.contains(&cards[1]))
             .collect();
         expanded_preflop_clusters.push(expanded_cluster_cards);
     }
@@ -513,7 +849,7 @@ pub fn get_ochs_distributions() -> Vec<Vec<f32>> {
             .iter()
             .map(|cluster| cluster.len())
             .sum::<usize>(),
-        1326
+        n_choose_k(deck.len(), 2)
     );
 
     let bar = pbar(iso.len());
@@ -558,15 +894,13 @@ pub fn get_ochs_distributions() -> Vec<Vec<f32>> {
         .collect();
     bar.finish_with_message("Done");
 
-    let file = File::create(format!("products/ochs_distributions.bin")).unwrap();
-    let buffer = BufWriter::new(file);
-    bincode::serialize_into(buffer, &ochs_features).unwrap();
+    write_atomically(&ochs_features, &path);
     ochs_features
 }
 
 pub fn get_equity_distributions(street: &str) -> Vec<Vec<f32>> {
-    let path = format!("products/{street}_equity_distributions.bin");
-    match File::open(path) {
+    let path = abstraction_file(&format!("{street}_equity_distributions.bin"));
+    match File::open(&path) {
         Err(_error) => {
             println!("[INFO] Computing {street} equity distributions...");
             let iso: Vec<u64> = if street == "flop" {
@@ -585,9 +919,7 @@ pub fn get_equity_distributions(street: &str) -> Vec<Vec<f32>> {
                 .collect();
             bar.finish_with_message("Done.");
 
-            let file = File::create(format!("products/{street}_equity_distributions.bin")).unwrap();
-            let buffer = BufWriter::new(file);
-            bincode::serialize_into(buffer, &dists).unwrap();
+            write_atomically(&dists, &path);
             dists
         }
         Ok(file) => {
@@ -597,135 +929,108 @@ pub fn get_equity_distributions(street: &str) -> Vec<Vec<f32>> {
     }
 }
 
-pub fn k_means_cluster(distributions: Vec<Vec<f32>>, k: i32, use_emd: bool) -> Vec<i32> {
-    assert!(k > 0 && k < 1_000_000_000);
-
-    // k-means++ initialization
-    let mut centers: Vec<Vec<f32>> = Vec::with_capacity(k as usize);
-    let first_center = distributions
+// Potential-aware imperfect recall abstraction (Ganzfried and Sandholm, "Potential-Aware
+// Imperfect-Recall Abstraction with Earth Mover's Distance in Imperfect-Information Games").
+// River hands are clustered by their OCHS equities. Then each turn hand is described by
+// the histogram of river buckets it can reach on the next card, and the turn hands are
+// clustered with the earth mover's distance between those histograms, where the cost of
+// moving mass between two river buckets is the distance between their cluster centers. The
+// flop is clustered the same way over turn buckets.
+pub fn create_potential_aware_abstraction() {
+    if [FLOP, TURN, RIVER]
         .iter()
-        .choose(&mut thread_rng())
-        .expect("Distributions cannot be empty")
-        .to_vec();
-    centers.push(first_center);
-
-    println!("Initializing centroids with k-means++");
-    let bar = pbar(k as usize);
-    for _ in 1..k {
-        let subset = distributions
-            .choose_multiple(&mut thread_rng(), 10000)
-            .cloned()
-            .collect::<Vec<Vec<f32>>>();
-        let distances: Vec<f32> = subset
-            .par_iter()
-            .map(|dist| {
-                centers
-                    .iter()
-                    .map(|center| {
-                        dist.iter()
-                            .zip(center.iter())
-                            .map(|(d, c)| (d - c).powi(2))
-                            .sum::<f32>()
-                            .sqrt()
-                    })
-                    .min_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or(f32::INFINITY)
-            })
-            .collect();
-
-        // Use the squared distances to calculate the sum for the probability distribution
-        let sum: f32 = distances.iter().map(|d| d.powi(2)).sum();
-        let choice = thread_rng().gen_range(0.0..sum);
-        let mut cumulative = 0.0;
-        for (i, &dist) in distances.iter().enumerate() {
-            cumulative += dist;
-            if cumulative >= choice {
-                centers.push(subset[i].clone());
-                break;
-            }
-        }
-        bar.inc(1);
+        .all(|&street| Path::new(&abstraction_path(street)).exists())
+    {
+        println!("[INFO] The abstraction is already built.");
+        return;
     }
-    bar.finish();
+    println!("[INFO] Clustering the river by OCHS.");
+    let (river, river_centers) = cached_clusters(RIVER, || {
+        let ochs = get_ochs_distributions();
+        let (mut buckets, centers) =
+            k_means(&ochs, configured_buckets(RIVER), &L2, CONFIG.k_means_iters);
+        let centers = sort_clusters(&mut buckets, centers, mean_equity);
+        (
+            load_river_isomorphic().into_iter().zip(buckets).collect(),
+            centers,
+        )
+    });
+    let river_ground = GroundEmd::from_centers(&river_centers, &L2);
+    let river_equities: Vec<f32> = river_centers.iter().map(|c| mean_equity(c)).collect();
 
-    let mut clusters: Vec<i32> = vec![0; distributions.len()];
-
-    let iters = CONFIG.k_means_iters;
-    let bar = pbar(iters as usize);
-    let mut prev_distance_sum = 0.0;
-    for iter in 0..iters {
-        let distance_sum: Mutex<f64> = Mutex::new(0.0);
-        let iter_bar = pbar(distributions.len());
-        clusters = distributions
-            .par_iter()
-            .map(|x| {
-                // Find the closest center to each hand
-                let mut closest_center: i32 = 0;
-                let mut closest_distance = f32::INFINITY;
-                for (i, center) in centers.iter().enumerate() {
-                    let distance: f32 = if use_emd {
-                        earth_movers_distance(x, center)
-                    } else {
-                        x.iter()
-                            .zip(center.iter())
-                            .map(|(i, j)| (i - j).powf(2.0))
-                            .sum()
-                    };
-                    if distance < closest_distance {
-                        closest_center = i as i32;
-                        closest_distance = distance;
-                    }
-                }
-                let mut d = distance_sum.lock().unwrap();
-                *d += closest_distance as f64;
-                iter_bar.inc(1);
-                closest_center
-            })
-            .collect();
+    println!("[INFO] Clustering the turn over river buckets.");
+    let (turn, turn_centers) = cached_clusters(TURN, || {
+        cluster_by_next_street(
+            &load_turn_isomorphic(),
+            &river,
+            &RIVER_INDEXER,
+            &river_ground,
+            &river_equities,
+            configured_buckets(TURN),
+        )
+    });
+    let turn_equities: Vec<f32> = turn_centers
+        .iter()
+        .map(|c| next_street_equity(c, &river_equities))
+        .collect();
+    drop(river);
 
-        iter_bar.finish();
-        let distance_sum_val: f64 = *distance_sum.lock().unwrap();
-        println!("Iteration {iter}: {}", distance_sum_val);
-        if (distance_sum_val - prev_distance_sum).abs() < 1e-6 {
-            println!("Converged.");
-            break;
-        }
-        prev_distance_sum = distance_sum_val;
-
-        centers = (0..k)
-            .into_par_iter()
-            .map(|cluster| {
-                // Find the Euclidian mean of each cluster - the new centroid
-                let mut sum: Vec<f32> = vec![0.0; distributions[0].len()];
-                let mut num_points = 0;
-                for i in 0..distributions.len() {
-                    if clusters[i] == cluster {
-                        num_points += 1;
-                        for j in 0..sum.len() {
-                            sum[j] += distributions[i][j];
-                        }
-                    }
-                }
-                let mean: Vec<f32> = sum.iter().map(|x| x / num_points as f32).collect();
-                mean
-            })
-            .collect();
-        bar.inc(1);
-    }
-    bar.finish();
+    // The ground distance between two turn buckets is the EMD between their centers
+    println!("[INFO] Computing turn cluster distances.");
+    let turn_ground = GroundEmd::from_centers(&turn_centers, &river_ground);
 
-    clusters
+    println!("[INFO] Clustering the flop over turn buckets.");
+    cached_clusters(FLOP, || {
+        cluster_by_next_street(
+            &load_flop_isomorphic(),
+            &turn,
+            &TURN_INDEXER,
+            &turn_ground,
+            &turn_equities,
+            configured_buckets(FLOP),
+        )
+    });
 }
 
-fn earth_movers_distance(v1: &Vec<f32>, v2: &Vec<f32>) -> f32 {
-    debug_assert!(v1.len() == v2.len());
-    let mut cdf1 = 0.0;
-    let mut cdf2 = 0.0;
-    let mut emd = 0.0;
-    for i in 0..v1.len() {
-        cdf1 += v1[i];
-        cdf2 += v2[i];
-        emd += (cdf1 - cdf2).abs();
-    }
-    emd
+// Buckets each hand by the distribution of next street buckets it reaches on the next card.
+// Returns the abstraction and the cluster centers, which are histograms over the next
+// street's buckets. The clusters are ordered by the equity of the next street buckets they
+// reach.
+fn cluster_by_next_street(
+    hands: &[u64],
+    next_street: &[u16],
+    next_indexer: &HandIndexer,
+    ground: &GroundEmd,
+    next_equities: &[f32],
+    k: usize,
+) -> (HashMap<u64, i32>, Vec<Vec<f32>>) {
+    let deck = deck();
+    let bar = pbar(hands.len());
+    let histograms: Vec<SparseHistogram> = hands
+        .par_iter()
+        .map(|&hand| {
+            let cards = hand2cards(hand);
+            let mut counts: HashMap<usize, f32> = HashMap::new();
+            let mut total = 0.0;
+            for card in deck.iter().filter(|c| !cards.contains(c)) {
+                let next = [cards.as_slice(), &[*card]].concat();
+                let bucket = next_street[next_indexer.index(&next) as usize];
+                *counts.entry(bucket as usize).or_insert(0.0) += 1.0;
+                total += 1.0;
+            }
+            bar.inc(1);
+            let mut histogram: SparseHistogram =
+                counts.into_iter().map(|(b, n)| (b, n / total)).collect();
+            histogram.sort_by_key(|&(b, _)| b);
+            histogram
+        })
+        .collect();
+    bar.finish();
+
+    let (mut clusters, centers) = k_means(&histograms, k, ground, CONFIG.k_means_iters);
+    let centers = sort_clusters(&mut clusters, centers, |c| {
+        next_street_equity(c, next_equities)
+    });
+    let abstraction = hands.iter().cloned().zip(clusters).collect();
+    (abstraction, centers)
 }
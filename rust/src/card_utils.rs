@@ -1,3 +1,5 @@
+use crate::card_abstraction::abstraction_file;
+use crate::config::{Variant, CONFIG};
 use crate::itertools::Itertools;
 use ahash::AHashMap as HashMap;
 use once_cell::sync::Lazy;
@@ -7,17 +9,19 @@ use smallvec::{SmallVec, ToSmallVec};
 use std::{
     collections::HashSet,
     fmt,
-    fs::File,
+    fs::{self, File},
     io::{BufReader, BufWriter},
     path::Path,
 };
 
-const FAST_HAND_TABLE_PATH: &str = "products/fast_strengths.bin";
-const FLOP_CANONICAL_PATH: &str = "products/flop_isomorphic.bin";
-const TURN_CANONICAL_PATH: &str = "products/turn_isomorphic.bin";
-const RIVER_CANONICAL_PATH: &str = "products/river_isomorphic.bin";
+// The isomorphic hands are kept with the abstraction files in abstraction_dir
+const FLOP_ISOMORPHIC_FILE: &str = "flop_isomorphic.bin";
+const TURN_ISOMORPHIC_FILE: &str = "turn_isomorphic.bin";
+const RIVER_ISOMORPHIC_FILE: &str = "river_isomorphic.bin";
 
 pub type SmallVecHand = SmallVec<[Card; 7]>;
+// A player's hole cards: two in hold'em, four in Omaha
+pub type Hole = SmallVec<[Card; 4]>;
 pub static FAST_HAND_TABLE: Lazy<FastHandTable> = Lazy::new(FastHandTable::new);
 
 pub const CLUBS: i32 = 0;
@@ -33,33 +37,42 @@ pub struct Card {
 
 impl Card {
     pub fn new(card: &str) -> Card {
-        let rank = match &card[0..1] {
-            "2" => 2,
-            "3" => 3,
-            "4" => 4,
-            "5" => 5,
-            "6" => 6,
-            "7" => 7,
-            "8" => 8,
-            "9" => 9,
-            "T" => 10,
-            "J" => 11,
-            "Q" => 12,
-            "K" => 13,
-            "A" => 14,
-            _ => panic!("bad card string '{}'", card),
+        Card::parse(card).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    // Like new, but returns an error for bad card strings instead of panicking
+    pub fn parse(card: &str) -> Result<Card, String> {
+        let mut chars = card.chars();
+        let (Some(rank), Some(suit), None) = (chars.next(), chars.next(), chars.next()) else {
+            return Err(format!("bad card string '{card}'"));
         };
-        let suit = match &card[1..2] {
-            "c" => CLUBS,
-            "d" => DIAMONDS,
-            "h" => HEARTS,
-            "s" => SPADES,
-            _ => panic!("bad card string"),
+        let rank = match rank {
+            '2' => 2,
+            '3' => 3,
+            '4' => 4,
+            '5' => 5,
+            '6' => 6,
+            '7' => 7,
+            '8' => 8,
+            '9' => 9,
+            'T' => 10,
+            'J' => 11,
+            'Q' => 12,
+            'K' => 13,
+            'A' => 14,
+            _ => return Err(format!("bad card string '{card}'")),
+        };
+        let suit = match suit {
+            'c' => CLUBS,
+            'd' => DIAMONDS,
+            'h' => HEARTS,
+            's' => SPADES,
+            _ => return Err(format!("bad card string '{card}'")),
         };
-        Card {
+        Ok(Card {
             rank,
             suit: suit as u8,
-        }
+        })
     }
 }
 
@@ -111,9 +124,19 @@ impl fmt::Display for Card {
     }
 }
 
+pub fn lowest_rank() -> u8 {
+    match CONFIG.variant() {
+        Variant::Holdem | Variant::Omaha => 2,
+        Variant::ShortDeck => 6,
+    }
+}
+
 pub fn deck() -> Vec<Card> {
     let mut deck = Vec::with_capacity(52);
-    let ranks = std::ops::Range { start: 2, end: 15 };
+    let ranks = std::ops::Range {
+        start: lowest_rank(),
+        end: 15,
+    };
     for rank in ranks {
         for suit in 0..4 {
             deck.push(Card { rank, suit });
@@ -233,45 +256,238 @@ pub fn isomorphic_hand(cards: &[Card]) -> SmallVecHand {
     isomorphic
 }
 
+// Evaluates 5 to 7 card hands with small lookup tables instead of a table of every hand. If
+// five or more cards share a suit, the hand can't make anything better than a flush except a
+// straight flush, so it is looked up by that suit's rank bitmask. Otherwise only the ranks
+// matter, and the multiset of ranks is looked up by its index in the combinatorial number
+// system. That index is found from the count of each rank in base 5, and the number of cards
+// of each suit is kept four bits per suit above it, so a hand's key is just the sum of its
+// cards' keys and there is no sorting or looping over ranks.
 pub struct FastHandTable {
-    strengths: HashMap<u64, i32>,
-}
+    lowest_rank: u8,
+    // 5^rank for each rank, offset so that the lowest rank is 0
+    rank_keys: [u32; 13],
+    // Multiset index contributed by the lowest LOW_RANKS ranks, with the number of cards
+    // among them in the upper 16 bits
+    low_offsets: Vec<u32>,
+    // Multiset index contributed by the other ranks, for each number of cards below them
+    high_offsets: Vec<u32>,
+    // Where the strengths of hands with each number of cards start in rank_strengths
+    size_offsets: [u32; 8],
+    rank_strengths: Vec<i32>,
+    flush_strengths: Vec<i32>,
+}
+
+const LOW_RANKS: usize = 7;
+const LOW_KEYS: u32 = 78_125; // 5^LOW_RANKS
 
 impl FastHandTable {
     pub fn new() -> FastHandTable {
-        FastHandTable {
-            strengths: FastHandTable::load_hand_strengths(),
+        let lowest_rank = lowest_rank();
+        let num_ranks = 15 - lowest_rank as usize;
+        let mut rank_keys = [0; 13];
+        for (rank, key) in rank_keys.iter_mut().enumerate().take(num_ranks) {
+            *key = 5u32.pow(rank as u32);
         }
-    }
 
-    pub fn hand_strength(&self, hand: &[Card]) -> Option<i32> {
-        let compact = cards2bitmap(hand);
-        self.strengths.get(&compact).cloned()
-    }
+        let mut low_offsets = vec![0; LOW_KEYS as usize];
+        for (key, offset) in low_offsets.iter_mut().enumerate() {
+            // Keys with more than 7 cards can't happen and are left at 0
+            let counts = base_5_digits(key, LOW_RANKS);
+            if counts.iter().sum::<usize>() > 7 {
+                continue;
+            }
+            let (index, seen) = multiset_index(&counts, 0, 0);
+            *offset = index | (seen << 16) as u32;
+        }
+        let high_ranks = num_ranks - LOW_RANKS;
+        let mut high_offsets = vec![0; 5usize.pow(high_ranks as u32) * 8];
+        for key in 0..5usize.pow(high_ranks as u32) {
+            let counts = base_5_digits(key, high_ranks);
+            let high_cards: usize = counts.iter().sum();
+            for seen in 0..8 {
+                if high_cards + seen <= 7 {
+                    let (index, _) = multiset_index(&counts, LOW_RANKS, seen);
+                    high_offsets[key * 8 + seen] = index;
+                }
+            }
+        }
+        let mut size_offsets = [0; 8];
+        for n in 1..8 {
+            size_offsets[n] = size_offsets[n - 1] + n_choose_k(num_ranks + n - 2, n - 1) as u32;
+        }
 
-    fn load_hand_strengths() -> HashMap<u64, i32> {
-        if !Path::new(FAST_HAND_TABLE_PATH).exists() {
-            println!("[INFO] Creating fast hand table.");
-            let mut table: HashMap<u64, i32> = HashMap::new();
-            let deck = deck();
-            let bar = pbar(133784560);
-            for hand in deck.iter().combinations(7) {
-                let cards = deepcopy(&hand);
-                let strength: i32 = hand_strength(&cards);
-                let bitmap = cards2bitmap(&cards);
-                table.insert(bitmap, strength);
-                bar.inc(1);
+        let mut table = FastHandTable {
+            lowest_rank,
+            rank_keys,
+            low_offsets,
+            high_offsets,
+            size_offsets,
+            rank_strengths: vec![0; size_offsets[7] as usize + n_choose_k(num_ranks + 6, 7)],
+            flush_strengths: vec![0; 1 << num_ranks],
+        };
+
+        // Deal the suits round robin so that no five cards share a suit
+        for n in 5..8 {
+            for ranks in (0..num_ranks).combinations_with_replacement(n) {
+                let cards: SmallVecHand = ranks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &r)| Card {
+                        rank: r as u8 + lowest_rank,
+                        suit: (i % 4) as u8,
+                    })
+                    .collect();
+                if cards.windows(5).any(|w| w[0].rank == w[4].rank) {
+                    continue;
+                }
+                let index = table.rank_index(table.key(&cards) as u32, n);
+                table.rank_strengths[index] = slow_hand_strength(&cards);
+            }
+        }
+        for mask in 0..1usize << num_ranks {
+            if mask.count_ones() >= 5 && mask.count_ones() <= 7 {
+                let cards: SmallVecHand = (0..num_ranks)
+                    .filter(|r| mask & (1 << r) != 0)
+                    .map(|r| Card {
+                        rank: r as u8 + lowest_rank,
+                        suit: 0,
+                    })
+                    .collect();
+                table.flush_strengths[mask] = slow_hand_strength(&cards);
             }
-            bar.finish();
-            serialize(table, FAST_HAND_TABLE_PATH);
         }
-        let table: HashMap<u64, i32> = read_serialized(FAST_HAND_TABLE_PATH);
         table
     }
+
+    pub fn hand_strength(&self, hand: &[Card]) -> i32 {
+        debug_assert!(hand.len() >= 5 && hand.len() <= 7);
+        let key = self.key(hand);
+        match flush_suit(key) {
+            Some(suit) => self.flush_strengths[self.suit_mask(hand, suit) as usize],
+            None => self.rank_strengths[self.rank_index(key as u32, hand.len())],
+        }
+    }
+
+    // The strength of each hole on the board. The board is only processed once, which is
+    // what the vectorized showdown needs to evaluate every hole on the river.
+    pub fn hole_strengths(&self, holes: &[[Card; 2]], board: &[Card]) -> Vec<i32> {
+        let board_key = self.key(board);
+        let board_masks = [0, 1, 2, 3].map(|suit| self.suit_mask(board, suit));
+        let n = board.len() + 2;
+        holes
+            .iter()
+            .map(|hole| {
+                let key = board_key + self.key(hole);
+                match flush_suit(key) {
+                    Some(suit) => {
+                        let mask = board_masks[suit as usize] | self.suit_mask(hole, suit);
+                        self.flush_strengths[mask as usize]
+                    }
+                    None => self.rank_strengths[self.rank_index(key as u32, n)],
+                }
+            })
+            .collect()
+    }
+
+    // The base 5 rank counts in the lower 32 bits, and the suit counts above them
+    #[inline]
+    fn key(&self, cards: &[Card]) -> u64 {
+        cards
+            .iter()
+            .map(|c| {
+                self.rank_keys[(c.rank - self.lowest_rank) as usize] as u64
+                    + (1 << (32 + 4 * c.suit as u64))
+            })
+            .sum()
+    }
+
+    #[inline]
+    fn suit_mask(&self, cards: &[Card], suit: u8) -> u16 {
+        cards
+            .iter()
+            .filter(|c| c.suit == suit)
+            .fold(0, |mask, c| mask | 1 << (c.rank - self.lowest_rank))
+    }
+
+    #[inline]
+    fn rank_index(&self, rank_key: u32, n: usize) -> usize {
+        let low = self.low_offsets[(rank_key % LOW_KEYS) as usize];
+        let high = (rank_key / LOW_KEYS) as usize * 8 + (low >> 16) as usize;
+        (self.size_offsets[n] + (low & 0xffff) + self.high_offsets[high]) as usize
+    }
 }
 
-// Uses the rs_poker library to evaluate the strength of a hand
+// The suit with at least five cards, if there is one. Adding three to each suit's count
+// carries into the top bit of its four bits exactly when the count is at least five.
+#[inline]
+fn flush_suit(key: u64) -> Option<u8> {
+    let flushes = ((key >> 32) as u32 + 0x3333) & 0x8888;
+    if flushes == 0 {
+        None
+    } else {
+        Some((flushes.trailing_zeros() / 4) as u8)
+    }
+}
+
+fn base_5_digits(mut key: usize, n: usize) -> SmallVec<[usize; 7]> {
+    (0..n)
+        .map(|_| {
+            let digit = key % 5;
+            key /= 5;
+            digit
+        })
+        .collect()
+}
+
+// Index of a multiset of ranks in the combinatorial number system, given the count of each
+// rank starting from first_rank and the number of cards with lower ranks. Sorting the cards
+// and adding each card's position to its rank makes them distinct, so they can be indexed
+// as a set. Also returns the total number of cards.
+fn multiset_index(counts: &[usize], first_rank: usize, seen: usize) -> (u32, usize) {
+    let mut index: u64 = 0;
+    let mut seen = seen;
+    for (i, &count) in counts.iter().enumerate() {
+        for _ in 0..count {
+            seen += 1;
+            index += n_choose_k(first_rank + i + seen - 1, seen) as u64;
+        }
+    }
+    let index = u32::try_from(index).expect("Multiset index doesn't fit in the lookup table");
+    (index, seen)
+}
+
+// The strength of a hand under the rules of the configured variant, without the lookup tables
+fn slow_hand_strength(cards: &[Card]) -> i32 {
+    match CONFIG.variant() {
+        Variant::ShortDeck => short_deck_hand_strength(cards),
+        // Omaha hands are evaluated five cards at a time with the hold'em rankings
+        _ => {
+            let (category, ranks) = evaluate_categories(cards, 2);
+            category * 100_000_000 + ranks
+        }
+    }
+}
+
+// Evaluates the strength of a hand under the rules of the configured variant. Higher is
+// better, and equal strengths tie.
 pub fn hand_strength(cards: &[Card]) -> i32 {
+    match CONFIG.variant() {
+        Variant::Holdem | Variant::ShortDeck => FAST_HAND_TABLE.hand_strength(cards),
+        Variant::Omaha => panic!("Omaha hands need to be evaluated with showdown_strength"),
+    }
+}
+
+// Evaluates a player's hand at showdown under the rules of the configured variant
+pub fn showdown_strength(hole: &[Card], board: &[Card]) -> i32 {
+    match CONFIG.variant() {
+        Variant::Omaha => omaha_hand_strength(hole, board),
+        _ => FAST_HAND_TABLE.hand_strength(&[hole, board].concat()),
+    }
+}
+
+// Uses the rs_poker library to evaluate the strength of a hand
+pub fn holdem_hand_strength(cards: &[Card]) -> i32 {
     let step: i32 = 100_000_000;
     let hand = cards2str(cards);
     let rank = Hand::new_from_str(&hand).unwrap().rank();
@@ -289,6 +505,134 @@ pub fn hand_strength(cards: &[Card]) -> i32 {
     rank_val * step + rank_strength as i32
 }
 
+const HIGH_CARD: i32 = 0;
+const ONE_PAIR: i32 = 1;
+const TWO_PAIR: i32 = 2;
+const THREE_OF_A_KIND: i32 = 3;
+const STRAIGHT: i32 = 4;
+const FLUSH: i32 = 5;
+const FULL_HOUSE: i32 = 6;
+const FOUR_OF_A_KIND: i32 = 7;
+const STRAIGHT_FLUSH: i32 = 8;
+
+// Short deck hold'em ranks hands like regular hold'em, except that flushes beat full houses
+// (since they are rarer with only 9 cards per suit), and the ace plays low in A-6-7-8-9.
+pub fn short_deck_hand_strength(cards: &[Card]) -> i32 {
+    let (category, ranks) = evaluate_categories(cards, 6);
+    let category = match category {
+        FLUSH => FULL_HOUSE,
+        FULL_HOUSE => FLUSH,
+        c => c,
+    };
+    category * 100_000_000 + ranks
+}
+
+// In Omaha, the hand is made from exactly two hole cards and three board cards.
+pub fn omaha_hand_strength(hole: &[Card], board: &[Card]) -> i32 {
+    let mut best = 0;
+    for hole_cards in hole.iter().combinations(2) {
+        for board_cards in board.iter().combinations(3) {
+            let cards: SmallVecHand = hole_cards
+                .iter()
+                .chain(board_cards.iter())
+                .map(|&&c| c)
+                .collect();
+            best = best.max(FAST_HAND_TABLE.hand_strength(&cards));
+        }
+    }
+    best
+}
+
+// Finds the best five card hand out of 5 to 7 cards. Returns the hand category and the
+// ranks that break ties within that category, packed four bits per rank with the most
+// significant rank first. lowest_rank is the rank which the ace sits below in a straight.
+fn evaluate_categories(cards: &[Card], lowest_rank: u8) -> (i32, i32) {
+    debug_assert!(cards.len() >= 5 && cards.len() <= 7);
+    let mut rank_counts = [0u8; 15];
+    let mut suit_masks = [0u16; 4];
+    for card in cards {
+        rank_counts[card.rank as usize] += 1;
+        suit_masks[card.suit as usize] |= 1 << card.rank;
+    }
+    let pack = |ranks: &[u8]| -> i32 {
+        ranks
+            .iter()
+            .take(5)
+            .fold(0, |packed, &rank| (packed << 4) | rank as i32)
+    };
+    // Ranks with at least n cards, from high to low
+    let ranks_with = |n: u8| -> SmallVec<[u8; 7]> {
+        (2..15)
+            .rev()
+            .filter(|&r| rank_counts[r as usize] >= n)
+            .collect()
+    };
+    let top_ranks = |mask: u16, n: usize| -> SmallVec<[u8; 7]> {
+        (2..15u8)
+            .rev()
+            .filter(|&r| mask & (1 << r) != 0)
+            .take(n)
+            .collect()
+    };
+
+    let flush_mask = suit_masks.iter().find(|m| m.count_ones() >= 5).copied();
+    if let Some(mask) = flush_mask {
+        if let Some(high) = straight_high_card(mask, lowest_rank) {
+            return (STRAIGHT_FLUSH, high as i32);
+        }
+    }
+    let quads = ranks_with(4);
+    if let Some(&quad) = quads.first() {
+        let kicker = ranks_with(1).into_iter().find(|&r| r != quad).unwrap();
+        return (FOUR_OF_A_KIND, pack(&[quad, kicker]));
+    }
+    let trips = ranks_with(3);
+    let pairs = ranks_with(2);
+    if let Some(&trip) = trips.first() {
+        if let Some(&pair) = pairs.iter().find(|&&r| r != trip) {
+            return (FULL_HOUSE, pack(&[trip, pair]));
+        }
+    }
+    if let Some(mask) = flush_mask {
+        return (FLUSH, pack(&top_ranks(mask, 5)));
+    }
+    let rank_mask = suit_masks.iter().fold(0, |all, m| all | m);
+    if let Some(high) = straight_high_card(rank_mask, lowest_rank) {
+        return (STRAIGHT, high as i32);
+    }
+    let singles = ranks_with(1);
+    if let Some(&trip) = trips.first() {
+        let mut ranks: SmallVec<[u8; 7]> = smallvec![trip];
+        ranks.extend(singles.into_iter().filter(|&r| r != trip).take(2));
+        return (THREE_OF_A_KIND, pack(&ranks));
+    }
+    if pairs.len() >= 2 {
+        let kicker = singles
+            .into_iter()
+            .find(|&r| r != pairs[0] && r != pairs[1])
+            .unwrap();
+        return (TWO_PAIR, pack(&[pairs[0], pairs[1], kicker]));
+    }
+    if let Some(&pair) = pairs.first() {
+        let mut ranks: SmallVec<[u8; 7]> = smallvec![pair];
+        ranks.extend(singles.into_iter().filter(|&r| r != pair).take(3));
+        return (ONE_PAIR, pack(&ranks));
+    }
+    (HIGH_CARD, pack(&singles))
+}
+
+// Returns the high card of the best straight in the rank bitmask, if there is one. The ace
+// can also play as the rank just below lowest_rank.
+fn straight_high_card(rank_mask: u16, lowest_rank: u8) -> Option<u8> {
+    let mut mask = rank_mask as u32;
+    if mask & (1 << 14) != 0 {
+        mask |= 1 << (lowest_rank - 1);
+    }
+    (lowest_rank + 3..15)
+        .rev()
+        .find(|&high| (mask >> (high - 4)) & 0b11111 == 0b11111)
+}
+
 // u64 hand representation
 // Each card is a single u8 byte, where
 //
@@ -321,6 +665,15 @@ pub fn len(hand: u64) -> i32 {
     -1
 }
 
+// Like str2cards, but returns an error for bad card strings instead of panicking
+pub fn try_str2cards(hand_str: &str) -> Result<Vec<Card>, String> {
+    let chars: Vec<char> = hand_str.chars().collect();
+    chars
+        .chunks(2)
+        .map(|card| Card::parse(&card.iter().collect::<String>()))
+        .collect()
+}
+
 pub fn str2cards(hand_str: &str) -> Vec<Card> {
     let mut result: Vec<Card> = Vec::new();
     let hand_str = hand_str.to_string();
@@ -444,20 +797,53 @@ pub fn cards2bitmap(cards: &[Card]) -> u64 {
     bitmap
 }
 
+// Number of ways to choose k items out of n
+pub fn n_choose_k(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |result, i| result * (n - i) / (i + 1))
+}
+
+// Number of ways to deal two hole cards and then n_cards - 2 board cards from the deck
+pub fn n_deals(n_cards: usize) -> usize {
+    let deck_size = deck().len();
+    n_choose_k(deck_size, 2) * n_choose_k(deck_size - 2, n_cards - 2)
+}
+
+// Each variant keeps its lookup tables and abstractions in its own products subdirectory,
+// since they are built from a different deck.
+pub fn variant_path(path: &str) -> String {
+    match CONFIG.variant() {
+        Variant::Holdem => path.to_string(),
+        Variant::ShortDeck => path.replacen("products/", "products/short_deck/", 1),
+        Variant::Omaha => path.replacen("products/", "products/omaha/", 1),
+    }
+}
+
+// Creates a file for writing, along with any missing parent directories.
+pub fn create_file(path: &str) -> File {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    File::create(path).unwrap()
+}
+
 pub fn load_flop_isomorphic() -> Vec<u64> {
-    load_isomorphic(5, FLOP_CANONICAL_PATH)
+    load_isomorphic(5, FLOP_ISOMORPHIC_FILE)
 }
 
 pub fn load_turn_isomorphic() -> Vec<u64> {
-    load_isomorphic(6, TURN_CANONICAL_PATH)
+    load_isomorphic(6, TURN_ISOMORPHIC_FILE)
 }
 
 pub fn load_river_isomorphic() -> Vec<u64> {
-    load_isomorphic(7, RIVER_CANONICAL_PATH)
+    load_isomorphic(7, RIVER_ISOMORPHIC_FILE)
 }
 
-fn load_isomorphic(n_cards: usize, path: &str) -> Vec<u64> {
-    match File::open(path) {
+fn load_isomorphic(n_cards: usize, name: &str) -> Vec<u64> {
+    let path = abstraction_file(name);
+    match File::open(&path) {
         Ok(file) => {
             let reader = BufReader::new(file);
             bincode::deserialize_from(reader).unwrap()
@@ -465,7 +851,7 @@ fn load_isomorphic(n_cards: usize, path: &str) -> Vec<u64> {
         Err(_e) => {
             // Find the isomorphic hands and write them to disk.
             let isomorphic = deal_isomorphic(n_cards, true);
-            let file = File::create(path).unwrap();
+            let file = create_file(&path);
             let buffer = BufWriter::new(file);
             bincode::serialize_into(buffer, &isomorphic).unwrap();
             println!("[INFO] Wrote isomorphic hands to {path}.");
@@ -476,7 +862,8 @@ fn load_isomorphic(n_cards: usize, path: &str) -> Vec<u64> {
 
 pub fn isomorphic_preflop_hands() -> HashSet<Vec<Card>> {
     let mut preflop_hands: HashSet<Vec<Card>> = HashSet::new();
-    for i in 2..15 {
+    let lowest_rank = lowest_rank() as usize;
+    for i in lowest_rank..15 {
         for j in i..15 {
             preflop_hands.insert(vec![
                 Card {
@@ -502,7 +889,8 @@ pub fn isomorphic_preflop_hands() -> HashSet<Vec<Card>> {
             }
         }
     }
-    assert_eq!(preflop_hands.len(), 169);
+    let n_ranks = 15 - lowest_rank;
+    assert_eq!(preflop_hands.len(), n_ranks * n_ranks);
     preflop_hands
 }
 
@@ -540,11 +928,22 @@ pub fn read_serialized(path: &str) -> HashMap<u64, i32> {
 }
 
 pub fn serialize(hand_data: HashMap<u64, i32>, path: &str) {
-    let file = File::create(path).unwrap();
+    let file = create_file(path);
     let buffer = BufWriter::new(file);
     bincode::serialize_into(buffer, &hand_data).unwrap();
 }
 
+// All hole card combinations of the configured variant that don't contain a blocker. This
+// is 1326 hands in hold'em and 270,725 in Omaha.
+pub fn non_blocking_holes(blockers: &[Card]) -> Vec<Hole> {
+    deck()
+        .into_iter()
+        .filter(|c| !blockers.contains(c))
+        .combinations(CONFIG.hole_cards())
+        .map(|cards| cards.into_iter().collect())
+        .collect()
+}
+
 pub fn non_blocking_preflop_hands(blockers: &[Card]) -> Vec<[Card; 2]> {
     let mut hands: Vec<[Card; 2]> = Vec::with_capacity(1326);
     let deck = deck();
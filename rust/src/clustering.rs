@@ -0,0 +1,385 @@
+// k-means clustering for the card abstractions, generic over the distance metric. Uses
+// Hamerly's algorithm, which keeps an upper bound on each point's distance to its center and
+// a lower bound on its distance to every other center, and uses the triangle inequality to
+// skip most of the distance computations once the clusters start to settle.
+// https://epubs.siam.org/doi/pdf/10.1137/1.9781611972801.12
+
+use crate::card_utils::pbar;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+// Number of points sampled for the k-means++ initialization
+const INIT_SAMPLE_SIZE: usize = 10_000;
+
+pub trait Distance: Sync {
+    type Point: Sync;
+
+    // Distance between a point and a cluster center. Centers are always dense vectors.
+    fn distance(&self, point: &Self::Point, center: &[f32]) -> f32;
+
+    fn center_distance(&self, a: &[f32], b: &[f32]) -> f32;
+
+    // The point as a dense vector, which is how centers are initialized
+    fn dense(&self, point: &Self::Point) -> Vec<f32>;
+
+    // Adds the point to a running sum of dense vectors, for averaging the clusters
+    fn add_to(&self, point: &Self::Point, sum: &mut [f32]);
+}
+
+// Euclidean distance
+pub struct L2;
+
+impl Distance for L2 {
+    type Point = Vec<f32>;
+
+    fn distance(&self, point: &Vec<f32>, center: &[f32]) -> f32 {
+        self.center_distance(point, center)
+    }
+
+    fn center_distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert!(a.len() == b.len());
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| (x - y).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    fn dense(&self, point: &Vec<f32>) -> Vec<f32> {
+        point.clone()
+    }
+
+    fn add_to(&self, point: &Vec<f32>, sum: &mut [f32]) {
+        for (s, x) in sum.iter_mut().zip(point.iter()) {
+            *s += x;
+        }
+    }
+}
+
+// Exact earth mover's distance between histograms over ordered buckets the same distance
+// apart, such as equity histograms. In one dimension this is the L1 distance between the
+// cumulative distributions.
+pub struct Emd1d;
+
+impl Distance for Emd1d {
+    type Point = Vec<f32>;
+
+    fn distance(&self, point: &Vec<f32>, center: &[f32]) -> f32 {
+        self.center_distance(point, center)
+    }
+
+    fn center_distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert!(a.len() == b.len());
+        let mut cdf_difference = 0.0;
+        let mut emd = 0.0;
+        for (x, y) in a.iter().zip(b.iter()) {
+            cdf_difference += x - y;
+            emd += f32::abs(cdf_difference);
+        }
+        emd
+    }
+
+    fn dense(&self, point: &Vec<f32>) -> Vec<f32> {
+        point.clone()
+    }
+
+    fn add_to(&self, point: &Vec<f32>, sum: &mut [f32]) {
+        for (s, x) in sum.iter_mut().zip(point.iter()) {
+            *s += x;
+        }
+    }
+}
+
+// A histogram stored as (bucket, probability) pairs for the nonzero buckets
+pub type SparseHistogram = Vec<(usize, f32)>;
+
+// Earth mover's distance between histograms over the buckets of another abstraction, such as
+// the next street's buckets or the OCHS opponent clusters, where the cost of moving mass
+// between two buckets comes from a ground distance matrix. Each bucket's neighbors are kept
+// sorted from nearest to farthest so that mass can be moved greedily. The greedy distance is
+// only approximately a metric, so the k-means bounds are approximate as well.
+pub struct GroundEmd {
+    pub distances: Vec<Vec<f32>>,
+    nearest: Vec<Vec<usize>>,
+}
+
+impl GroundEmd {
+    pub fn new(distances: Vec<Vec<f32>>) -> GroundEmd {
+        let nearest = distances
+            .par_iter()
+            .map(|row| {
+                let mut order: Vec<usize> = (0..row.len()).collect();
+                order.sort_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap());
+                order
+            })
+            .collect();
+        GroundEmd { distances, nearest }
+    }
+
+    // The ground distances are the distances between the centers of another clustering
+    pub fn from_centers<D: Distance>(centers: &[Vec<f32>], distance: &D) -> GroundEmd {
+        let distances = centers
+            .par_iter()
+            .map(|a| {
+                centers
+                    .iter()
+                    .map(|b| distance.center_distance(a, b))
+                    .collect()
+            })
+            .collect();
+        GroundEmd::new(distances)
+    }
+}
+
+impl Distance for GroundEmd {
+    type Point = SparseHistogram;
+
+    // This is the fast approximation used by Ganzfried and Sandholm: each bucket of the point
+    // sends its mass to the nearest buckets of the center that still have room.
+    fn distance(&self, point: &SparseHistogram, center: &[f32]) -> f32 {
+        let mut room = center.to_vec();
+        let mut cost = 0.0;
+        for &(bucket, mass) in point {
+            let mut left = mass;
+            for &target in self.nearest[bucket].iter() {
+                if left <= 0.0 {
+                    break;
+                }
+                let moved = left.min(room[target]);
+                if moved > 0.0 {
+                    cost += moved * self.distances[bucket][target];
+                    room[target] -= moved;
+                    left -= moved;
+                }
+            }
+        }
+        cost
+    }
+
+    fn center_distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.distance(&to_sparse(a), b)
+    }
+
+    fn dense(&self, point: &SparseHistogram) -> Vec<f32> {
+        let mut v = vec![0.0; self.distances.len()];
+        for &(bucket, p) in point {
+            v[bucket] = p;
+        }
+        v
+    }
+
+    fn add_to(&self, point: &SparseHistogram, sum: &mut [f32]) {
+        for &(bucket, p) in point {
+            sum[bucket] += p;
+        }
+    }
+}
+
+pub fn to_sparse(histogram: &[f32]) -> SparseHistogram {
+    histogram
+        .iter()
+        .enumerate()
+        .filter(|(_, &p)| p > 0.0)
+        .map(|(i, &p)| (i, p))
+        .collect()
+}
+
+// Clusters the points into k clusters. Returns the cluster of each point and the centers.
+pub fn k_means<D: Distance>(
+    points: &[D::Point],
+    k: usize,
+    distance: &D,
+    max_iters: u64,
+) -> (Vec<i32>, Vec<Vec<f32>>) {
+    assert!(k > 0 && points.len() >= k, "Need at least k points");
+    let mut centers = k_means_plus_plus(points, k, distance);
+
+    // Hamerly's bounds: the distance to the assigned center is at most upper, and the
+    // distance to every other center is at least lower.
+    let mut assignments: Vec<usize> = vec![0; points.len()];
+    let mut upper: Vec<f32> = vec![f32::INFINITY; points.len()];
+    let mut lower: Vec<f32> = vec![0.0; points.len()];
+
+    let bar = pbar(max_iters as usize);
+    for iter in 0..max_iters {
+        // Half the distance from each center to the nearest other center. A point closer
+        // than this to its center can't be closer to any other center.
+        let center_distances: Vec<Vec<f32>> = centers
+            .par_iter()
+            .map(|a| {
+                centers
+                    .iter()
+                    .map(|b| distance.center_distance(a, b))
+                    .collect()
+            })
+            .collect();
+        let half_gaps: Vec<f32> = (0..k)
+            .map(|j| {
+                (0..k)
+                    .filter(|&other| other != j)
+                    .map(|other| center_distances[j][other])
+                    .fold(f32::INFINITY, f32::min)
+                    / 2.0
+            })
+            .collect();
+
+        let changed: usize = points
+            .par_iter()
+            .zip(assignments.par_iter_mut())
+            .zip(upper.par_iter_mut().zip(lower.par_iter_mut()))
+            .map(|((point, assignment), (upper, lower))| {
+                let bound = half_gaps[*assignment].max(*lower);
+                if *upper <= bound {
+                    return 0;
+                }
+                *upper = distance.distance(point, &centers[*assignment]);
+                if *upper <= bound {
+                    return 0;
+                }
+                let mut best = (0, f32::INFINITY);
+                let mut second = f32::INFINITY;
+                for (j, center) in centers.iter().enumerate() {
+                    let d = distance.distance(point, center);
+                    if d < best.1 {
+                        second = best.1;
+                        best = (j, d);
+                    } else if d < second {
+                        second = d;
+                    }
+                }
+                let moved = (best.0 != *assignment) as usize;
+                *assignment = best.0;
+                *upper = best.1;
+                *lower = second;
+                moved
+            })
+            .sum();
+        bar.inc(1);
+        if changed == 0 && iter > 0 {
+            println!("Converged after {iter} iterations.");
+            break;
+        }
+
+        // Move each center to the mean of its points, and loosen the bounds by how far the
+        // centers moved.
+        let new_centers = cluster_means(points, &assignments, &centers, distance);
+        let movement: Vec<f32> = centers
+            .par_iter()
+            .zip(new_centers.par_iter())
+            .map(|(old, new)| distance.center_distance(old, new))
+            .collect();
+        let max_movement = movement.iter().cloned().fold(0.0, f32::max);
+        upper
+            .par_iter_mut()
+            .zip(lower.par_iter_mut())
+            .zip(assignments.par_iter())
+            .for_each(|((upper, lower), &assignment)| {
+                *upper += movement[assignment];
+                *lower -= max_movement;
+            });
+        centers = new_centers;
+    }
+    bar.finish();
+
+    let clusters = assignments.iter().map(|&a| a as i32).collect();
+    (clusters, centers)
+}
+
+// Relabels the clusters in increasing order of key(center), so that neighbouring cluster ids
+// hold similar hands and can share nodes when a history has fewer buckets than its street.
+// Returns the centers in their new order.
+pub fn sort_clusters<F>(clusters: &mut [i32], centers: Vec<Vec<f32>>, key: F) -> Vec<Vec<f32>>
+where
+    F: Fn(&[f32]) -> f32,
+{
+    let keys: Vec<f32> = centers.iter().map(|c| key(c)).collect();
+    let mut order: Vec<usize> = (0..centers.len()).collect();
+    order.sort_by(|&a, &b| keys[a].total_cmp(&keys[b]));
+    let mut new_id = vec![0; centers.len()];
+    for (id, &old) in order.iter().enumerate() {
+        new_id[old] = id as i32;
+    }
+    for cluster in clusters.iter_mut() {
+        *cluster = new_id[*cluster as usize];
+    }
+    order.into_iter().map(|old| centers[old].clone()).collect()
+}
+
+// Picks the initial centers from a sample of the points, each chosen with probability
+// proportional to its squared distance from the nearest center chosen so far.
+fn k_means_plus_plus<D: Distance>(points: &[D::Point], k: usize, distance: &D) -> Vec<Vec<f32>> {
+    println!("Initializing centroids with k-means++");
+    let mut rng = thread_rng();
+    let sample: Vec<&D::Point> = points
+        .choose_multiple(&mut rng, INIT_SAMPLE_SIZE.max(k))
+        .collect();
+    let mut centers: Vec<Vec<f32>> = vec![distance.dense(sample[0])];
+    let mut nearest: Vec<f32> = vec![f32::INFINITY; sample.len()];
+    let bar = pbar(k);
+    while centers.len() < k {
+        let newest = centers.last().unwrap();
+        nearest
+            .par_iter_mut()
+            .zip(sample.par_iter())
+            .for_each(|(d, point)| *d = d.min(distance.distance(point, newest)));
+        let weights: Vec<f32> = nearest.iter().map(|d| d * d).collect();
+        let next = match WeightedIndex::new(&weights) {
+            Ok(dist) => sample[dist.sample(&mut rng)],
+            // Every sampled point is already a center
+            Err(_) => points.choose(&mut rng).unwrap(),
+        };
+        centers.push(distance.dense(next));
+        bar.inc(1);
+    }
+    bar.finish();
+    centers
+}
+
+// The mean of each cluster's points. Empty clusters keep their old center.
+fn cluster_means<D: Distance>(
+    points: &[D::Point],
+    assignments: &[usize],
+    old_centers: &[Vec<f32>],
+    distance: &D,
+) -> Vec<Vec<f32>> {
+    let k = old_centers.len();
+    let dim = old_centers[0].len();
+    // Each thread sums a large chunk of points, so there are only a few partial sums
+    let chunk_size = points.len() / rayon::current_num_threads() + 1;
+    let (sums, counts) = points
+        .par_iter()
+        .zip(assignments.par_iter())
+        .with_min_len(chunk_size)
+        .fold(
+            || (vec![vec![0.0; dim]; k], vec![0usize; k]),
+            |(mut sums, mut counts), (point, &cluster)| {
+                distance.add_to(point, &mut sums[cluster]);
+                counts[cluster] += 1;
+                (sums, counts)
+            },
+        )
+        .reduce(
+            || (vec![vec![0.0; dim]; k], vec![0usize; k]),
+            |(mut sums, mut counts), (other_sums, other_counts)| {
+                for j in 0..k {
+                    for (s, x) in sums[j].iter_mut().zip(other_sums[j].iter()) {
+                        *s += x;
+                    }
+                    counts[j] += other_counts[j];
+                }
+                (sums, counts)
+            },
+        );
+    sums.into_iter()
+        .zip(counts)
+        .zip(old_centers.iter())
+        .map(|((sum, count), old)| {
+            if count == 0 {
+                old.clone()
+            } else {
+                sum.into_iter().map(|s| s / count as f32).collect()
+            }
+        })
+        .collect()
+}
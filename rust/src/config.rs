@@ -1,5 +1,6 @@
 // For reading and storing the configuration file info
 
+use crate::trainer_utils::*;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -12,19 +13,29 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| {
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     // Game parameters
-    pub stack_size: u16,
-    pub big_blind: u16,
-    pub small_blind: u16,
+    pub stack_size: Amount,
+    pub big_blind: Amount,
+    pub small_blind: Amount,
+    pub ante: Amount,
+    pub straddle: Amount,
     pub last_street: String,
+    pub variant: String,
+    pub num_players: usize,
 
     // Abstraction
     pub bet_abstraction: Vec<Vec<f64>>,
+    pub preflop_abstraction: String,
+    pub board_texture: bool,
     pub flop_buckets: i32,
     pub turn_buckets: i32,
     pub river_buckets: i32,
+    pub bucket_granularity: Vec<GranularityRule>,
 
     // File paths
     pub nodes_path: String,
+    pub metrics_path: String,
+    pub watchlist_path: String,
+    pub abstraction_dir: String,
 
     // Training
     pub train_iters: usize,
@@ -32,6 +43,20 @@ pub struct Config {
     pub eval_every: usize,
     pub warm_start: bool,
     pub k_means_iters: u64,
+    pub metrics_endpoint: bool,
+    pub sync_every: usize,
+    pub watchlist: Vec<WatchedSpot>,
+    pub prune_after: usize,
+    pub prune_threshold: f32,
+    pub prune_interval: usize,
+    pub full_traversal_prob: f64,
+
+    // Local best response
+    pub lbr_bets: Vec<Vec<f64>>,
+    pub lbr_opponent_samples: usize,
+    pub lbr_rollouts: usize,
+    pub lbr_exact_river: bool,
+    pub lbr_raise_from: String,
 
     // Real time solving
     pub subgame_solving: bool,
@@ -41,3 +66,135 @@ pub struct Config {
     // Preflop chart
     pub preflop_strategy_path: String,
 }
+
+// Postflop histories with a pot of at least min_pot big blinds, and at least min_bets bets
+// and raises so far, use this fraction of their street's card buckets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GranularityRule {
+    pub min_pot: f64,
+    pub min_bets: usize,
+    pub fraction: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedSpot {
+    pub hole: String,
+    pub board: String,
+    pub history: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Holdem,
+    // Short deck (6+) hold'em: 36 card deck, flushes beat full houses, A-6-7-8-9 is a straight
+    ShortDeck,
+    // Pot-limit Omaha: four hole cards, of which exactly two must be used at showdown
+    Omaha,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflopAbstraction {
+    // One bucket per strategically distinct hand, such as AKs or 72o
+    Lossless,
+    // One bucket per combination of two cards, so that suits matter when resolving
+    SuitAware,
+    // The 8 OCHS opponent clusters, for small test blueprints
+    Clustered,
+}
+
+impl Config {
+    pub fn variant(&self) -> Variant {
+        match self.variant.as_str() {
+            "holdem" => Variant::Holdem,
+            "short_deck" => Variant::ShortDeck,
+            "omaha" => Variant::Omaha,
+            _ => panic!("Bad variant in params.toml: {}", self.variant),
+        }
+    }
+
+    pub fn preflop_abstraction(&self) -> PreflopAbstraction {
+        match self.preflop_abstraction.as_str() {
+            "lossless" => PreflopAbstraction::Lossless,
+            "suit_aware" => PreflopAbstraction::SuitAware,
+            "clustered" => PreflopAbstraction::Clustered,
+            _ => panic!(
+                "Bad preflop_abstraction in params.toml: {}",
+                self.preflop_abstraction
+            ),
+        }
+    }
+
+    pub fn hole_cards(&self) -> usize {
+        match self.variant() {
+            Variant::Omaha => 4,
+            _ => 2,
+        }
+    }
+
+    // The heads-up trainer, the bot and their LBR work with whole ranges of two card hands.
+    // Omaha is only trained by the multi-way trainer, which deals individual hands, and
+    // multiway_exploitability evaluates its heads-up blueprints.
+    pub fn require_two_hole_cards(&self, feature: &str) {
+        assert!(
+            self.hole_cards() == 2,
+            "{feature} only supports two hole cards, not variant = \"{}\". Omaha is trained with train_multiway.",
+            self.variant
+        );
+    }
+
+    // Omaha is played pot-limit, and the hold'em variants are no-limit
+    pub fn pot_limit(&self) -> bool {
+        self.variant() == Variant::Omaha
+    }
+
+    // The street after which the hand goes to showdown
+    pub fn last_street(&self) -> usize {
+        street_named(&self.last_street, "last_street")
+    }
+
+    // The first street on which LBR bets and raises. Before it, LBR only calls or folds.
+    pub fn lbr_raise_from(&self) -> usize {
+        street_named(&self.lbr_raise_from, "lbr_raise_from")
+    }
+
+    // Each player's stack after posting the ante
+    pub fn starting_stack(&self) -> Amount {
+        self.stack_size - self.ante
+    }
+
+    // The forced bet posted by the player before the cards are dealt. With a straddle, the
+    // dealer posts the straddle instead of the small blind.
+    pub fn blind(&self, player: usize) -> Amount {
+        match player {
+            DEALER if self.straddle > 0 => self.straddle,
+            DEALER => self.small_blind,
+            OPPONENT => self.big_blind,
+            _ => panic!("Bad player ID"),
+        }
+    }
+
+    // The largest forced bet, which is the amount to call at the start of the hand
+    pub fn largest_blind(&self) -> Amount {
+        self.blind(DEALER).max(self.blind(OPPONENT))
+    }
+
+    // The dealer acts first preflop, unless they straddled, in which case the big blind
+    // acts first. The big blind always acts first after the flop.
+    pub fn first_player(&self, street: usize) -> usize {
+        if street == PREFLOP && self.straddle == 0 {
+            DEALER
+        } else {
+            OPPONENT
+        }
+    }
+}
+
+fn street_named(name: &str, field: &str) -> usize {
+    match name {
+        "preflop" => PREFLOP,
+        "flop" => FLOP,
+        "turn" => TURN,
+        "river" => RIVER,
+        _ => panic!("Bad {field} in params.toml: {name}"),
+    }
+}
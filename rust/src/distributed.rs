@@ -0,0 +1,278 @@
+// Distributed training. A coordinator holds the master blueprint and hands out shards of the
+// training iterations to worker processes, on this machine or others. Each worker runs its
+// iterations on its own copy of the blueprint, then sends back how much its regrets and
+// strategy sums changed. The coordinator merges every worker's changes into the master and
+// sends the combined changes back, so that all the copies agree again before the next round.
+//
+// Strategy sums and visit counts only ever grow, so the workers' changes to them add up. But
+// DCFR discounts the regrets, and each worker discounts the master's regrets as it trains, so
+// adding up K workers' changes would discount them K times and can flip their sign. The
+// coordinator averages the workers' regrets instead, which discounts the master's regrets
+// once, and is the same as training on the master with a single worker.
+
+use crate::game::{Game, Holdem};
+use crate::nodes::{Node, Nodes};
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// The change in the nodes at each history since the last merge
+pub type Deltas<H> = Vec<(H, Vec<Node>)>;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Message<H> {
+    // Sent to each worker when it connects, with the master blueprint so far
+    Start {
+        bet_abstraction: Vec<Vec<f64>>,
+        nodes: Deltas<H>,
+    },
+    // Run the training iterations with these indices
+    Work {
+        first: usize,
+        last: usize,
+    },
+    Deltas(Deltas<H>),
+    Done,
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Connection {
+        stream.set_nodelay(true).unwrap();
+        Connection {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: BufWriter::new(stream),
+        }
+    }
+
+    fn send<H: Serialize>(&mut self, message: &Message<H>) {
+        bincode::serialize_into(&mut self.writer, message).expect("Failed to send message");
+        self.writer.flush().unwrap();
+    }
+
+    fn receive<H: DeserializeOwned>(&mut self) -> Message<H> {
+        bincode::deserialize_from(&mut self.reader).expect("Lost connection")
+    }
+}
+
+pub struct Coordinator<G: Game = Holdem> {
+    pub nodes: Nodes<G>,
+    pub iterations: usize,
+    workers: Vec<Connection>,
+}
+
+impl<G: Game> Coordinator<G>
+where
+    G::History: Serialize + DeserializeOwned,
+{
+    // Waits for n_workers workers to connect and sends each of them the starting blueprint.
+    pub fn new(listener: TcpListener, n_workers: usize, nodes: Nodes<G>) -> Coordinator<G> {
+        assert!(
+            n_workers > 0,
+            "Distributed training needs at least one worker"
+        );
+        let mut workers = Vec::new();
+        while workers.len() < n_workers {
+            let (stream, address) = listener.accept().unwrap();
+            println!("[INFO] Worker {} connected from {address}", workers.len());
+            let mut worker = Connection::new(stream);
+            worker.send(&Message::Start {
+                bet_abstraction: nodes.bet_abstraction.clone(),
+                nodes: snapshot(&nodes),
+            });
+            workers.push(worker);
+        }
+        Coordinator {
+            nodes,
+            iterations: 0,
+            workers,
+        }
+    }
+
+    // Runs iters training iterations across the workers, merging their changes every
+    // sync_every iterations.
+    pub fn train(&mut self, iters: usize, sync_every: usize) {
+        let end = self.iterations + iters;
+        while self.iterations < end {
+            let round = sync_every.max(1).min(end - self.iterations);
+            let n_workers = self.workers.len();
+            for (i, worker) in self.workers.iter_mut().enumerate() {
+                worker.send(&Message::<G::History>::Work {
+                    first: self.iterations + round * i / n_workers,
+                    last: self.iterations + round * (i + 1) / n_workers,
+                });
+            }
+
+            let merged = Nodes::<G>::default();
+            for worker in &mut self.workers {
+                match worker.receive() {
+                    Message::Deltas(deltas) => apply_deltas(&merged, &deltas),
+                    message => panic!("Expected deltas from the worker, got {message:?}"),
+                }
+            }
+            average_regrets(&merged, n_workers);
+            let merged = snapshot(&merged);
+            apply_deltas(&self.nodes, &merged);
+            for worker in &mut self.workers {
+                worker.send(&Message::Deltas(merged.clone()));
+            }
+            self.iterations += round;
+        }
+    }
+
+    // Tells the workers to exit and returns the master blueprint.
+    pub fn finish(mut self) -> Nodes<G> {
+        for worker in &mut self.workers {
+            worker.send(&Message::<G::History>::Done);
+        }
+        self.nodes
+    }
+}
+
+// Connects to the coordinator and runs the iterations it hands out until it says it's done.
+// iteration runs the training iteration with the given index on the worker's blueprint.
+pub fn run_worker<G, A, F>(address: A, iteration: F)
+where
+    G: Game,
+    G::History: Serialize + DeserializeOwned,
+    A: ToSocketAddrs + Copy,
+    F: Fn(&Nodes<G>, usize) + Sync,
+{
+    let mut coordinator = Connection::new(connect(address));
+    let (local, baseline) = match coordinator.receive::<G::History>() {
+        Message::Start {
+            bet_abstraction,
+            nodes,
+        } => {
+            let local = with_bet_abstraction(&bet_abstraction);
+            let baseline = with_bet_abstraction(&bet_abstraction);
+            apply_deltas(&local, &nodes);
+            apply_deltas(&baseline, &nodes);
+            (local, baseline)
+        }
+        message => panic!("Expected the starting blueprint, got {message:?}"),
+    };
+
+    loop {
+        match coordinator.receive::<G::History>() {
+            Message::Work { first, last } => {
+                (first..last)
+                    .into_par_iter()
+                    .for_each(|i| iteration(&local, i));
+                coordinator.send(&Message::Deltas(node_deltas(&local, &baseline)));
+            }
+            // Everyone's changes, including this worker's own
+            Message::Deltas(merged) => {
+                apply_deltas(&baseline, &merged);
+                for (history, _) in &merged {
+                    let nodes = baseline.dashmap.get(history).unwrap();
+                    let nodes = nodes.iter().map(|n| Mutex::new(n.lock().unwrap().clone()));
+                    local.dashmap.insert(history.clone(), nodes.collect());
+                }
+            }
+            Message::Done => return,
+            message => panic!("Unexpected message from the coordinator: {message:?}"),
+        }
+    }
+}
+
+// The coordinator may not be up yet when a worker starts, so keep trying for a while.
+fn connect<A: ToSocketAddrs + Copy>(address: A) -> TcpStream {
+    for _ in 0..60 {
+        if let Ok(stream) = TcpStream::connect(address) {
+            return stream;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+    panic!("Couldn't connect to the coordinator");
+}
+
+fn with_bet_abstraction<G: Game>(bet_abstraction: &[Vec<f64>]) -> Nodes<G> {
+    Nodes {
+        bet_abstraction: bet_abstraction.to_vec(),
+        ..Nodes::default()
+    }
+}
+
+fn snapshot<G: Game>(nodes: &Nodes<G>) -> Deltas<G::History> {
+    nodes
+        .dashmap
+        .iter()
+        .map(|elem| {
+            let history_nodes = elem.value().iter();
+            let history_nodes = history_nodes.map(|n| n.lock().unwrap().clone()).collect();
+            (elem.key().clone(), history_nodes)
+        })
+        .collect()
+}
+
+// The difference between nodes and baseline, at the histories where they differ
+pub fn node_deltas<G: Game>(nodes: &Nodes<G>, baseline: &Nodes<G>) -> Deltas<G::History> {
+    let mut deltas = Vec::new();
+    for elem in &nodes.dashmap {
+        let base = baseline.dashmap.get(elem.key());
+        let mut changed = false;
+        let history_deltas: Vec<Node> = elem
+            .value()
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let mut delta = n.lock().unwrap().clone();
+                if let Some(base) = &base {
+                    let base = base[i].lock().unwrap();
+                    for a in 0..delta.num_actions {
+                        delta.regrets[a] -= base.regrets[a];
+                        delta.strategy_sum[a] -= base.strategy_sum[a];
+                    }
+                    delta.t -= base.t;
+                }
+                changed |= delta != Node::new(delta.num_actions);
+                delta
+            })
+            .collect();
+        if changed {
+            deltas.push((elem.key().clone(), history_deltas));
+        }
+    }
+    deltas
+}
+
+// Adds the deltas into nodes, creating the histories that nodes doesn't have yet.
+pub fn apply_deltas<G: Game>(nodes: &Nodes<G>, deltas: &Deltas<G::History>) {
+    for (history, history_deltas) in deltas {
+        let history_nodes = nodes.dashmap.entry(history.clone()).or_insert_with(|| {
+            let new_nodes = history_deltas.iter().map(|d| Node::new(d.num_actions));
+            new_nodes.map(Mutex::new).collect()
+        });
+        for (node, delta) in history_nodes.iter().zip(history_deltas) {
+            let mut node = node.lock().unwrap();
+            for a in 0..node.num_actions {
+                node.regrets[a] += delta.regrets[a];
+                node.strategy_sum[a] += delta.strategy_sum[a];
+            }
+            node.t += delta.t;
+        }
+    }
+}
+
+// Turns the sum of the workers' regret changes into their average. A worker that didn't
+// visit a node left its regrets as they were, so it counts as no change.
+fn average_regrets<G: Game>(nodes: &Nodes<G>, n_workers: usize) {
+    for elem in &nodes.dashmap {
+        for node in elem.value() {
+            let mut node = node.lock().unwrap();
+            for a in 0..node.num_actions {
+                node.regrets[a] /= n_workers as f32;
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Equity of a hand or range against another hand or range on any board. When there are few
+// enough showdowns, every runout is enumerated for an exact answer, and otherwise runouts and
+// hands are sampled until the time limit runs out.
+
+use crate::card_utils::*;
+use crate::config::CONFIG;
+use crate::ranges::*;
+use itertools::Itertools;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use smallvec::ToSmallVec;
+use std::time::{Duration, Instant};
+
+// Largest number of runouts times pairs of hands to enumerate exactly. Hand vs hand is exact
+// from preflop, and range vs range from the turn.
+const MAX_EXACT_SHOWDOWNS: usize = 100_000_000;
+pub const EQUITY_TIME_LIMIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Equity {
+    pub win: f64,
+    pub tie: f64,
+    pub lose: f64,
+    // Number of showdowns evaluated
+    pub samples: u64,
+    pub exact: bool,
+}
+
+impl Equity {
+    // Share of the pot won, counting ties as half
+    pub fn equity(&self) -> f64 {
+        self.win + self.tie / 2.0
+    }
+
+    fn add(mut self, other: Equity) -> Equity {
+        self.win += other.win;
+        self.tie += other.tie;
+        self.lose += other.lose;
+        self.samples += other.samples;
+        self
+    }
+
+    fn normalize(mut self) -> Equity {
+        let total = self.win + self.tie + self.lose;
+        assert!(total > 0.0, "The hands block each other");
+        self.win /= total;
+        self.tie /= total;
+        self.lose /= total;
+        self
+    }
+}
+
+pub fn hand_vs_hand(
+    hero: &[Card],
+    villain: &[Card],
+    board: &[Card],
+    time_limit: Duration,
+) -> Equity {
+    range_vs_range(
+        &Range::from_holes(&[hero.to_smallvec()]),
+        &Range::from_holes(&[villain.to_smallvec()]),
+        board,
+        time_limit,
+    )
+}
+
+pub fn hand_vs_range(
+    hero: &[Card],
+    villain: &Range,
+    board: &[Card],
+    time_limit: Duration,
+) -> Equity {
+    range_vs_range(
+        &Range::from_holes(&[hero.to_smallvec()]),
+        villain,
+        board,
+        time_limit,
+    )
+}
+
+pub fn range_vs_range(
+    hero: &Range,
+    villain: &Range,
+    board: &[Card],
+    time_limit: Duration,
+) -> Equity {
+    range_vs_range_capped(hero, villain, board, time_limit, MAX_EXACT_SHOWDOWNS)
+}
+
+// Like range_vs_range, but only enumerates up to max_exact_showdowns showdowns, and samples
+// within the time limit otherwise. Exact enumeration isn't bounded by the time limit.
+pub fn range_vs_range_capped(
+    hero: &Range,
+    villain: &Range,
+    board: &[Card],
+    time_limit: Duration,
+    max_exact_showdowns: usize,
+) -> Equity {
+    let hero = weighted_holes(hero, board);
+    let villain = weighted_holes(villain, board);
+    assert!(
+        !hero.is_empty() && !villain.is_empty(),
+        "A range is empty on this board"
+    );
+    let mut rest_of_deck = deck();
+    rest_of_deck.retain(|c| !board.contains(c));
+    let runouts = n_choose_k(rest_of_deck.len(), 5 - board.len());
+    if runouts * hero.len() * villain.len() <= max_exact_showdowns {
+        exact_equity(&hero, &villain, board, &rest_of_deck)
+    } else {
+        sampled_equity(&hero, &villain, board, &rest_of_deck, time_limit)
+    }
+}
+
+// Parses "random" for every hand, or a comma separated list of holes such as "AhKd,QsQc".
+pub fn parse_range(range: &str) -> Result<Range, String> {
+    if range == "random" {
+        return Ok(Range::new());
+    }
+    let holes = range
+        .split(',')
+        .map(|hole| {
+            let cards = try_str2cards(hole.trim())?;
+            let distinct = cards.iter().all_unique();
+            if cards.len() != CONFIG.hole_cards() || !distinct {
+                return Err(format!("Bad hole cards '{hole}' in range '{range}'"));
+            }
+            Ok(cards.to_smallvec())
+        })
+        .collect::<Result<Vec<Hole>, String>>()?;
+    Ok(Range::from_holes(&holes))
+}
+
+// Checks that the equity of the ranges can be computed on the board: the board is a real
+// board, and some hand in each range doesn't share a card with the board or the other hand.
+// range_vs_range panics otherwise.
+pub fn check_equity_inputs(hero: &Range, villain: &Range, board: &[Card]) -> Result<(), String> {
+    if board.len() > 5 || !board.iter().all_unique() {
+        return Err(format!("Bad board {}", cards2str(board)));
+    }
+    let hero = weighted_holes(hero, board);
+    let villain = weighted_holes(villain, board);
+    let showdown_possible = hero.iter().any(|(hero_hole, _)| {
+        villain
+            .iter()
+            .any(|(villain_hole, _)| !villain_hole.iter().any(|c| hero_hole.contains(c)))
+    });
+    if showdown_possible {
+        Ok(())
+    } else {
+        Err("The ranges and board block each other".to_string())
+    }
+}
+
+// The hands in the range that the board doesn't block, with their weights
+fn weighted_holes(range: &Range, board: &[Card]) -> Vec<(Hole, f64)> {
+    range
+        .hands
+        .iter()
+        .zip(range.probs.iter())
+        .filter(|(hole, &prob)| prob > PROB_CUTOFF && !hole.iter().any(|c| board.contains(c)))
+        .map(|(hole, &prob)| (hole.clone(), prob))
+        .collect()
+}
+
+fn exact_equity(
+    hero: &[(Hole, f64)],
+    villain: &[(Hole, f64)],
+    board: &[Card],
+    rest_of_deck: &[Card],
+) -> Equity {
+    // Every pair of hands that doesn't share a card is dealt with every runout they don't
+    // block, and the number of those runouts is the same for every pair, so each showdown
+    // counts with the weight of its pair.
+    let pairs: Vec<(usize, usize, f64)> = hero
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (h, hero_prob))| {
+            villain
+                .iter()
+                .enumerate()
+                .filter(move |(_, (v, _))| !h.iter().any(|c| v.contains(c)))
+                .map(move |(j, (_, villain_prob))| (i, j, hero_prob * villain_prob))
+        })
+        .collect();
+
+    let result = rest_of_deck
+        .iter()
+        .combinations(5 - board.len())
+        .par_bridge()
+        .map(|runout| {
+            let full_board: SmallVecHand = board
+                .iter()
+                .chain(runout.iter().cloned())
+                .cloned()
+                .collect();
+            let strength = |hole: &Hole| -> Option<i32> {
+                if hole.iter().any(|c| runout.contains(&c)) {
+                    None
+                } else {
+                    Some(showdown_strength(hole, &full_board))
+                }
+            };
+            let hero_strengths: Vec<Option<i32>> = hero.iter().map(|(h, _)| strength(h)).collect();
+            let villain_strengths: Vec<Option<i32>> =
+                villain.iter().map(|(v, _)| strength(v)).collect();
+            let mut equity = Equity::default();
+            for &(i, j, weight) in &pairs {
+                if let (Some(h), Some(v)) = (hero_strengths[i], villain_strengths[j]) {
+                    record_showdown(&mut equity, h, v, weight);
+                }
+            }
+            equity
+        })
+        .reduce(Equity::default, Equity::add);
+    Equity {
+        exact: true,
+        ..result.normalize()
+    }
+}
+
+fn sampled_equity(
+    hero: &[(Hole, f64)],
+    villain: &[(Hole, f64)],
+    board: &[Card],
+    rest_of_deck: &[Card],
+    time_limit: Duration,
+) -> Equity {
+    let deadline = Instant::now() + time_limit;
+    let hero_dist = WeightedIndex::new(hero.iter().map(|h| h.1)).unwrap();
+    let villain_dist = WeightedIndex::new(villain.iter().map(|v| v.1)).unwrap();
+    let result = (0..rayon::current_num_threads())
+        .into_par_iter()
+        .map(|_| {
+            let mut rng = thread_rng();
+            let mut equity = Equity::default();
+            let mut full_board: SmallVecHand = SmallVecHand::new();
+            // Check the time every so often, and always take at least one batch of samples
+            loop {
+                for _ in 0..1000 {
+                    let h = &hero[hero_dist.sample(&mut rng)].0;
+                    let v = &villain[villain_dist.sample(&mut rng)].0;
+                    if h.iter().any(|c| v.contains(c)) {
+                        continue;
+                    }
+                    full_board.clear();
+                    full_board.extend_from_slice(board);
+                    while full_board.len() < 5 {
+                        let card = *rest_of_deck.choose(&mut rng).unwrap();
+                        if !full_board.contains(&card) && !h.contains(&card) && !v.contains(&card) {
+                            full_board.push(card);
+                        }
+                    }
+                    let hero_strength = showdown_strength(h, &full_board);
+                    let villain_strength = showdown_strength(v, &full_board);
+                    record_showdown(&mut equity, hero_strength, villain_strength, 1.0);
+                }
+                if Instant::now() >= deadline {
+                    break;
+                }
+            }
+            equity
+        })
+        .reduce(Equity::default, Equity::add);
+    result.normalize()
+}
+
+fn record_showdown(equity: &mut Equity, hero: i32, villain: i32, weight: f64) {
+    if hero > villain {
+        equity.win += weight;
+    } else if hero < villain {
+        equity.lose += weight;
+    } else {
+        equity.tie += weight;
+    }
+    equity.samples += 1;
+}
@@ -5,6 +5,8 @@ use crate::bot::Bot;
 use crate::card_utils;
 use crate::card_utils::*;
 use crate::config::CONFIG;
+use crate::game::{Game, SmallGame};
+use crate::multiway::*;
 use crate::nodes::*;
 use crate::ranges::*;
 use crate::trainer_utils::*;
@@ -14,25 +16,44 @@ use rayon::iter::IntoParallelIterator;
 use rayon::iter::ParallelIterator;
 use smallvec::{SmallVec, ToSmallVec};
 
-const BETS: [f64; 2] = [1.0, ALL_IN];
-
 pub fn bot_exploitability(bot: &Bot, iters: usize) -> f64 {
     let get_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
         bot.get_strategy(hole, board, history)
     };
-    exploitability(&get_strategy, iters)
+    exploitability(&get_strategy, iters).0
 }
 
 pub fn blueprint_exploitability(blueprint: &Nodes, iters: usize) -> f64 {
+    blueprint_exploitability_interval(blueprint, iters).0
+}
+
+// The LBR exploitability in BB/h and the half-width of its 95% confidence interval
+pub fn blueprint_exploitability_interval(blueprint: &Nodes, iters: usize) -> (f64, f64) {
     let get_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
         blueprint.get_strategy(hole, board, history)
     };
     exploitability(&get_strategy, iters)
 }
 
-fn exploitability<F>(get_strategy: &F, iters: usize) -> f64
+fn exploitability<F>(get_strategy: &F, iters: usize) -> (f64, f64)
 where
     F: Fn(&[Card], &[Card], &ActionHistory) -> Strategy + std::marker::Sync,
+{
+    CONFIG.require_two_hole_cards("LBR");
+    lbr_interval(iters, || play_hand(get_strategy))
+}
+
+// LBR against a heads-up blueprint of the multi-way trainer, which is how Omaha is trained.
+// The opponent's range has every hole of the variant, which in Omaha is 270,725 hands.
+// Returns the exploitability in BB/h and the half-width of its 95% confidence interval.
+pub fn multiway_exploitability(nodes: &MultiwayNodes, iters: usize) -> (f64, f64) {
+    assert_eq!(CONFIG.num_players, 2, "LBR needs a heads-up blueprint");
+    lbr_interval(iters, || play_multiway_hand(nodes))
+}
+
+fn lbr_interval<F>(iters: usize, play: F) -> (f64, f64)
+where
+    F: Fn() -> f64 + std::marker::Sync,
 {
     println!("[INFO] Calculating exploitability...");
     let bar = card_utils::pbar(iters as usize);
@@ -40,7 +61,7 @@ where
     let exploits: Vec<f64> = (0..iters)
         .into_par_iter()
         .map(|_i| {
-            let e = play_hand(get_strategy) / (CONFIG.big_blind as f64);
+            let e = play() / (CONFIG.big_blind as f64);
             bar.inc(1);
             e
         })
@@ -51,7 +72,7 @@ where
     let std = statistical::standard_deviation(&exploits, Some(mean));
     let confidence = 1.96 * std / (iters as f64).sqrt();
     println!("Exploitability: {mean} +/- {confidence} BB/h\n");
-    mean
+    (mean, confidence)
 }
 
 pub fn play_hand<F>(get_strategy: &F) -> f64
@@ -100,6 +121,171 @@ where
     terminal_utility(&exploiter_hole, &opp_hole, &board, &history, exploiter)
 }
 
+// Like play_hand, for a blueprint of the multi-way trainer. LBR bets the blueprint's own sizes,
+// so that the opponent's strategy after each bet is in the blueprint.
+pub fn play_multiway_hand(nodes: &MultiwayNodes) -> f64 {
+    let mut deck = card_utils::deck();
+    let mut rng = &mut rand::thread_rng();
+    deck.shuffle(&mut rng);
+    let exploiter = *[0, 1].choose(&mut rng).unwrap();
+    let n = CONFIG.hole_cards();
+    let holes = [&deck[..n], &deck[n..2 * n]];
+    let full_board = &deck[2 * n..2 * n + 5];
+    let mut opp_range = Range::new();
+    opp_range.remove_blockers(holes[exploiter]);
+
+    let mut history = MultiwayHistory::new(2);
+    while !history.hand_over() {
+        let board = &full_board[..board_length(history.street)];
+        opp_range.remove_blockers(board);
+        let actions = history.next_actions(&nodes.bet_abstraction);
+        let action = if history.player == exploiter {
+            multiway_local_best_response(nodes, &opp_range, holes[exploiter], board, &history)
+        } else {
+            let strategies = nodes.average_strategies(&history);
+            let bucket = multiway_card_bucket(holes[1 - exploiter], board, history.street);
+            let index = sample_index(&strategies[bucket as usize]);
+            // Update the range given that they made the action
+            opp_range.update(|cards| {
+                let bucket = multiway_card_bucket(cards, board, history.street);
+                strategies[bucket as usize][index] as f64
+            });
+            actions[index].clone()
+        };
+        history.add(&action);
+    }
+    let strengths: Vec<i32> = holes
+        .iter()
+        .map(|hole| showdown_strength(hole, full_board))
+        .collect();
+    history.utility(&strengths)[exploiter]
+}
+
+fn multiway_local_best_response(
+    nodes: &MultiwayNodes,
+    opp_range: &Range,
+    exploiter_hole: &[Card],
+    board: &[Card],
+    history: &MultiwayHistory,
+) -> Action {
+    let win_prob = win_probability(opp_range, exploiter_hole, board);
+    let pot = history.pot() as f64;
+    let to_call = history.to_call() as f64;
+    let call_util = win_prob * pot - (1.0 - win_prob) * to_call;
+    let mut max_util = call_util;
+    let mut best_action = Action {
+        action: ActionType::Call,
+        amount: history.to_call(),
+    };
+    if history.street < CONFIG.lbr_raise_from() {
+        return best_action;
+    }
+    // The opponent's buckets are the same after every bet
+    let buckets: Vec<i32> = opp_range
+        .hands
+        .iter()
+        .zip(opp_range.probs.iter())
+        .map(|(hole, &prob)| {
+            if prob < PROB_CUTOFF {
+                0
+            } else {
+                multiway_card_bucket(hole, board, history.street)
+            }
+        })
+        .collect();
+    for action in history.next_actions(&nodes.bet_abstraction) {
+        if action.action != ActionType::Bet {
+            continue;
+        }
+        let mut next_history = history.clone();
+        next_history.add(&action);
+        let strategies = nodes.average_strategies(&next_history);
+        let Some(fold) = next_history
+            .next_actions(&nodes.bet_abstraction)
+            .iter()
+            .position(|a| a == &FOLD)
+        else {
+            continue;
+        };
+        let mut fold_prob = 0.0;
+        let mut new_range = opp_range.clone();
+        for (i, &bucket) in buckets.iter().enumerate() {
+            let hand_prob = opp_range.probs[i];
+            if hand_prob < PROB_CUTOFF {
+                continue;
+            }
+            let fold_strategy = strategies[bucket as usize][fold] as f64;
+            fold_prob += hand_prob * fold_strategy;
+            new_range.probs[i] = hand_prob * (1.0 - fold_strategy);
+        }
+        new_range.normalize_range();
+        // The same greedy approximation as local_best_response
+        let win_prob = win_probability(&new_range, exploiter_hole, board);
+        let amount = action.amount as f64;
+        let util = fold_prob * pot
+            + (1.0 - fold_prob)
+                * (win_prob * (pot + amount) - (1.0 - win_prob) * (to_call + amount));
+        if util > max_util {
+            max_util = util;
+            best_action = action;
+        }
+    }
+    if max_util <= 0.0 && history.is_legal_next_action(&FOLD) {
+        FOLD
+    } else {
+        best_action
+    }
+}
+
+// Plays two strategies against each other, alternating seats. Returns the first strategy's
+// winnings and the 95% confidence interval, in BB/h.
+pub fn head_to_head<F, G>(a: &F, b: &G, hands: usize) -> (f64, f64)
+where
+    F: Fn(&[Card], &[Card], &ActionHistory) -> Strategy + std::marker::Sync,
+    G: Fn(&[Card], &[Card], &ActionHistory) -> Strategy + std::marker::Sync,
+{
+    println!("[INFO] Playing {hands} hands head to head...");
+    let bar = card_utils::pbar(hands);
+    let winnings: Vec<f64> = (0..hands)
+        .into_par_iter()
+        .map(|i| {
+            let w = play_match_hand(a, b, i % 2) / (CONFIG.big_blind as f64);
+            bar.inc(1);
+            w
+        })
+        .collect();
+    bar.finish();
+    let mean = statistical::mean(&winnings);
+    let std = statistical::standard_deviation(&winnings, Some(mean));
+    let confidence = 1.96 * std / (hands as f64).sqrt();
+    (mean, confidence)
+}
+
+fn play_match_hand<F, G>(a: &F, b: &G, a_seat: usize) -> f64
+where
+    F: Fn(&[Card], &[Card], &ActionHistory) -> Strategy,
+    G: Fn(&[Card], &[Card], &ActionHistory) -> Strategy,
+{
+    let mut deck = card_utils::deck();
+    deck.shuffle(&mut rand::thread_rng());
+    let mut history = ActionHistory::new();
+    while !history.hand_over() {
+        let hand = get_hand(&deck, history.player, history.street);
+        let (hole, board) = hand.split_at(CONFIG.hole_cards());
+        let strategy = if history.player == a_seat {
+            a(hole, board, &history)
+        } else {
+            b(hole, board, &history)
+        };
+        history.add(&sample_action_from_strategy(&strategy));
+    }
+    let a_hole = get_hand(&deck, a_seat, PREFLOP);
+    let b_hole = get_hand(&deck, 1 - a_seat, PREFLOP);
+    let board_start = 2 * CONFIG.hole_cards();
+    let board = &deck[board_start..board_start + 5];
+    terminal_utility(&a_hole, &b_hole, board, &history, a_seat)
+}
+
 fn local_best_response<F>(
     get_strategy: F,
     opp_range: &Range,
@@ -115,7 +301,7 @@ where
     let exploiter_hand = get_hand(deck, history.player, history.street);
     let exploiter_hole = &exploiter_hand[..2];
     let board = &exploiter_hand[2..];
-    let win_prob = win_probability_rollout(opp_range, exploiter_hole, board);
+    let win_prob = win_probability(opp_range, exploiter_hole, board);
     let pot = history.pot() as f64;
     let to_call = history.to_call() as f64;
 
@@ -126,9 +312,10 @@ where
         action: ActionType::Call,
         amount: history.to_call(),
     };
-    let bet_abstraction = vec![BETS.to_vec(); 4]; // Bet abstraction is the same for every street
-    for action in history.next_actions(&bet_abstraction) {
-        if action.action != ActionType::Bet {
+    // Only call or fold before the street LBR starts raising on
+    let may_raise = history.street >= CONFIG.lbr_raise_from();
+    for action in history.next_actions(&CONFIG.lbr_bets) {
+        if action.action != ActionType::Bet || !may_raise {
             continue;
         }
         // fold_prob is the probability that the opponent folds after we do this action
@@ -162,7 +349,7 @@ where
         new_range.normalize_range();
         // Calculate the utility of this action assuming we both check/call to
         // showdown after this
-        let win_prob = win_probability_rollout(&new_range, exploiter_hole, board);
+        let win_prob = win_probability(&new_range, exploiter_hole, board);
         let amount = action.amount as f64;
 
         // The greedy approximation of the expected utility of each possible bet size is:
@@ -188,6 +375,50 @@ where
     }
 }
 
+// The exploiter's chance of winning at showdown against the range, exactly on the river if
+// lbr_exact_river is set and from rollouts otherwise
+fn win_probability(opp_range: &Range, exploiter_hole: &[Card], board: &[Card]) -> f64 {
+    if CONFIG.lbr_exact_river && board.len() == 5 {
+        // None if no hand in the range is left after the blockers
+        if let Some(win_prob) = exact_river_win_probability(opp_range, exploiter_hole, board) {
+            return win_prob;
+        }
+    }
+    win_probability_rollout(opp_range, exploiter_hole, board)
+}
+
+pub fn exact_river_win_probability(
+    opp_range: &Range,
+    exploiter_hole: &[Card],
+    board: &[Card],
+) -> Option<f64> {
+    let exploiter_strength = showdown_strength(exploiter_hole, board);
+    let mut wins = 0.0;
+    let mut total = 0.0;
+    for (opp_hole, &prob) in opp_range.hands.iter().zip(opp_range.probs.iter()) {
+        let blocked = opp_hole
+            .iter()
+            .any(|c| board.contains(c) || exploiter_hole.contains(c));
+        if prob < PROB_CUTOFF || blocked {
+            continue;
+        }
+        let opp_strength = showdown_strength(opp_hole, board);
+        if exploiter_strength > opp_strength {
+            wins += prob;
+        } else if exploiter_strength == opp_strength {
+            wins += prob / 2.0;
+        }
+        total += prob;
+    }
+    if total > 0.0 {
+        Some(wins / total)
+    } else {
+        None
+    }
+}
+
+// Samples lbr_opponent_samples hands from the range and lbr_rollouts runouts of the board for
+// each
 pub fn win_probability_rollout(opp_range: &Range, exploiter_hole: &[Card], board: &[Card]) -> f64 {
     let mut deck = card_utils::deck();
     // Remove the blockers (exploiter's hole cards and board cards) from the deck
@@ -199,7 +430,7 @@ pub fn win_probability_rollout(opp_range: &Range, exploiter_hole: &[Card], board
 
     let mut wins = 0.0;
     let mut n = 0;
-    for _i in 0..100 {
+    for _i in 0..CONFIG.lbr_opponent_samples {
         // Sample a random opponent hand from their range
         let opp_hole = opp_range.sample_hand();
         let opp_hole: SmallVecHand = opp_hole.to_smallvec();
@@ -209,10 +440,9 @@ pub fn win_probability_rollout(opp_range: &Range, exploiter_hole: &[Card], board
         subdeck.retain(|c| !opp_hole.contains(c));
 
         // Sample some possible rollouts from the remaining deck
-        const N_ROLLOUTS: usize = 10;
-        let mut rollouts: Vec<SmallVec<[Card; 5]>> = Vec::with_capacity(N_ROLLOUTS);
+        let mut rollouts: Vec<SmallVec<[Card; 5]>> = Vec::with_capacity(CONFIG.lbr_rollouts);
         let mut rng = rand::thread_rng();
-        for _i in 0..N_ROLLOUTS {
+        for _i in 0..CONFIG.lbr_rollouts {
             let rollout: SmallVec<[Card; 5]> = subdeck
                 .choose_multiple(&mut rng, 5 - board_len)
                 .cloned()
@@ -223,12 +453,8 @@ pub fn win_probability_rollout(opp_range: &Range, exploiter_hole: &[Card], board
         for rollout in rollouts {
             let mut full_board: SmallVecHand = board.clone();
             full_board.extend(rollout);
-            let mut opp_hand: SmallVecHand = opp_hole.clone();
-            opp_hand.extend(full_board.clone());
-            let mut exploiter_hand: SmallVecHand = exploiter_hole.clone();
-            exploiter_hand.extend(full_board);
-            let exploiter_strength = FAST_HAND_TABLE.hand_strength(&exploiter_hand).unwrap_or(0);
-            let opp_strength = FAST_HAND_TABLE.hand_strength(&opp_hand).unwrap_or(0);
+            let exploiter_strength = showdown_strength(&exploiter_hole, &full_board);
+            let opp_strength = showdown_strength(&opp_hole, &full_board);
 
             if exploiter_strength > opp_strength {
                 wins += 1.0;
@@ -241,3 +467,201 @@ pub fn win_probability_rollout(opp_range: &Range, exploiter_hole: &[Card], board
     let win_prob = wins / n as f64;
     win_prob as f64
 }
+
+// Exact values of a small game's average strategies, found by visiting every deal. LBR only
+// gives a lower bound on hold'em's exploitability, but these are exact for Kuhn and Leduc.
+
+// A board, the hands it leaves, and the probability that the opponent reaches the current
+// history with each of them
+type Deal<G> = (<G as Game>::Board, Vec<<G as Game>::Hand>, Vec<f64>);
+
+// The first player's value when both players play their average strategies
+pub fn game_value<G: SmallGame>(nodes: &Nodes<G>) -> f64 {
+    small_game_value(nodes, DEALER, false)
+}
+
+// What the responder wins when it best responds to the other player's average strategy
+pub fn best_response_value<G: SmallGame>(nodes: &Nodes<G>, responder: usize) -> f64 {
+    small_game_value(nodes, responder, true)
+}
+
+// The average of what each player's best response wins, which is zero exactly at a Nash
+// equilibrium
+pub fn exact_exploitability<G: SmallGame>(nodes: &Nodes<G>) -> f64 {
+    (best_response_value(nodes, DEALER) + best_response_value(nodes, OPPONENT)) / 2.0
+}
+
+fn small_game_value<G: SmallGame>(nodes: &Nodes<G>, responder: usize, best_response: bool) -> f64 {
+    let boards = G::boards();
+    let deals: Vec<Deal<G>> = boards
+        .iter()
+        .map(|board| {
+            let hands = G::hands(board);
+            let opp_reach_probs = vec![1.0; hands.len()];
+            (*board, hands, opp_reach_probs)
+        })
+        .collect();
+    let values = response_values(nodes, &deals, &G::root(), responder, best_response);
+    // Every pair of hands that doesn't conflict is equally likely
+    let total: f64 = deals
+        .iter()
+        .zip(values.iter())
+        .map(|((_, hands, _), values)| {
+            let pairs: usize = hands
+                .iter()
+                .map(|hand| {
+                    hands
+                        .iter()
+                        .filter(|other| !G::conflicts(hand, other))
+                        .count()
+                })
+                .sum();
+            values.iter().sum::<f64>() / pairs as f64
+        })
+        .sum();
+    total / boards.len() as f64
+}
+
+// The responder's value with each hand of each deal, summed over the opponent's hands weighted
+// by their reach probabilities. The responder plays its average strategy, or for a best
+// response, each of its infosets takes the action that wins the most over every deal.
+fn response_values<G: SmallGame>(
+    nodes: &Nodes<G>,
+    deals: &[Deal<G>],
+    history: &G::History,
+    responder: usize,
+    best_response: bool,
+) -> Vec<Vec<f64>> {
+    if G::hand_over(history) {
+        return deals
+            .iter()
+            .map(|(board, hands, opp_reach_probs)| {
+                G::terminal_utility(
+                    hands.clone(),
+                    opp_reach_probs.clone(),
+                    board,
+                    history,
+                    responder,
+                )
+            })
+            .collect();
+    }
+    let actions = G::next_actions(history, &nodes.bet_abstraction);
+    let infosets: Vec<Vec<InfoSet<G::History>>> = deals
+        .iter()
+        .map(|(board, hands, _)| {
+            hands
+                .iter()
+                .map(|hand| G::infoset(nodes, hand, board, history))
+                .collect()
+        })
+        .collect();
+    let strategies: Vec<Vec<SmallVecFloats>> = infosets
+        .iter()
+        .map(|infosets| {
+            infosets
+                .iter()
+                .map(|infoset| average_strategy(nodes, infoset, actions.len()))
+                .collect()
+        })
+        .collect();
+
+    if G::player(history) != responder {
+        let mut values: Vec<Vec<f64>> = deals
+            .iter()
+            .map(|(_, hands, _)| vec![0.0; hands.len()])
+            .collect();
+        for (action_index, action) in actions.iter().enumerate() {
+            let next_deals: Vec<Deal<G>> = deals
+                .iter()
+                .zip(strategies.iter())
+                .map(|((board, hands, opp_reach_probs), strategies)| {
+                    let next_reach_probs = opp_reach_probs
+                        .iter()
+                        .zip(strategies.iter())
+                        .map(|(prob, strategy)| prob * strategy[action_index] as f64)
+                        .collect();
+                    (*board, hands.clone(), next_reach_probs)
+                })
+                .collect();
+            let next_history = G::next_history(history, action);
+            let action_values =
+                response_values(nodes, &next_deals, &next_history, responder, best_response);
+            for (values, action_values) in values.iter_mut().zip(action_values.iter()) {
+                for (value, action_value) in values.iter_mut().zip(action_values.iter()) {
+                    *value += action_value;
+                }
+            }
+        }
+        return values;
+    }
+
+    // Indexed by action, deal and hand
+    let action_values: Vec<Vec<Vec<f64>>> = actions
+        .iter()
+        .map(|action| {
+            let next_history = G::next_history(history, action);
+            response_values(nodes, deals, &next_history, responder, best_response)
+        })
+        .collect();
+    let best_actions: HashMap<i32, usize> = if best_response {
+        let mut totals: HashMap<i32, Vec<f64>> = HashMap::new();
+        for (deal_index, infosets) in infosets.iter().enumerate() {
+            for (hand_index, infoset) in infosets.iter().enumerate() {
+                let total = totals
+                    .entry(infoset.card_bucket)
+                    .or_insert_with(|| vec![0.0; actions.len()]);
+                for (action_index, values) in action_values.iter().enumerate() {
+                    total[action_index] += values[deal_index][hand_index];
+                }
+            }
+        }
+        totals
+            .iter()
+            .map(|(&bucket, total)| {
+                let best = (0..total.len())
+                    .max_by(|&a, &b| total[a].total_cmp(&total[b]))
+                    .unwrap();
+                (bucket, best)
+            })
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    infosets
+        .iter()
+        .zip(strategies.iter())
+        .enumerate()
+        .map(|(deal_index, (infosets, strategies))| {
+            infosets
+                .iter()
+                .zip(strategies.iter())
+                .enumerate()
+                .map(|(hand_index, (infoset, strategy))| {
+                    if best_response {
+                        let best = best_actions[&infoset.card_bucket];
+                        action_values[best][deal_index][hand_index]
+                    } else {
+                        action_values
+                            .iter()
+                            .zip(strategy.iter())
+                            .map(|(values, &prob)| prob as f64 * values[deal_index][hand_index])
+                            .sum()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Histories that training never visited play uniformly at random
+fn average_strategy<G: Game>(
+    nodes: &Nodes<G>,
+    infoset: &InfoSet<G::History>,
+    num_actions: usize,
+) -> SmallVecFloats {
+    match nodes.get(infoset) {
+        Some(node) => node.cumulative_strategy(),
+        None => normalize_smallvec(&vec![1.0; num_actions]),
+    }
+}
@@ -0,0 +1,268 @@
+// What to play at infosets the blueprint doesn't have, like histories that training never
+// reached or that prune_blueprint dropped. The fallbacks are tried in order until one works:
+//
+//   1. The nearest ancestor in the blueprint where the same player acts, on this street or the
+//      one before, mapped to the actions here with strategy_like
+//   2. The same history with the last bet changed to the nearest other size that's in the
+//      blueprint
+//   3. Solving the subgame, when the bot has subgame solving enabled
+//   4. A heuristic that bets strong hands, calls when the equity beats the pot odds, and
+//      otherwise checks or folds
+//
+// Every fallback is counted, and logged the first time it's needed at a history, so a missing
+// node never takes down the server.
+
+use crate::card_utils::*;
+use crate::equity::hand_vs_range;
+use crate::nodes::Nodes;
+use crate::ranges::Range;
+use crate::trainer_utils::*;
+use ahash::AHashMap as HashMap;
+use dashmap::{DashMap, DashSet};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// Time for the heuristic's preflop equity estimate, which is cached per isomorphic hand
+const HEURISTIC_PREFLOP_EQUITY_TIME: Duration = Duration::from_millis(20);
+// The heuristic bets with at least this much equity against a random hand
+pub const HEURISTIC_BET_EQUITY: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fallback {
+    ParentStreet,
+    NearbyBetSize,
+    Subgame,
+    Heuristic,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Fallback::ParentStreet => "parent street",
+            Fallback::NearbyBetSize => "nearby bet size",
+            Fallback::Subgame => "subgame",
+            Fallback::Heuristic => "heuristic",
+        };
+        write!(f, "{name}")
+    }
+}
+
+const FALLBACKS: [Fallback; 4] = [
+    Fallback::ParentStreet,
+    Fallback::NearbyBetSize,
+    Fallback::Subgame,
+    Fallback::Heuristic,
+];
+
+// The histories whose fallbacks have been logged. LBR asks for the strategy of every hand in
+// the opponent's range, which would otherwise log the same history over a thousand times.
+static LOGGED_HISTORIES: Lazy<DashSet<ActionHistory>> = Lazy::new(DashSet::new);
+static HEURISTIC_EQUITY_CACHE: Lazy<DashMap<SmallVecHand, f64>> = Lazy::new(DashMap::new);
+
+static FALLBACK_COUNTS: [AtomicUsize; 4] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+// How many times each fallback has been used since the program started
+pub fn fallback_counts() -> Vec<(Fallback, usize)> {
+    FALLBACKS
+        .iter()
+        .zip(FALLBACK_COUNTS.iter())
+        .map(|(&fallback, count)| (fallback, count.load(Ordering::Relaxed)))
+        .collect()
+}
+
+// The strategy at an infoset that isn't in the blueprint. subgame solves the subgame from
+// there, if that's allowed.
+pub fn fallback_strategy(
+    nodes: &Nodes,
+    hole: &[Card],
+    board: &[Card],
+    history: &ActionHistory,
+    subgame: Option<&dyn Fn() -> Strategy>,
+) -> Strategy {
+    let (fallback, strategy) = if let Some(strategy) = parent_street(nodes, hole, board, history) {
+        (Fallback::ParentStreet, strategy)
+    } else if let Some(strategy) = nearby_bet_size(nodes, hole, board, history) {
+        (Fallback::NearbyBetSize, strategy)
+    } else if let Some(subgame) = subgame {
+        (Fallback::Subgame, subgame())
+    } else {
+        let actions = history.next_actions(&nodes.bet_abstraction);
+        let equity = heuristic_equity(hole, board);
+        (
+            Fallback::Heuristic,
+            heuristic_strategy(equity, history, &actions),
+        )
+    };
+    let count = FALLBACK_COUNTS[fallback as usize].fetch_add(1, Ordering::Relaxed) + 1;
+    if LOGGED_HISTORIES.insert(history.clone()) {
+        println!(
+            "[WARN] No node for {} {} {history}, used the {fallback} fallback ({count} times so far)",
+            cards2str(hole),
+            cards2str(board)
+        );
+    }
+    strategy
+}
+
+// The hole's equity against a random hand. Postflop that's how often it beats a random hand on
+// the current board, which only takes a hand strength lookup per opponent hand.
+pub fn heuristic_equity(hole: &[Card], board: &[Card]) -> f64 {
+    let hand = isomorphic_hand(&[hole, board].concat());
+    if let Some(equity) = HEURISTIC_EQUITY_CACHE.get(&hand) {
+        return *equity;
+    }
+    let equity = if board.is_empty() {
+        hand_vs_range(hole, &Range::new(), board, HEURISTIC_PREFLOP_EQUITY_TIME).equity()
+    } else {
+        let mut deck = deck();
+        deck.retain(|c| !hole.contains(c) && !board.contains(c));
+        let opp_holes: Vec<[Card; 2]> = deck
+            .iter()
+            .combinations(2)
+            .map(|cards| [*cards[0], *cards[1]])
+            .collect();
+        let strength = FAST_HAND_TABLE.hand_strength(&[hole, board].concat());
+        let opp_strengths = FAST_HAND_TABLE.hole_strengths(&opp_holes, board);
+        let wins: f64 = opp_strengths
+            .iter()
+            .map(|&opp_strength| match strength.cmp(&opp_strength) {
+                std::cmp::Ordering::Greater => 1.0,
+                std::cmp::Ordering::Equal => 0.5,
+                std::cmp::Ordering::Less => 0.0,
+            })
+            .sum();
+        wins / opp_strengths.len() as f64
+    };
+    HEURISTIC_EQUITY_CACHE.insert(hand, equity);
+    equity
+}
+
+fn parent_street(
+    nodes: &Nodes,
+    hole: &[Card],
+    board: &[Card],
+    history: &ActionHistory,
+) -> Option<Strategy> {
+    let actions = history.next_actions(&nodes.bet_abstraction);
+    let oldest_street = history.street.saturating_sub(1);
+    let mut ancestor = history.clone();
+    while !ancestor.is_empty() {
+        ancestor = ancestor.without_last_action();
+        if ancestor.street < oldest_street {
+            return None;
+        }
+        // The opponent's strategy there says nothing about how this player should play
+        if ancestor.player != history.player {
+            continue;
+        }
+        let board = &board[..board_length(ancestor.street)];
+        if let Some(strategy) = nodes.try_get_strategy(hole, board, &ancestor) {
+            return Some(strategy_like(&strategy, &actions));
+        }
+    }
+    None
+}
+
+fn nearby_bet_size(
+    nodes: &Nodes,
+    hole: &[Card],
+    board: &[Card],
+    history: &ActionHistory,
+) -> Option<Strategy> {
+    let actions = history.get_actions();
+    let last_bet = actions.iter().rposition(|a| a.action == ActionType::Bet)?;
+    let mut before_bet = ActionHistory::new();
+    for action in &actions[..last_bet] {
+        before_bet.add(action);
+    }
+    let bet = actions[last_bet].amount as i64;
+    let mut other_bets: Vec<Action> = before_bet
+        .next_actions(&nodes.bet_abstraction)
+        .into_iter()
+        .filter(|a| a.action == ActionType::Bet && a.amount as i64 != bet)
+        .collect();
+    other_bets.sort_by_key(|a| (a.amount as i64 - bet).abs());
+
+    let next_actions = history.next_actions(&nodes.bet_abstraction);
+    for other_bet in other_bets {
+        // Replay the actions after the bet, with calls adjusted to the new size
+        let mut nearby = before_bet.clone();
+        nearby.add(&other_bet);
+        let replayed = actions[last_bet + 1..].iter().all(|action| {
+            let action = nearby.adjust_action(action);
+            let legal = !nearby.hand_over() && nearby.is_legal_next_action(&action);
+            if legal {
+                nearby.add(&action);
+            }
+            legal
+        });
+        if !replayed || nearby.street != history.street || nearby.hand_over() {
+            continue;
+        }
+        if let Some(strategy) = nodes.try_get_strategy(hole, board, &nearby) {
+            return Some(strategy_like(&strategy, &next_actions));
+        }
+    }
+    None
+}
+
+// Bets the smallest size with a strong hand, calls when the equity beats the pot odds, and
+// otherwise checks or folds
+pub fn heuristic_strategy(equity: f64, history: &ActionHistory, actions: &[Action]) -> Strategy {
+    let to_call = history.to_call() as f64;
+    let pot_odds = to_call / (history.pot() as f64 + to_call);
+    let smallest_bet = actions
+        .iter()
+        .filter(|a| a.action == ActionType::Bet)
+        .min_by_key(|a| a.amount);
+    let fold = actions.iter().find(|a| a.action == ActionType::Fold);
+    let call = actions.iter().find(|a| a.action == ActionType::Call);
+    let action = match (smallest_bet, fold) {
+        (Some(bet), _) if equity >= HEURISTIC_BET_EQUITY => bet,
+        (_, Some(fold)) if equity < pot_odds => fold,
+        _ => call.unwrap(),
+    };
+    actions
+        .iter()
+        .map(|a| (a.clone(), if a == action { 1.0 } else { 0.0 }))
+        .collect()
+}
+
+// Maps another node's strategy onto these actions: folding, calling and betting as often as
+// it does, with its betting split evenly between the bet sizes here. If there's no way to
+// fold or bet here, that probability goes to calling.
+pub fn strategy_like(strategy: &Strategy, actions: &[Action]) -> Strategy {
+    let mut probs: HashMap<ActionType, f64> = HashMap::new();
+    for (action, prob) in strategy {
+        *probs.entry(action.action.clone()).or_insert(0.0) += prob;
+    }
+    let count =
+        |action_type: &ActionType| actions.iter().filter(|a| &a.action == action_type).count();
+    let mut like = Strategy::new();
+    for (action_type, prob) in probs {
+        let n = count(&action_type);
+        if n == 0 {
+            let call = actions
+                .iter()
+                .find(|a| a.action == ActionType::Call)
+                .unwrap();
+            *like.entry(call.clone()).or_insert(0.0) += prob;
+            continue;
+        }
+        for action in actions.iter().filter(|a| a.action == action_type) {
+            *like.entry(action.clone()).or_insert(0.0) += prob / n as f64;
+        }
+    }
+    for action in actions {
+        like.entry(action.clone()).or_insert(0.0);
+    }
+    like
+}
@@ -0,0 +1,326 @@
+// The rules of a two player zero-sum poker game, as the trainer sees them. A history is the
+// public actions so far, and Nodes keeps a node at each history for every bucket of the acting
+// player's cards, so that iterate() can traverse the tree with every hand at once.
+// Heads-up no-limit hold'em implements this, and so do Kuhn poker and Leduc hold'em, whose
+// known equilibria check the trainer and the best response code without training the full game.
+
+use crate::card_utils::*;
+use crate::config::CONFIG;
+use crate::nodes::*;
+use crate::ranges::Range;
+use crate::trainer_utils::*;
+use rand::prelude::*;
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hash;
+
+pub trait Game: Sized {
+    type History: Clone + Eq + Hash + fmt::Debug + fmt::Display + Send + Sync;
+    // A player's private cards
+    type Hand: Copy + Send + Sync;
+    // The public cards of a deal. Each street's cards are only seen once the street starts.
+    type Board: Copy + Send + Sync;
+
+    fn root() -> Self::History;
+
+    fn hand_over(history: &Self::History) -> bool;
+
+    // The player to act, DEALER or OPPONENT
+    fn player(history: &Self::History) -> usize;
+
+    fn on_last_street(history: &Self::History) -> bool;
+
+    fn next_actions(
+        history: &Self::History,
+        bet_abstraction: &[Vec<f64>],
+    ) -> SmallVec<[Action; NUM_ACTIONS]>;
+
+    fn next_history(history: &Self::History, action: &Action) -> Self::History;
+
+    // A random board
+    fn deal() -> Self::Board;
+
+    // Every hand that the board doesn't block. Both players' hands are dealt from these.
+    fn hands(board: &Self::Board) -> Vec<Self::Hand>;
+
+    fn infoset(
+        nodes: &Nodes<Self>,
+        hand: &Self::Hand,
+        board: &Self::Board,
+        history: &Self::History,
+    ) -> InfoSet<Self::History>;
+
+    // Number of nodes at a history
+    fn num_nodes(nodes: &Nodes<Self>, history: &Self::History) -> usize;
+
+    // The node of a history that an infoset uses, out of the history's n_nodes
+    fn node_index(nodes: &Nodes<Self>, infoset: &InfoSet<Self::History>, n_nodes: usize) -> usize;
+
+    // The traverser's utility with each of the hands at the end of a hand, against every other
+    // hand the opponent can hold weighted by its reach probability
+    fn terminal_utility(
+        hands: Vec<Self::Hand>,
+        opp_reach_probs: Vec<f64>,
+        board: &Self::Board,
+        history: &Self::History,
+        traverser: usize,
+    ) -> Vec<f64>;
+}
+
+// A game with few enough deals that every one of them can be visited, so that its game value
+// and best responses can be computed exactly. Its card buckets must not lose any information.
+pub trait SmallGame: Game {
+    // Every board, which are all equally likely
+    fn boards() -> Vec<Self::Board>;
+
+    // Whether the two hands share a card, so that the players can't hold both
+    fn conflicts(hand: &Self::Hand, other: &Self::Hand) -> bool;
+}
+
+// Heads-up no-limit hold'em with the stacks, blinds and abstractions in params.toml
+#[derive(Debug)]
+pub struct Holdem;
+
+impl Game for Holdem {
+    type History = ActionHistory;
+    type Hand = [Card; 2];
+    type Board = [Card; 5];
+
+    fn root() -> ActionHistory {
+        ActionHistory::new()
+    }
+
+    fn hand_over(history: &ActionHistory) -> bool {
+        history.hand_over()
+    }
+
+    fn player(history: &ActionHistory) -> usize {
+        history.player
+    }
+
+    fn on_last_street(history: &ActionHistory) -> bool {
+        history.street == CONFIG.last_street()
+    }
+
+    fn next_actions(
+        history: &ActionHistory,
+        bet_abstraction: &[Vec<f64>],
+    ) -> SmallVec<[Action; NUM_ACTIONS]> {
+        history.next_actions(bet_abstraction)
+    }
+
+    fn next_history(history: &ActionHistory, action: &Action) -> ActionHistory {
+        let mut next_history = history.clone();
+        next_history.add(action);
+        next_history
+    }
+
+    fn deal() -> [Card; 5] {
+        let mut deck = deck();
+        deck.shuffle(&mut rand::thread_rng());
+        [deck[0], deck[1], deck[2], deck[3], deck[4]]
+    }
+
+    fn hands(board: &[Card; 5]) -> Vec<[Card; 2]> {
+        let mut range = Range::new();
+        range.remove_blockers(board);
+        let mut hands = Vec::with_capacity(range.hands.len());
+        for hand_index in 0..range.hands.len() {
+            if range.probs[hand_index] > 0.0 {
+                let hand = &range.hands[hand_index];
+                hands.push([hand[0], hand[1]]);
+            }
+        }
+        hands
+    }
+
+    fn infoset(
+        nodes: &Nodes,
+        hand: &[Card; 2],
+        board: &[Card; 5],
+        history: &ActionHistory,
+    ) -> InfoSet {
+        nodes.infoset(hand, board, history)
+    }
+
+    fn num_nodes(nodes: &Nodes, history: &ActionHistory) -> usize {
+        nodes.num_nodes(history)
+    }
+
+    fn node_index(nodes: &Nodes, infoset: &InfoSet, n_nodes: usize) -> usize {
+        nodes.node_index(infoset, n_nodes)
+    }
+
+    fn terminal_utility(
+        hands: Vec<[Card; 2]>,
+        opp_reach_probs: Vec<f64>,
+        board: &[Card; 5],
+        history: &ActionHistory,
+        traverser: usize,
+    ) -> Vec<f64> {
+        terminal_utility_vectorized(hands, opp_reach_probs, board, history, traverser)
+    }
+}
+
+// The public actions of a small game, split into its betting rounds
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct RoundHistory {
+    pub rounds: Vec<Vec<Action>>,
+}
+
+impl RoundHistory {
+    pub fn new() -> RoundHistory {
+        RoundHistory {
+            rounds: vec![Vec::new()],
+        }
+    }
+
+    pub fn round(&self) -> usize {
+        self.rounds.len() - 1
+    }
+
+    pub fn current_round(&self) -> &[Action] {
+        self.rounds.last().unwrap()
+    }
+
+    // Players act in turn, and the first player starts every round
+    pub fn player(&self) -> usize {
+        self.current_round().len() % 2
+    }
+
+    // A round ends when a bet or the second check is called
+    pub fn round_over(&self) -> bool {
+        let actions = self.current_round();
+        actions.len() >= 2 && actions.last().unwrap().action == ActionType::Call
+    }
+
+    // The player who folded, if someone did
+    pub fn folded(&self) -> Option<usize> {
+        let actions = self.current_round();
+        match actions.last() {
+            Some(action) if action.action == ActionType::Fold => Some((actions.len() - 1) % 2),
+            _ => None,
+        }
+    }
+
+    // Chips put into the pot by each player, on top of the antes
+    pub fn contributions(&self) -> [Amount; 2] {
+        let mut contributions = [0, 0];
+        for actions in self.rounds.iter() {
+            for (i, action) in actions.iter().enumerate() {
+                contributions[i % 2] += action.amount;
+            }
+        }
+        contributions
+    }
+
+    pub fn to_call(&self) -> Amount {
+        let contributions = self.contributions();
+        contributions[1 - self.player()] - contributions[self.player()]
+    }
+
+    // The hand is over after a fold, or once the last round ends. add doesn't start a round
+    // after the last one.
+    pub fn hand_over(&self) -> bool {
+        self.folded().is_some() || self.round_over()
+    }
+
+    // Betting the fixed bet size, while the round has had fewer than max_bets bets, calling,
+    // and folding to a bet. Like ActionHistory, a bet's amount includes the call.
+    pub fn next_actions(
+        &self,
+        bet_size: Amount,
+        max_bets: usize,
+    ) -> SmallVec<[Action; NUM_ACTIONS]> {
+        let to_call = self.to_call();
+        let num_bets = self
+            .current_round()
+            .iter()
+            .filter(|a| a.action == ActionType::Bet)
+            .count();
+        let mut actions = SmallVec::new();
+        if num_bets < max_bets {
+            actions.push(Action {
+                action: ActionType::Bet,
+                amount: to_call + bet_size,
+            });
+        }
+        actions.push(Action {
+            action: ActionType::Call,
+            amount: to_call,
+        });
+        if to_call > 0 {
+            actions.push(FOLD);
+        }
+        actions
+    }
+
+    // Adds the action, and starts the next round if it ends this one
+    pub fn add(&self, action: &Action, num_rounds: usize) -> RoundHistory {
+        let mut next = self.clone();
+        next.rounds.last_mut().unwrap().push(action.clone());
+        if next.round_over() && next.round() + 1 < num_rounds {
+            next.rounds.push(Vec::new());
+        }
+        next
+    }
+
+    // The chips that the traverser wins when the hand is over. showdown compares the
+    // traverser's hand with the opponent's, in case nobody folded.
+    pub fn winnings(&self, ante: Amount, traverser: usize, showdown: Ordering) -> f64 {
+        let contributions = self.contributions();
+        let result = match self.folded() {
+            Some(folder) if folder == traverser => Ordering::Less,
+            Some(_) => Ordering::Greater,
+            None => showdown,
+        };
+        match result {
+            Ordering::Greater => (ante + contributions[1 - traverser]) as f64,
+            Ordering::Less => -((ante + contributions[traverser]) as f64),
+            Ordering::Equal => 0.0,
+        }
+    }
+}
+
+impl Default for RoundHistory {
+    fn default() -> RoundHistory {
+        RoundHistory::new()
+    }
+}
+
+impl fmt::Display for RoundHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rounds: Vec<String> = self
+            .rounds
+            .iter()
+            .map(|actions| actions.iter().map(|a| format!("{a},")).collect())
+            .collect();
+        write!(f, "{}", rounds.join("/"))
+    }
+}
+
+// Game::terminal_utility for a small game, where every pair of hands that doesn't conflict
+// is equally likely. compare is the showdown of the traverser's hand with the opponent's.
+pub fn small_game_utility<G: SmallGame<History = RoundHistory>>(
+    hands: &[G::Hand],
+    opp_reach_probs: &[f64],
+    history: &RoundHistory,
+    ante: Amount,
+    traverser: usize,
+    compare: impl Fn(&G::Hand, &G::Hand) -> Ordering,
+) -> Vec<f64> {
+    hands
+        .iter()
+        .map(|hand| {
+            hands
+                .iter()
+                .zip(opp_reach_probs.iter())
+                .filter(|(opp_hand, _)| !G::conflicts(hand, opp_hand))
+                .map(|(opp_hand, prob)| {
+                    prob * history.winnings(ante, traverser, compare(hand, opp_hand))
+                })
+                .sum()
+        })
+        .collect()
+}
@@ -0,0 +1,325 @@
+// Maps every hand to a dense index of its isomorphism class, in the style of Waugh's hand
+// indexer: https://www.cs.cmu.edu/~./kwaugh/publications/isomorphism13.pdf
+//
+// Cards are dealt in rounds (the hole cards, then the board), and two hands are isomorphic
+// if one is a suit permutation of the other with the same cards in each round. The cards of
+// each suit are described by their configuration, the number of cards of that suit in each
+// round, and their pattern, an index of which ranks they are among the hands with that
+// configuration. A hand's index is the offset of its sorted suit configurations plus the
+// index of the multiset of patterns of the suits that share a configuration, so indexing
+// takes a few table lookups instead of a hash of the isomorphic hand.
+
+use crate::card_utils::*;
+use crate::trainer_utils::{FLOP, RIVER, TURN};
+use ahash::AHashMap as HashMap;
+use once_cell::sync::Lazy;
+
+const SUITS: usize = 4;
+const MAX_ROUNDS: usize = 4;
+
+// The postflop indexers treat the board as a single round, since the order the board cards
+// were dealt in doesn't change the hand's strength.
+pub static FLOP_INDEXER: Lazy<HandIndexer> = Lazy::new(|| HandIndexer::new(&[2, 3], lowest_rank()));
+pub static TURN_INDEXER: Lazy<HandIndexer> = Lazy::new(|| HandIndexer::new(&[2, 4], lowest_rank()));
+pub static RIVER_INDEXER: Lazy<HandIndexer> =
+    Lazy::new(|| HandIndexer::new(&[2, 5], lowest_rank()));
+
+pub fn street_indexer(street: usize) -> &'static HandIndexer {
+    match street {
+        FLOP => &FLOP_INDEXER,
+        TURN => &TURN_INDEXER,
+        RIVER => &RIVER_INDEXER,
+        _ => panic!("Bad street"),
+    }
+}
+
+// The number of cards of one suit in each round
+type SuitConfig = [u8; MAX_ROUNDS];
+
+struct Configuration {
+    suits: [SuitConfig; SUITS],
+    // Number of patterns a single suit with each configuration can have
+    pattern_sizes: [u64; SUITS],
+    offset: u64,
+}
+
+pub struct HandIndexer {
+    rounds: Vec<usize>,
+    lowest_rank: u8,
+    num_ranks: usize,
+    configurations: Vec<Configuration>,
+    config_ids: HashMap<[SuitConfig; SUITS], usize>,
+    size: u64,
+}
+
+impl HandIndexer {
+    pub fn new(cards_per_round: &[usize], lowest_rank: u8) -> HandIndexer {
+        assert!(!cards_per_round.is_empty() && cards_per_round.len() <= MAX_ROUNDS);
+        let num_ranks = 15 - lowest_rank as usize;
+        let mut indexer = HandIndexer {
+            rounds: cards_per_round.to_vec(),
+            lowest_rank,
+            num_ranks,
+            configurations: Vec::new(),
+            config_ids: HashMap::new(),
+            size: 0,
+        };
+
+        // Every way to split each round's cards between the suits, with the suits sorted by
+        // configuration from most cards to fewest
+        let mut suit_configs = Vec::new();
+        indexer.suit_configs(0, [0; MAX_ROUNDS], &mut suit_configs);
+        suit_configs.sort_by(|a, b| b.cmp(a));
+        let mut configs = Vec::new();
+        indexer.configurations(&suit_configs, 0, &mut Vec::new(), &mut configs);
+
+        for suits in configs {
+            let pattern_sizes = suits.map(|c| indexer.pattern_size(&c));
+            let configuration = Configuration {
+                suits,
+                pattern_sizes,
+                offset: indexer.size,
+            };
+            indexer.size += configuration_size(&configuration);
+            indexer
+                .config_ids
+                .insert(suits, indexer.configurations.len());
+            indexer.configurations.push(configuration);
+        }
+        indexer
+    }
+
+    // Number of isomorphism classes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // The cards must be in round order, but can be in any order within a round.
+    pub fn index(&self, cards: &[Card]) -> u64 {
+        debug_assert!(cards.len() == self.rounds.iter().sum::<usize>());
+        let mut masks = [[0u16; MAX_ROUNDS]; SUITS];
+        let mut configs = [[0u8; MAX_ROUNDS]; SUITS];
+        let mut start = 0;
+        for (round, &n) in self.rounds.iter().enumerate() {
+            for card in &cards[start..start + n] {
+                let suit = card.suit as usize;
+                masks[suit][round] |= 1 << (card.rank - self.lowest_rank);
+                configs[suit][round] += 1;
+            }
+            start += n;
+        }
+
+        // Sorting the suits by configuration and then pattern makes the index independent of
+        // the suits' names
+        let mut suits = [(configs[0], 0); SUITS];
+        for suit in 0..SUITS {
+            suits[suit] = (configs[suit], self.pattern_index(&masks[suit]));
+        }
+        suits.sort_unstable_by(|a, b| b.cmp(a));
+        let config_key = suits.map(|s| s.0);
+        let configuration = &self.configurations[self.config_ids[&config_key]];
+
+        let mut index = 0;
+        let mut multiplier = 1;
+        let mut i = 0;
+        while i < SUITS {
+            let group_end = (i..SUITS)
+                .find(|&j| suits[j].0 != suits[i].0)
+                .unwrap_or(SUITS);
+            // The group's patterns are sorted from largest to smallest, so adding the number
+            // of suits after each one makes them distinct, and they can be indexed as a set
+            let k = group_end - i;
+            let mut group_index = 0;
+            for (j, suit) in suits[i..group_end].iter().enumerate() {
+                group_index += n_choose_k(suit.1 as usize + k - 1 - j, k - j) as u64;
+            }
+            index += group_index * multiplier;
+            multiplier *= n_multichoose_k(configuration.pattern_sizes[i], k);
+            i = group_end;
+        }
+        configuration.offset + index
+    }
+
+    // A representative hand of the isomorphism class, in round order
+    pub fn unindex(&self, index: u64) -> Vec<Card> {
+        assert!(index < self.size, "Hand index out of range");
+        let config_id = self.configurations.partition_point(|c| c.offset <= index) - 1;
+        let configuration = &self.configurations[config_id];
+        let mut remainder = index - configuration.offset;
+
+        let mut patterns = [0u64; SUITS];
+        let mut i = 0;
+        while i < SUITS {
+            let group_end = (i..SUITS)
+                .find(|&j| configuration.suits[j] != configuration.suits[i])
+                .unwrap_or(SUITS);
+            let k = group_end - i;
+            let group_size = n_multichoose_k(configuration.pattern_sizes[i], k);
+            let mut group_index = remainder % group_size;
+            remainder /= group_size;
+            for (j, pattern) in patterns[i..group_end].iter_mut().enumerate() {
+                let n = largest_n_choose_k_at_most(group_index, k - j);
+                group_index -= n_choose_k(n, k - j) as u64;
+                *pattern = (n + 1 + j - k) as u64;
+            }
+            i = group_end;
+        }
+
+        let mut rounds: Vec<Vec<Card>> = vec![Vec::new(); self.rounds.len()];
+        for (suit, (config, &pattern)) in configuration.suits.iter().zip(&patterns).enumerate() {
+            let masks = self.pattern_masks(config, pattern);
+            for (round, mask) in masks.iter().take(self.rounds.len()).enumerate() {
+                for rank in 0..self.num_ranks {
+                    if mask & (1 << rank) != 0 {
+                        rounds[round].push(Card {
+                            rank: rank as u8 + self.lowest_rank,
+                            suit: suit as u8,
+                        });
+                    }
+                }
+            }
+        }
+        rounds.concat()
+    }
+
+    // Index of the ranks of one suit's cards among the hands with the same configuration. Each
+    // round's ranks are indexed as a set among the ranks not used in earlier rounds.
+    fn pattern_index(&self, masks: &[u16; MAX_ROUNDS]) -> u64 {
+        let mut index = 0;
+        let mut multiplier = 1;
+        let mut used: u16 = 0;
+        for &mask in masks.iter().take(self.rounds.len()) {
+            let free = self.num_ranks - used.count_ones() as usize;
+            let count = mask.count_ones() as usize;
+            let mut set_index = 0;
+            let mut bits = mask;
+            let mut j = 1;
+            while bits != 0 {
+                let bit = bits.trailing_zeros();
+                let position = (!used & ((1 << bit) - 1)).count_ones() as usize;
+                set_index += n_choose_k(position, j) as u64;
+                bits &= bits - 1;
+                j += 1;
+            }
+            index += set_index * multiplier;
+            multiplier *= n_choose_k(free, count) as u64;
+            used |= mask;
+        }
+        index
+    }
+
+    fn pattern_masks(&self, config: &SuitConfig, mut index: u64) -> [u16; MAX_ROUNDS] {
+        let mut masks = [0u16; MAX_ROUNDS];
+        let mut used: u16 = 0;
+        for round in 0..self.rounds.len() {
+            let free = self.num_ranks - used.count_ones() as usize;
+            let count = config[round] as usize;
+            let size = n_choose_k(free, count) as u64;
+            let mut set_index = index % size;
+            index /= size;
+            for j in (1..=count).rev() {
+                let position = largest_n_choose_k_at_most(set_index, j);
+                set_index -= n_choose_k(position, j) as u64;
+                masks[round] |= 1 << nth_unset_bit(used, position);
+            }
+            used |= masks[round];
+        }
+        masks
+    }
+
+    fn pattern_size(&self, config: &SuitConfig) -> u64 {
+        let mut free = self.num_ranks;
+        let mut size = 1;
+        for &count in config.iter().take(self.rounds.len()) {
+            size *= n_choose_k(free, count as usize) as u64;
+            free -= count as usize;
+        }
+        size
+    }
+
+    fn suit_configs(&self, round: usize, config: SuitConfig, configs: &mut Vec<SuitConfig>) {
+        if round == self.rounds.len() {
+            if config.iter().map(|&c| c as usize).sum::<usize>() <= self.num_ranks {
+                configs.push(config);
+            }
+            return;
+        }
+        for count in 0..=self.rounds[round] {
+            let mut next = config;
+            next[round] = count as u8;
+            self.suit_configs(round + 1, next, configs);
+        }
+    }
+
+    fn configurations(
+        &self,
+        suit_configs: &[SuitConfig],
+        start: usize,
+        chosen: &mut Vec<SuitConfig>,
+        configs: &mut Vec<[SuitConfig; SUITS]>,
+    ) {
+        if chosen.len() == SUITS {
+            let fits = (0..self.rounds.len())
+                .all(|r| chosen.iter().map(|c| c[r] as usize).sum::<usize>() == self.rounds[r]);
+            if fits {
+                configs.push([chosen[0], chosen[1], chosen[2], chosen[3]]);
+            }
+            return;
+        }
+        for i in start..suit_configs.len() {
+            chosen.push(suit_configs[i]);
+            self.configurations(suit_configs, i, chosen, configs);
+            chosen.pop();
+        }
+    }
+}
+
+fn configuration_size(configuration: &Configuration) -> u64 {
+    let mut size = 1;
+    let mut i = 0;
+    while i < SUITS {
+        let group_end = (i..SUITS)
+            .find(|&j| configuration.suits[j] != configuration.suits[i])
+            .unwrap_or(SUITS);
+        size *= n_multichoose_k(configuration.pattern_sizes[i], group_end - i);
+        i = group_end;
+    }
+    size
+}
+
+// Number of multisets of size k drawn from n items
+fn n_multichoose_k(n: u64, k: usize) -> u64 {
+    n_choose_k(n as usize + k - 1, k) as u64
+}
+
+// Decodes one element of a set from its combinatorial number system index
+fn largest_n_choose_k_at_most(value: u64, k: usize) -> usize {
+    let mut low = k - 1;
+    let mut high = k;
+    while n_choose_k(high, k) as u64 <= value {
+        high *= 2;
+    }
+    // n_choose_k(low, k) <= value < n_choose_k(high, k)
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if n_choose_k(mid, k) as u64 <= value {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+fn nth_unset_bit(used: u16, n: usize) -> usize {
+    let mut remaining = n;
+    for bit in 0..16 {
+        if used & (1 << bit) == 0 {
+            if remaining == 0 {
+                return bit;
+            }
+            remaining -= 1;
+        }
+    }
+    panic!("Not enough unset bits");
+}
@@ -0,0 +1,103 @@
+// Kuhn poker: a three card deck (J, Q, K), one card each, a one chip ante and a
+// single one chip bet. The first player's equilibrium value is exactly -1/18.
+// http://en.wikipedia.org/wiki/Kuhn_poker
+
+use crate::game::*;
+use crate::nodes::*;
+use crate::trainer_utils::*;
+use smallvec::SmallVec;
+
+pub const KUHN_GAME_VALUE: f64 = -1.0 / 18.0;
+
+const ANTE: Amount = 1;
+const BET_SIZE: Amount = 1;
+
+// Cards are 0 = J, 1 = Q, 2 = K. Nothing is dealt to the board.
+#[derive(Debug)]
+pub struct Kuhn;
+
+impl Game for Kuhn {
+    type History = RoundHistory;
+    type Hand = u8;
+    type Board = ();
+
+    fn root() -> RoundHistory {
+        RoundHistory::new()
+    }
+
+    fn hand_over(history: &RoundHistory) -> bool {
+        history.hand_over()
+    }
+
+    fn player(history: &RoundHistory) -> usize {
+        history.player()
+    }
+
+    fn on_last_street(_history: &RoundHistory) -> bool {
+        true
+    }
+
+    fn next_actions(
+        history: &RoundHistory,
+        _bet_abstraction: &[Vec<f64>],
+    ) -> SmallVec<[Action; NUM_ACTIONS]> {
+        history.next_actions(BET_SIZE, 1)
+    }
+
+    fn next_history(history: &RoundHistory, action: &Action) -> RoundHistory {
+        history.add(action, 1)
+    }
+
+    fn deal() {}
+
+    fn hands(_board: &()) -> Vec<u8> {
+        vec![0, 1, 2]
+    }
+
+    fn infoset(
+        _nodes: &Nodes<Kuhn>,
+        hand: &u8,
+        _board: &(),
+        history: &RoundHistory,
+    ) -> InfoSet<RoundHistory> {
+        InfoSet {
+            history: history.clone(),
+            card_bucket: *hand as i32,
+        }
+    }
+
+    fn num_nodes(_nodes: &Nodes<Kuhn>, _history: &RoundHistory) -> usize {
+        3
+    }
+
+    fn node_index(_nodes: &Nodes<Kuhn>, infoset: &InfoSet<RoundHistory>, _n_nodes: usize) -> usize {
+        infoset.card_bucket as usize
+    }
+
+    fn terminal_utility(
+        hands: Vec<u8>,
+        opp_reach_probs: Vec<f64>,
+        _board: &(),
+        history: &RoundHistory,
+        traverser: usize,
+    ) -> Vec<f64> {
+        small_game_utility::<Kuhn>(
+            &hands,
+            &opp_reach_probs,
+            history,
+            ANTE,
+            traverser,
+            |a, b| a.cmp(b),
+        )
+    }
+}
+
+impl SmallGame for Kuhn {
+    fn boards() -> Vec<()> {
+        vec![()]
+    }
+
+    fn conflicts(hand: &u8, other: &u8) -> bool {
+        hand == other
+    }
+}
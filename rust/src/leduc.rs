@@ -0,0 +1,132 @@
+// Leduc hold'em: a six card deck (two each of J, Q, K), one private card each, a one
+// chip ante, and one public card dealt after the first betting round. Bets are 2 chips
+// in the first round and 4 in the second, with at most two bets per round. A pair
+// with the public card beats any unpaired hand. The first player's equilibrium value
+// is about -0.0856.
+
+use crate::game::*;
+use crate::nodes::*;
+use crate::trainer_utils::*;
+use rand::prelude::*;
+use smallvec::SmallVec;
+
+pub const LEDUC_GAME_VALUE: f64 = -0.0856;
+
+const ANTE: Amount = 1;
+const BET_SIZES: [Amount; 2] = [2, 4];
+const MAX_BETS: usize = 2;
+const NUM_RANKS: usize = 3;
+
+// Cards are numbered 0..6, and the rank of a card is card / 2. The board is the public card.
+#[derive(Debug)]
+pub struct Leduc;
+
+fn rank(card: u8) -> usize {
+    (card / 2) as usize
+}
+
+impl Game for Leduc {
+    type History = RoundHistory;
+    type Hand = u8;
+    type Board = u8;
+
+    fn root() -> RoundHistory {
+        RoundHistory::new()
+    }
+
+    fn hand_over(history: &RoundHistory) -> bool {
+        history.hand_over()
+    }
+
+    fn player(history: &RoundHistory) -> usize {
+        history.player()
+    }
+
+    fn on_last_street(history: &RoundHistory) -> bool {
+        history.round() == BET_SIZES.len() - 1
+    }
+
+    fn next_actions(
+        history: &RoundHistory,
+        _bet_abstraction: &[Vec<f64>],
+    ) -> SmallVec<[Action; NUM_ACTIONS]> {
+        history.next_actions(BET_SIZES[history.round()], MAX_BETS)
+    }
+
+    fn next_history(history: &RoundHistory, action: &Action) -> RoundHistory {
+        history.add(action, BET_SIZES.len())
+    }
+
+    fn deal() -> u8 {
+        rand::thread_rng().gen_range(0..6)
+    }
+
+    fn hands(board: &u8) -> Vec<u8> {
+        (0..6).filter(|card| card != board).collect()
+    }
+
+    // The rank of the private card in the first round, and its rank together with the public
+    // card's in the second
+    fn infoset(
+        _nodes: &Nodes<Leduc>,
+        hand: &u8,
+        board: &u8,
+        history: &RoundHistory,
+    ) -> InfoSet<RoundHistory> {
+        let card_bucket = if history.round() == 0 {
+            rank(*hand)
+        } else {
+            rank(*hand) * NUM_RANKS + rank(*board)
+        };
+        InfoSet {
+            history: history.clone(),
+            card_bucket: card_bucket as i32,
+        }
+    }
+
+    fn num_nodes(_nodes: &Nodes<Leduc>, history: &RoundHistory) -> usize {
+        NUM_RANKS.pow(history.round() as u32 + 1)
+    }
+
+    fn node_index(
+        _nodes: &Nodes<Leduc>,
+        infoset: &InfoSet<RoundHistory>,
+        _n_nodes: usize,
+    ) -> usize {
+        infoset.card_bucket as usize
+    }
+
+    fn terminal_utility(
+        hands: Vec<u8>,
+        opp_reach_probs: Vec<f64>,
+        board: &u8,
+        history: &RoundHistory,
+        traverser: usize,
+    ) -> Vec<f64> {
+        let strength = |card: &u8| {
+            if rank(*card) == rank(*board) {
+                NUM_RANKS + rank(*card)
+            } else {
+                rank(*card)
+            }
+        };
+        small_game_utility::<Leduc>(
+            &hands,
+            &opp_reach_probs,
+            history,
+            ANTE,
+            traverser,
+            |a, b| strength(a).cmp(&strength(b)),
+        )
+    }
+}
+
+impl SmallGame for Leduc {
+    fn boards() -> Vec<u8> {
+        (0..6).collect()
+    }
+
+    fn conflicts(hand: &u8, other: &u8) -> bool {
+        hand == other
+    }
+}
@@ -15,24 +15,54 @@ extern crate serde_json;
 #[macro_use]
 extern crate smallvec;
 
+mod abstraction_quality;
 mod backend;
+mod blueprint_diff;
+mod board_texture;
 mod bot;
+mod browser;
 mod card_abstraction;
 mod card_utils;
+mod clustering;
 mod config;
+mod distributed;
+mod equity;
 mod exploiter;
+mod fallback;
+mod game;
+mod hand_indexer;
+mod kuhn;
+mod leduc;
+mod multiway;
+mod opponents;
 mod ranges;
+mod telemetry;
 mod trainer;
 mod nodes;
 mod trainer_utils;
 
+pub use abstraction_quality::*;
 pub use backend::*;
+pub use blueprint_diff::*;
+pub use board_texture::*;
 pub use bot::*;
+pub use browser::*;
 pub use card_abstraction::*;
 pub use card_utils::*;
+pub use clustering::*;
 pub use config::*;
+pub use distributed::*;
+pub use equity::*;
 pub use exploiter::*;
+pub use fallback::*;
+pub use game::*;
+pub use hand_indexer::*;
+pub use kuhn::*;
+pub use leduc::*;
+pub use multiway::*;
+pub use opponents::*;
 pub use ranges::*;
+pub use telemetry::*;
 pub use trainer::*;
 pub use trainer_utils::*;
 pub use nodes::*;
@@ -0,0 +1,559 @@
+// Multi-way (3 to 6 player) games. ActionHistory is specialized for heads-up play, so this
+// has its own history which tracks each seat's contributions, side pots and who still has
+// to act, and an external sampling MCCFR trainer which deals every player a hand and samples
+// the other players' actions. Since it deals individual hands instead of whole ranges, the
+// trainer is also used for heads-up Omaha.
+//
+// Seats are numbered clockwise from the button: 0 is the button, 1 the small blind, 2 the
+// big blind and 3 is under the gun. Heads-up, the button posts the small blind, or straddles
+// instead.
+
+use crate::card_abstraction::num_omaha_buckets;
+use crate::card_utils;
+use crate::card_utils::*;
+use crate::config::{Variant, CONFIG};
+use crate::exploiter::multiway_exploitability;
+use crate::nodes::*;
+use crate::trainer_utils::*;
+use dashmap::DashMap;
+use rand::prelude::*;
+use rayon::prelude::*;
+use smallvec::SmallVec;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::Mutex;
+
+pub const MAX_PLAYERS: usize = 6;
+
+type Seats<T> = SmallVec<[T; MAX_PLAYERS]>;
+
+pub fn position_name(seat: usize, num_players: usize) -> &'static str {
+    let names: &[&str] = match num_players {
+        2 => &["BTN", "BB"],
+        3 => &["BTN", "SB", "BB"],
+        4 => &["BTN", "SB", "BB", "CO"],
+        5 => &["BTN", "SB", "BB", "UTG", "CO"],
+        6 => &["BTN", "SB", "BB", "UTG", "HJ", "CO"],
+        _ => panic!("Bad number of players: {num_players}"),
+    };
+    names[seat]
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MultiwayHistory {
+    history: SmallVec<[Action; 16]>,
+    // Each seat's stack at the start of the hand
+    stacks: Seats<Amount>,
+    // Chips each seat has put in the pot this hand, including blinds and antes
+    contributions: Seats<Amount>,
+    folded: Seats<bool>,
+    // Seats which still have to act before the betting round is over
+    to_act: Seats<bool>,
+    // Size of the last raise on this street, which is the minimum size of the next raise
+    last_raise: Amount,
+    pub street: usize,
+    pub player: usize,
+}
+
+impl MultiwayHistory {
+    pub fn new(num_players: usize) -> MultiwayHistory {
+        let stacks: Seats<Amount> = smallvec![CONFIG.stack_size; num_players];
+        MultiwayHistory::with_stacks(&stacks)
+    }
+
+    pub fn with_stacks(stacks: &[Amount]) -> MultiwayHistory {
+        let num_players = stacks.len();
+        assert!(
+            (2..=MAX_PLAYERS).contains(&num_players),
+            "Need 2 to {MAX_PLAYERS} players, not {num_players}"
+        );
+        let mut contributions: Seats<Amount> = smallvec![CONFIG.ante; num_players];
+        let first_player = if num_players == 2 {
+            // Heads-up, the button posts the small blind, or the straddle instead of it, and
+            // the blinds are the same as ActionHistory's
+            contributions[0] += CONFIG.blind(DEALER);
+            contributions[1] += CONFIG.blind(OPPONENT);
+            CONFIG.first_player(PREFLOP)
+        } else {
+            contributions[1] += CONFIG.small_blind;
+            contributions[2] += CONFIG.big_blind;
+            // The player under the gun posts the straddle, and the seat after them acts first
+            let under_the_gun = 3 % num_players;
+            if CONFIG.straddle > 0 {
+                contributions[under_the_gun] += CONFIG.straddle;
+                (under_the_gun + 1) % num_players
+            } else {
+                under_the_gun
+            }
+        };
+        MultiwayHistory {
+            history: SmallVec::new(),
+            stacks: stacks.iter().cloned().collect(),
+            contributions,
+            folded: smallvec![false; num_players],
+            to_act: smallvec![true; num_players],
+            // The straddle is a raise, so the next raise has to be at least as big
+            last_raise: CONFIG.big_blind.max(CONFIG.straddle),
+            street: PREFLOP,
+            player: first_player,
+        }
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.contributions.len()
+    }
+
+    pub fn hand_over(&self) -> bool {
+        self.street == SHOWDOWN || self.num_remaining() == 1
+    }
+
+    // Number of players who haven't folded
+    pub fn num_remaining(&self) -> usize {
+        self.folded.iter().filter(|&&f| !f).count()
+    }
+
+    pub fn folded(&self, seat: usize) -> bool {
+        self.folded[seat]
+    }
+
+    pub fn contribution(&self, seat: usize) -> Amount {
+        self.contributions[seat]
+    }
+
+    pub fn stack(&self, seat: usize) -> Amount {
+        self.stacks[seat] - self.contributions[seat]
+    }
+
+    // True if the player is still in the hand and has chips left to bet
+    fn can_act(&self, seat: usize) -> bool {
+        !self.folded[seat] && self.stack(seat) > 0
+    }
+
+    pub fn pot(&self) -> Amount {
+        self.contributions.iter().sum()
+    }
+
+    // Returns the amount the current player needs to call, so 0 for checking
+    pub fn to_call(&self) -> Amount {
+        let max_contribution = *self.contributions.iter().max().unwrap();
+        let to_call = max_contribution - self.contributions[self.player];
+        to_call.min(self.stack(self.player))
+    }
+
+    pub fn min_bet(&self) -> Amount {
+        let max_contribution = *self.contributions.iter().max().unwrap();
+        let min_raise = max_contribution - self.contributions[self.player] + self.last_raise;
+        min_raise.min(self.max_bet())
+    }
+
+    pub fn max_bet(&self) -> Amount {
+        if CONFIG.pot_limit() {
+            pot_limit_bet(self.pot(), self.to_call()).min(self.stack(self.player))
+        } else {
+            self.stack(self.player)
+        }
+    }
+
+    pub fn is_legal_next_action(&self, action: &Action) -> bool {
+        match action.action {
+            ActionType::Bet => {
+                // Raising only makes sense if someone else has chips left to call with
+                let can_be_called =
+                    (0..self.num_players()).any(|p| p != self.player && self.can_act(p));
+                let size_ok = action.amount >= self.min_bet() && action.amount <= self.max_bet();
+                can_be_called && size_ok && action.amount > self.to_call()
+            }
+            ActionType::Call => action.amount == self.to_call(),
+            ActionType::Fold => self.to_call() != 0,
+        }
+    }
+
+    // Add a new action to this history, and move on to the next player to act
+    pub fn add(&mut self, action: &Action) {
+        debug_assert!(
+            self.is_legal_next_action(action),
+            "Action {:?} is illegal for history {}",
+            action,
+            self
+        );
+        let player = self.player;
+        match action.action {
+            ActionType::Fold => self.folded[player] = true,
+            ActionType::Call => self.contributions[player] += action.amount,
+            ActionType::Bet => {
+                let raise = action.amount - self.to_call();
+                self.last_raise = self.last_raise.max(raise);
+                self.contributions[player] += action.amount;
+                for p in 0..self.num_players() {
+                    self.to_act[p] = p != player && self.can_act(p);
+                }
+            }
+        }
+        self.to_act[player] = false;
+        self.history.push(action.clone());
+        if self.hand_over() {
+            return;
+        }
+
+        if self.to_act.iter().any(|&a| a) {
+            self.player = self.next_to_act(player + 1);
+            return;
+        }
+        // The betting round is over
+        self.street += 1;
+        self.last_raise = CONFIG.big_blind;
+        for p in 0..self.num_players() {
+            self.to_act[p] = self.can_act(p);
+        }
+        let num_can_act = self.to_act.iter().filter(|&&a| a).count();
+        if self.street > CONFIG.last_street() || num_can_act <= 1 {
+            // Either the last street is over, or nobody can bet, so run out the board
+            self.street = SHOWDOWN;
+        } else {
+            // The first player after the button acts first
+            self.player = self.next_to_act(1);
+        }
+    }
+
+    // The first seat at or clockwise from the given seat which still has to act
+    fn next_to_act(&self, seat: usize) -> usize {
+        let n = self.num_players();
+        (0..n)
+            .map(|i| (seat + i) % n)
+            .find(|&p| self.to_act[p])
+            .expect("Nobody left to act")
+    }
+
+    pub fn next_actions(&self, bet_abstraction: &[Vec<f64>]) -> SmallVec<[Action; NUM_ACTIONS]> {
+        if self.hand_over() {
+            return smallvec![];
+        }
+        let pot = self.pot();
+        let to_call = self.to_call();
+        let mut actions: SmallVec<[Action; NUM_ACTIONS]> = SmallVec::new();
+        for pot_fraction in bet_abstraction[self.street].iter() {
+            let amount = if pot_fraction == &ALL_IN {
+                self.max_bet()
+            } else {
+                (pot_fraction * pot as f64) as Amount + to_call
+            };
+            let action = Action {
+                action: ActionType::Bet,
+                amount,
+            };
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+        actions.push(Action {
+            action: ActionType::Call,
+            amount: to_call,
+        });
+        actions.push(FOLD);
+        actions.retain(|a| self.is_legal_next_action(a));
+        actions
+    }
+
+    // The main pot followed by any side pots, each with the seats eligible to win it
+    pub fn pots(&self) -> Vec<(Amount, Seats<usize>)> {
+        let mut levels: Vec<Amount> = (0..self.num_players())
+            .filter(|&p| !self.folded[p])
+            .map(|p| self.contributions[p])
+            .collect();
+        levels.sort();
+        levels.dedup();
+
+        let mut pots = Vec::new();
+        let mut previous = 0;
+        for level in levels {
+            let amount: Amount = self
+                .contributions
+                .iter()
+                .map(|&c| c.min(level) - c.min(previous))
+                .sum();
+            let eligible: Seats<usize> = (0..self.num_players())
+                .filter(|&p| !self.folded[p] && self.contributions[p] >= level)
+                .collect();
+            pots.push((amount, eligible));
+            previous = level;
+        }
+        pots
+    }
+
+    // Each player's net winnings at the end of the hand. strengths holds the showdown
+    // hand strength of each seat, and is ignored for folded players.
+    pub fn utility(&self, strengths: &[i32]) -> Seats<f64> {
+        debug_assert!(self.hand_over());
+        let mut winnings: Seats<f64> = smallvec![0.0; self.num_players()];
+        for (amount, eligible) in self.pots() {
+            let best = eligible.iter().map(|&p| strengths[p]).max().unwrap();
+            let winners: Seats<usize> = eligible
+                .into_iter()
+                .filter(|&p| strengths[p] == best)
+                .collect();
+            for &p in winners.iter() {
+                winnings[p] += amount as f64 / winners.len() as f64;
+            }
+        }
+        for (p, w) in winnings.iter_mut().enumerate() {
+            *w -= self.contributions[p] as f64;
+        }
+        winnings
+    }
+}
+
+impl fmt::Display for MultiwayHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = self.num_players();
+        let mut replay = MultiwayHistory::with_stacks(&self.stacks);
+        let mut seat = replay.player;
+        for action in self.history.iter() {
+            write!(f, "{}: {}, ", position_name(seat, n), action)?;
+            replay.add(action);
+            seat = replay.player;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct MultiwayNodes {
+    pub dashmap: DashMap<MultiwayHistory, Vec<Mutex<Node>>>,
+    pub bet_abstraction: Vec<Vec<f64>>,
+}
+
+impl MultiwayNodes {
+    pub fn new(bet_abstraction: &[Vec<f64>]) -> MultiwayNodes {
+        MultiwayNodes {
+            dashmap: DashMap::new(),
+            bet_abstraction: bet_abstraction.to_vec(),
+        }
+    }
+
+    pub fn get(&self, history: &MultiwayHistory, card_bucket: i32) -> Option<Node> {
+        let nodes = self.dashmap.get(history)?;
+        let node = nodes.get(card_bucket as usize)?.lock().unwrap();
+        Some(node.clone())
+    }
+
+    // Regret matching strategy at this infoset, creating its nodes if needed
+    pub fn get_current_strategy(
+        &self,
+        history: &MultiwayHistory,
+        card_bucket: i32,
+    ) -> SmallVecFloats {
+        let nodes = self.dashmap.entry(history.clone()).or_insert_with(|| {
+            let num_actions = history.next_actions(&self.bet_abstraction).len();
+            (0..multiway_num_buckets(history.street))
+                .map(|_| Mutex::new(Node::new(num_actions)))
+                .collect()
+        });
+        let node = nodes[card_bucket as usize].lock().unwrap();
+        let positive_regrets: SmallVecFloats = node
+            .regrets
+            .iter()
+            .take(node.num_actions)
+            .map(|r| if *r >= 0.0 { *r } else { 0.0 })
+            .collect();
+        normalize_smallvec(&positive_regrets)
+    }
+
+    pub fn add_regrets(&self, history: &MultiwayHistory, card_bucket: i32, regrets: &[f64]) {
+        let nodes = self.dashmap.get(history).unwrap();
+        let mut node = nodes[card_bucket as usize].lock().unwrap();
+        for (i, regret) in regrets.iter().enumerate() {
+            node.regrets[i] += *regret as f32;
+        }
+        node.t += 1;
+    }
+
+    pub fn update_strategy_sum(
+        &self,
+        history: &MultiwayHistory,
+        card_bucket: i32,
+        strategy: &[f32],
+    ) {
+        let nodes = self.dashmap.get(history).unwrap();
+        let mut node = nodes[card_bucket as usize].lock().unwrap();
+        for (i, prob) in strategy.iter().enumerate() {
+            node.strategy_sum[i] += prob;
+        }
+    }
+
+    pub fn get_strategy(
+        &self,
+        hole: &[Card],
+        board: &[Card],
+        history: &MultiwayHistory,
+    ) -> Strategy {
+        let bucket = multiway_card_bucket(hole, board, history.street);
+        let node = self
+            .get(history, bucket)
+            .unwrap_or_else(|| panic!("Node not found for history {history}"));
+        let actions = history.next_actions(&self.bet_abstraction);
+        actions
+            .into_iter()
+            .zip(node.cumulative_strategy())
+            .map(|(action, prob)| (action, prob as f64))
+            .collect()
+    }
+
+    // The average strategy of each card bucket at a history, which is uniform if training
+    // never reached the history
+    pub fn average_strategies(&self, history: &MultiwayHistory) -> Vec<SmallVecFloats> {
+        match self.dashmap.get(history) {
+            Some(nodes) => nodes
+                .iter()
+                .map(|node| node.lock().unwrap().cumulative_strategy())
+                .collect(),
+            None => {
+                let num_actions = history.next_actions(&self.bet_abstraction).len();
+                let uniform: SmallVecFloats = smallvec![1.0 / num_actions as f32; num_actions];
+                vec![uniform; multiway_num_buckets(history.street)]
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dashmap.iter().map(|elem| elem.value().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dashmap.is_empty()
+    }
+}
+
+// Omaha hands are bucketed by omaha_bin, which has far fewer buckets than the abstraction
+pub fn multiway_num_buckets(street: usize) -> usize {
+    match CONFIG.variant() {
+        Variant::Omaha => num_omaha_buckets(street),
+        _ => num_buckets(street),
+    }
+}
+
+pub fn multiway_card_bucket(hole: &[Card], board: &[Card], street: usize) -> i32 {
+    let cards = [hole, &board[..board_length(street)]].concat();
+    ABSTRACTION.bin(&cards)
+}
+
+pub fn train_multiway(iters: usize, eval_every: usize) {
+    let deck = card_utils::deck();
+    let nodes = MultiwayNodes::new(&CONFIG.bet_abstraction);
+    println!("[INFO] Beginning {} player training.", CONFIG.num_players);
+    let num_epochs = iters / eval_every;
+    for epoch in 0..num_epochs {
+        println!("[INFO] Training epoch {}/{}", epoch + 1, num_epochs);
+        let bar = card_utils::pbar(eval_every);
+        (0..eval_every).into_par_iter().for_each(|_| {
+            multiway_cfr_iteration(&deck, CONFIG.num_players, &nodes);
+            bar.inc(1);
+        });
+        bar.finish_with_message("Done");
+        serialize_multiway_nodes(&nodes);
+        println!("{} nodes reached.", nodes.len());
+        // LBR needs a single opponent
+        if CONFIG.num_players == 2 {
+            multiway_exploitability(&nodes, CONFIG.lbr_iters);
+        }
+    }
+}
+
+// Deals a hand to each player and runs one external sampling MCCFR traversal for each
+pub fn multiway_cfr_iteration(deck: &[Card], num_players: usize, nodes: &MultiwayNodes) {
+    let mut deck = deck.to_vec();
+    deck.shuffle(&mut thread_rng());
+    let n = CONFIG.hole_cards();
+    let board = &deck[n * num_players..n * num_players + 5];
+    let mut buckets: Vec<[i32; 4]> = Vec::with_capacity(num_players);
+    let mut strengths: Vec<i32> = Vec::with_capacity(num_players);
+    for p in 0..num_players {
+        let hole = &deck[n * p..n * p + n];
+        let mut player_buckets = [0; 4];
+        for (street, bucket) in player_buckets.iter_mut().enumerate() {
+            if street <= CONFIG.last_street() {
+                *bucket = multiway_card_bucket(hole, board, street);
+            }
+        }
+        buckets.push(player_buckets);
+        strengths.push(showdown_strength(hole, board));
+    }
+    for traverser in 0..num_players {
+        multiway_iterate(
+            traverser,
+            &buckets,
+            &strengths,
+            &MultiwayHistory::new(num_players),
+            nodes,
+        );
+    }
+}
+
+// Returns the traverser's utility. All of the traverser's actions are explored, and one
+// action is sampled for each of the other players.
+pub fn multiway_iterate(
+    traverser: usize,
+    buckets: &[[i32; 4]],
+    strengths: &[i32],
+    history: &MultiwayHistory,
+    nodes: &MultiwayNodes,
+) -> f64 {
+    if history.hand_over() {
+        return history.utility(strengths)[traverser];
+    }
+    let player = history.player;
+    let bucket = buckets[player][history.street];
+    let actions = history.next_actions(&nodes.bet_abstraction);
+    let strategy = nodes.get_current_strategy(history, bucket);
+
+    if player == traverser {
+        let mut action_utils: SmallVec<[f64; NUM_ACTIONS]> = SmallVec::new();
+        for action in actions.iter() {
+            let mut next = history.clone();
+            next.add(action);
+            action_utils.push(multiway_iterate(
+                traverser, buckets, strengths, &next, nodes,
+            ));
+        }
+        let node_util: f64 = action_utils
+            .iter()
+            .zip(strategy.iter())
+            .map(|(util, &prob)| util * prob as f64)
+            .sum();
+        let regrets: SmallVec<[f64; NUM_ACTIONS]> =
+            action_utils.iter().map(|util| util - node_util).collect();
+        nodes.add_regrets(history, bucket, &regrets);
+        node_util
+    } else {
+        nodes.update_strategy_sum(history, bucket, &strategy);
+        let index = sample_index(&strategy);
+        let mut next = history.clone();
+        next.add(&actions[index]);
+        multiway_iterate(traverser, buckets, strengths, &next, nodes)
+    }
+}
+
+pub fn sample_index(strategy: &[f32]) -> usize {
+    let mut r: f32 = thread_rng().gen();
+    for (i, prob) in strategy.iter().enumerate() {
+        if r < *prob {
+            return i;
+        }
+        r -= prob;
+    }
+    strategy.len() - 1
+}
+
+pub fn serialize_multiway_nodes(nodes: &MultiwayNodes) {
+    let file = create_file(&CONFIG.nodes_path);
+    let mut buf_writer = BufWriter::new(file);
+    bincode::serialize_into(&mut buf_writer, nodes).expect("Failed to serialize nodes");
+    buf_writer.flush().unwrap();
+    println!("[INFO] Saved strategy.");
+}
+
+pub fn load_multiway_nodes(path: &str) -> MultiwayNodes {
+    let file = File::open(path).expect("Nodes file not found");
+    let reader = BufReader::new(file);
+    bincode::deserialize_from(reader).expect("Failed to deserialize nodes")
+}
@@ -1,16 +1,31 @@
+use crate::card_abstraction::{
+    configured_buckets, num_preflop_buckets, with_board_texture, Abstraction,
+};
 use crate::card_utils::*;
-use crate::config::CONFIG;
+use crate::config::{GranularityRule, CONFIG};
+use crate::fallback::fallback_strategy;
+use crate::game::{Game, Holdem};
 use crate::trainer_utils::*;
 use dashmap::DashMap;
-use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
 
 // Upper limit on branching factor of blueprint game tree.
 pub const NUM_ACTIONS: usize = 5;
 
+// The nodes of a game's blueprint, a vector of them at each history indexed by card bucket.
+// Hold'em unless it's given another Game.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct Nodes {
-    pub dashmap: DashMap<ActionHistory, Vec<Mutex<Node>>>,
+#[serde(bound(
+    serialize = "G::History: serde::Serialize",
+    deserialize = "G::History: serde::Deserialize<'de>"
+))]
+pub struct Nodes<G: Game = Holdem> {
+    pub dashmap: DashMap<G::History, Vec<Mutex<Node>>>,
     pub bet_abstraction: Vec<Vec<f64>>,
+    // The card abstraction this blueprint is trained with, if it isn't the global ABSTRACTION
+    #[serde(skip)]
+    pub abstraction: Option<Arc<Abstraction>>,
 }
 
 impl Nodes {
@@ -19,36 +34,56 @@ impl Nodes {
         Nodes {
             dashmap: DashMap::new(),
             bet_abstraction: bet_abstraction.to_vec(),
+            abstraction: None,
         }
     }
 
-    pub fn get(&self, infoset: &InfoSet) -> Option<Node> {
-        let nodes = match self.dashmap.get(&infoset.history) {
-            Some(n) => n,
-            None => return None,
-        };
-        let node_mutex = match nodes.value().get(infoset.card_bucket as usize) {
-            Some(mutex) => mutex,
-            None => return None,
-        };
+    pub fn with_abstraction(bet_abstraction: &[Vec<f64>], abstraction: Arc<Abstraction>) -> Nodes {
+        Nodes {
+            dashmap: DashMap::new(),
+            bet_abstraction: bet_abstraction.to_vec(),
+            abstraction: Some(abstraction),
+        }
+    }
+
+    pub fn infoset(&self, hole: &[Card], board: &[Card], history: &ActionHistory) -> InfoSet {
+        match &self.abstraction {
+            Some(abstraction) => InfoSet::with_abstraction(hole, board, history, abstraction),
+            None => InfoSet::from_hand(hole, board, history),
+        }
+    }
+}
+
+impl<G: Game> Nodes<G> {
+    pub fn get(&self, infoset: &InfoSet<G::History>) -> Option<Node> {
+        let nodes = self.dashmap.get(&infoset.history)?;
+        let node_mutex = nodes
+            .value()
+            .get(G::node_index(self, infoset, nodes.len()))?;
         let node_guard = node_mutex.lock().unwrap();
         Some(node_guard.clone())
     }
 
     pub fn add_regret_vectorized(
         &self,
-        infosets: &[InfoSet],
+        infosets: &[InfoSet<G::History>],
         action_utility: &[f64],
         node_utility: &[f64],
         action_index: usize,
+        skip: &[bool],
     ) {
         let history = infosets[0].history.clone();
         let node_vec = self.dashmap.get(&history).unwrap();
         for (hand_idx, utility) in action_utility.iter().enumerate() {
+            if skip[hand_idx] {
+                continue;
+            }
             let regret = utility - node_utility[hand_idx];
 
             let infoset = &infosets[hand_idx];
-            let node_mutex = node_vec.get(infoset.card_bucket as usize).unwrap();
+            let node_mutex = node_vec
+                .get(G::node_index(self, infoset, node_vec.len()))
+                .unwrap();
             let mut node = node_mutex.lock().unwrap();
             let mut accumulated_regret = node.regrets[action_index] + regret as f32;
 
@@ -64,10 +99,12 @@ impl Nodes {
         }
     }
 
-    pub fn add_regret(&self, infoset: &InfoSet, action_index: usize, regret: f64) {
+    pub fn add_regret(&self, infoset: &InfoSet<G::History>, action_index: usize, regret: f64) {
         let history = infoset.history.clone();
         let node_vec = self.dashmap.get(&history).unwrap();
-        let node_mutex = node_vec.get(infoset.card_bucket as usize).unwrap();
+        let node_mutex = node_vec
+            .get(G::node_index(self, infoset, node_vec.len()))
+            .unwrap();
         let mut node = node_mutex.lock().unwrap();
         let accumulated_regret = node.regrets[action_index] + regret as f32;
         // DCFR
@@ -80,11 +117,13 @@ impl Nodes {
         node.regrets[action_index] = accumulated_regret;
     }
 
-    pub fn update_strategy_sum_vectorized(&self, infosets: &[InfoSet], probs: &[f64]) {
+    pub fn update_strategy_sum_vectorized(&self, infosets: &[InfoSet<G::History>], probs: &[f64]) {
         let history = infosets[0].history.clone();
         let node_vec = self.dashmap.get(&history).unwrap();
         for (infoset, &prob) in infosets.iter().zip(probs.iter()) {
-            let node_mutex = node_vec.get(infoset.card_bucket as usize).unwrap();
+            let node_mutex = node_vec
+                .get(G::node_index(self, infoset, node_vec.len()))
+                .unwrap();
             let mut node = node_mutex.lock().unwrap();
             let positive_regrets: SmallVecFloats = node
                 .regrets
@@ -104,10 +143,12 @@ impl Nodes {
         }
     }
 
-    pub fn update_strategy_sum(&self, infoset: &InfoSet, prob: f32) {
+    pub fn update_strategy_sum(&self, infoset: &InfoSet<G::History>, prob: f32) {
         let history = infoset.history.clone();
         let node_vec = self.dashmap.get(&history).unwrap();
-        let node_mutex = node_vec.get(infoset.card_bucket as usize).unwrap();
+        let node_mutex = node_vec
+            .get(G::node_index(self, infoset, node_vec.len()))
+            .unwrap();
         let mut node = node_mutex.lock().unwrap();
         let positive_regrets: SmallVecFloats = node
             .regrets
@@ -127,75 +168,63 @@ impl Nodes {
         node.t += 1;
     }
 
-    pub fn reset_strategy_sum(&self, infoset: &InfoSet) {
+    pub fn reset_strategy_sum(&self, infoset: &InfoSet<G::History>) {
         let history = infoset.history.clone();
         let node_vec = self.dashmap.get(&history).unwrap();
-        let node_mutex = node_vec.get(infoset.card_bucket as usize).unwrap();
+        let node_mutex = node_vec
+            .get(G::node_index(self, infoset, node_vec.len()))
+            .unwrap();
         let mut node = node_mutex.lock().unwrap();
         node.strategy_sum = [0.0; NUM_ACTIONS];
     }
 
-    pub fn get_current_strategy(&self, infoset: &InfoSet) -> SmallVecFloats {
+    pub fn get_current_strategy(&self, infoset: &InfoSet<G::History>) -> SmallVecFloats {
         if !self.dashmap.contains_key(&infoset.history) {
             self.initialize_node_vec(&infoset.history);
         }
-        let node = self.get(infoset).unwrap();
-        let positive_regrets: SmallVecFloats = node
-            .regrets
+        self.get(infoset).unwrap().current_strategy()
+    }
+
+    pub fn get_nodes_vectorized(&self, infosets: &[InfoSet<G::History>]) -> Vec<Node> {
+        let node_vec = self.dashmap.get(&infosets[0].history).unwrap();
+        infosets
             .iter()
-            .take(node.num_actions)
-            .map(|r| if *r >= 0.0 { *r } else { 0.0 })
-            .collect();
-        let regret_norm: SmallVecFloats = normalize_smallvec(&positive_regrets);
-        regret_norm
+            .map(|infoset| {
+                let node_mutex = node_vec
+                    .get(G::node_index(self, infoset, node_vec.len()))
+                    .unwrap();
+                node_mutex.lock().unwrap().clone()
+            })
+            .collect()
     }
 
-    pub fn get_current_strategy_vectorized(&self, infosets: &[InfoSet]) -> Vec<SmallVecFloats> {
-        let history: &ActionHistory = &infosets[0].history;
+    pub fn get_current_strategy_vectorized(
+        &self,
+        infosets: &[InfoSet<G::History>],
+    ) -> Vec<SmallVecFloats> {
+        let history: &G::History = &infosets[0].history;
         if !self.dashmap.contains_key(history) {
             self.initialize_node_vec(history);
         }
         let node_vec_ref = self.dashmap.get(history).unwrap();
         let node_vec = node_vec_ref.value();
-        let positive_regrets: Vec<[f32; NUM_ACTIONS]> = infosets
+        infosets
             .iter()
             .map(|infoset| {
-                let mut r = node_vec
-                    .get(infoset.card_bucket as usize)
+                node_vec
+                    .get(G::node_index(self, infoset, node_vec.len()))
                     .unwrap()
                     .lock()
                     .unwrap()
-                    .regrets;
-                for i in 0..r.len() {
-                    if r[i] < 0.0 {
-                        r[i] = 0.0;
-                    }
-                }
-                r
+                    .current_strategy()
             })
-            .collect();
-
-        let regret_norms: Vec<SmallVecFloats> = positive_regrets
-            .iter()
-            .map(|r| normalize_smallvec(r))
-            .collect();
-        regret_norms
+            .collect()
     }
 
-    fn initialize_node_vec(&self, history: &ActionHistory) {
+    fn initialize_node_vec(&self, history: &G::History) {
         // Create the Vec<Node> at this history if it doesn't exist yet
-        let n_buckets = if history.street == PREFLOP {
-            169
-        } else if history.street == FLOP {
-            CONFIG.flop_buckets
-        } else if history.street == TURN {
-            CONFIG.turn_buckets
-        } else if history.street == RIVER {
-            CONFIG.river_buckets
-        } else {
-            panic!("Bad street")
-        } as usize;
-        let new_node: Node = Node::new(history.next_actions(&self.bet_abstraction).len());
+        let n_buckets = G::num_nodes(self, history);
+        let new_node: Node = Node::new(G::next_actions(history, &self.bet_abstraction).len());
         let new_mutex_nodes: Vec<Mutex<Node>> = (0..n_buckets)
             .map(|i| Mutex::new(new_node.clone()))
             .collect();
@@ -210,21 +239,67 @@ impl Nodes {
         });
         length
     }
+}
+
+impl Nodes {
+    fn street_buckets(&self, street: usize) -> usize {
+        match &self.abstraction {
+            Some(abstraction) => abstraction.num_buckets(street),
+            None => STREET_BUCKETS[street],
+        }
+    }
+
+    // Number of nodes at a history, one for each bucket that the bucket_granularity rules leave it
+    pub(crate) fn num_nodes(&self, history: &ActionHistory) -> usize {
+        history_buckets(
+            &CONFIG.bucket_granularity,
+            history,
+            self.street_buckets(history.street),
+        )
+    }
+
+    // Each history has as many nodes as it had buckets when the blueprint was trained, which
+    // can be fewer than its street has. Then neighbouring hand buckets, which are ordered by
+    // equity, share a node, and each board texture keeps its own nodes.
+    pub(crate) fn node_index(&self, infoset: &InfoSet, n_nodes: usize) -> usize {
+        let street = infoset.history.street;
+        let street_buckets = self.street_buckets(street);
+        if n_nodes == street_buckets {
+            return infoset.card_bucket as usize;
+        }
+        let textures = num_textures(street);
+        let hand_nodes = n_nodes / textures;
+        let hand_bucket = infoset.card_bucket as usize / textures;
+        let texture = infoset.card_bucket as usize % textures;
+        let hand_node =
+            (hand_bucket * hand_nodes / (street_buckets / textures)).min(hand_nodes - 1);
+        hand_node * textures + texture
+    }
 
     pub fn get_strategy(&self, hole: &[Card], board: &[Card], history: &ActionHistory) -> Strategy {
-        let infoset = InfoSet::from_hand(hole, board, history);
-        let node = self
-            .get(&infoset)
-            .expect(&format!("Node not found for infoset {}", &infoset))
-            .clone(); // All nodes must be in infoset
-        let mut strategy = Strategy::new();
-        let actions = infoset.next_actions(&self.bet_abstraction);
-        let cumulative_strategy = node.cumulative_strategy();
-        for (action, prob) in actions.iter().zip(node.cumulative_strategy().iter()) {
-            strategy.insert(action.clone(), *prob as f64);
+        match self.try_get_strategy(hole, board, history) {
+            Some(strategy) => strategy,
+            None => fallback_strategy(self, hole, board, history, None),
         }
-        let sum: f64 = strategy.values().sum();
-        strategy
+    }
+
+    // None if the blueprint doesn't have the infoset, like after prune_blueprint dropped its
+    // history. get_strategy falls back to another strategy instead.
+    pub fn try_get_strategy(
+        &self,
+        hole: &[Card],
+        board: &[Card],
+        history: &ActionHistory,
+    ) -> Option<Strategy> {
+        let infoset = self.infoset(hole, board, history);
+        let node = self.get(&infoset)?;
+        let actions = infoset.next_actions(&self.bet_abstraction);
+        let strategy = actions
+            .iter()
+            .zip(node.cumulative_strategy().iter())
+            .map(|(action, prob)| (action.clone(), *prob as f64))
+            .collect();
+        Some(strategy)
     }
 
     pub fn get_strategy_vectorized(&self, infosets: &[InfoSet]) -> Vec<SmallVecFloats> {
@@ -239,7 +314,7 @@ impl Nodes {
             .iter()
             .map(|infoset| {
                 let node = node_vec
-                    .get(infoset.card_bucket as usize)
+                    .get(self.node_index(infoset, node_vec.len()))
                     .unwrap()
                     .lock()
                     .unwrap();
@@ -250,6 +325,64 @@ impl Nodes {
     }
 }
 
+impl<G: Game> Default for Nodes<G> {
+    fn default() -> Nodes<G> {
+        Nodes {
+            dashmap: DashMap::new(),
+            bet_abstraction: Vec::new(),
+            abstraction: None,
+        }
+    }
+}
+
+static STREET_BUCKETS: Lazy<[usize; 4]> =
+    Lazy::new(|| [PREFLOP, FLOP, TURN, RIVER].map(num_buckets));
+
+// Number of card abstraction buckets on the given street
+pub fn num_buckets(street: usize) -> usize {
+    if street == PREFLOP {
+        num_preflop_buckets()
+    } else {
+        with_board_texture(configured_buckets(street))
+    }
+}
+
+// Number of board textures the buckets of a street are split into
+fn num_textures(street: usize) -> usize {
+    if street == PREFLOP {
+        1
+    } else {
+        with_board_texture(1)
+    }
+}
+
+// Number of buckets at a history, chosen by the last of the bucket_granularity rules that
+// matches it. The rule's fraction applies to the hand buckets, and every board texture keeps
+// its buckets. Preflop histories always use every bucket.
+pub fn history_buckets(
+    rules: &[GranularityRule],
+    history: &ActionHistory,
+    street_buckets: usize,
+) -> usize {
+    if history.street == PREFLOP {
+        return street_buckets;
+    }
+    let pot = history.pot() as f64 / CONFIG.big_blind as f64;
+    let bets = history
+        .get_actions()
+        .iter()
+        .filter(|a| a.action == ActionType::Bet)
+        .count();
+    let fraction = rules
+        .iter()
+        .rev()
+        .find(|rule| pot >= rule.min_pot && bets >= rule.min_bets)
+        .map_or(1.0, |rule| rule.fraction);
+    let textures = num_textures(history.street);
+    let hand_buckets = street_buckets / textures;
+    ((hand_buckets as f64 * fraction).round() as usize).clamp(1, hand_buckets) * textures
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Node {
     pub regrets: [f32; NUM_ACTIONS],
@@ -273,4 +406,13 @@ impl Node {
         // TODO: Should this round off low probabilities here? < 0.05
         normalize_smallvec(&self.strategy_sum[..self.num_actions])
     }
+
+    // The strategy of the current iteration, from regret matching
+    pub fn current_strategy(&self) -> SmallVecFloats {
+        let positive_regrets: SmallVecFloats = self.regrets[..self.num_actions]
+            .iter()
+            .map(|r| r.max(0.0))
+            .collect();
+        normalize_smallvec(&positive_regrets)
+    }
 }
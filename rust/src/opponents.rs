@@ -0,0 +1,203 @@
+// Scripted baseline opponents to benchmark the bot against. Losing to any of them points to a
+// leak: losing to always-shove means the bot doesn't fold to shoves or call them correctly,
+// losing to always-raise means it overfolds to aggression, and not beating always-call means
+// it bluffs too much or value bets too little.
+
+use crate::card_utils::*;
+use crate::config::CONFIG;
+use crate::equity::hand_vs_range;
+use crate::exploiter::head_to_head;
+use crate::fallback::heuristic_strategy;
+use crate::ranges::Range;
+use crate::trainer_utils::*;
+use std::fmt;
+use std::fmt::Write;
+use std::time::Duration;
+
+// Time for the opponents' equity estimates
+const OPPONENT_EQUITY_TIME: Duration = Duration::from_millis(5);
+// The tight-aggressive bot plays preflop with at least this much equity against a random hand
+const TAG_PREFLOP_EQUITY: f64 = 0.6;
+// and bets postflop with at least this much
+const TAG_BET_EQUITY: f64 = 0.65;
+// It only calls with this much more equity than the pot odds need
+const TAG_CALL_MARGIN: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptedOpponent {
+    AlwaysCall,
+    AlwaysRaise,
+    AlwaysShove,
+    TightAggressive,
+    EquityThreshold,
+}
+
+impl fmt::Display for ScriptedOpponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScriptedOpponent::AlwaysCall => "always call",
+            ScriptedOpponent::AlwaysRaise => "always raise",
+            ScriptedOpponent::AlwaysShove => "always shove",
+            ScriptedOpponent::TightAggressive => "tight-aggressive",
+            ScriptedOpponent::EquityThreshold => "equity threshold",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub const SCRIPTED_OPPONENTS: [ScriptedOpponent; 5] = [
+    ScriptedOpponent::AlwaysCall,
+    ScriptedOpponent::AlwaysRaise,
+    ScriptedOpponent::AlwaysShove,
+    ScriptedOpponent::TightAggressive,
+    ScriptedOpponent::EquityThreshold,
+];
+
+impl ScriptedOpponent {
+    // The opponent's strategy over the actions of the config's bet abstraction
+    pub fn get_strategy(&self, hole: &[Card], board: &[Card], history: &ActionHistory) -> Strategy {
+        let actions = history.next_actions(&CONFIG.bet_abstraction);
+        let equity = || hand_vs_range(hole, &Range::new(), board, OPPONENT_EQUITY_TIME).equity();
+        match self {
+            ScriptedOpponent::AlwaysCall => always_call(&actions),
+            ScriptedOpponent::AlwaysRaise => always_raise(&actions),
+            ScriptedOpponent::AlwaysShove => always_shove(&actions),
+            ScriptedOpponent::TightAggressive => {
+                tight_aggressive_strategy(equity(), history, &actions)
+            }
+            ScriptedOpponent::EquityThreshold => heuristic_strategy(equity(), history, &actions),
+        }
+    }
+}
+
+pub fn always_call(actions: &[Action]) -> Strategy {
+    let call = actions
+        .iter()
+        .find(|a| a.action == ActionType::Call)
+        .unwrap();
+    pure_strategy(actions, call)
+}
+
+// Makes the biggest bet short of all in, or goes all in if that's the only bet left
+pub fn always_raise(actions: &[Action]) -> Strategy {
+    // The biggest bet is all in
+    let mut bets: Vec<&Action> = actions
+        .iter()
+        .filter(|a| a.action == ActionType::Bet)
+        .collect();
+    bets.sort_by_key(|a| a.amount);
+    let bet = bets.iter().rev().nth(1).or(bets.last());
+    match bet {
+        Some(bet) => pure_strategy(actions, bet),
+        None => always_call(actions),
+    }
+}
+
+// Goes all in whenever it can, and otherwise calls
+pub fn always_shove(actions: &[Action]) -> Strategy {
+    // The biggest bet is all in
+    let all_in = actions
+        .iter()
+        .filter(|a| a.action == ActionType::Bet)
+        .max_by_key(|a| a.amount);
+    match all_in {
+        Some(all_in) => pure_strategy(actions, all_in),
+        None => always_call(actions),
+    }
+}
+
+// Preflop, raises the hands with enough equity and folds the rest to a raise. Postflop, bets
+// about the pot with strong hands and only calls with a margin over the pot odds.
+pub fn tight_aggressive_strategy(
+    equity: f64,
+    history: &ActionHistory,
+    actions: &[Action],
+) -> Strategy {
+    let to_call = history.to_call() as f64;
+    let pot = history.pot() as f64;
+    let pot_odds = to_call / (pot + to_call);
+    let pot_bet = actions
+        .iter()
+        .filter(|a| a.action == ActionType::Bet)
+        .min_by_key(|a| (a.amount as i64 - (pot + to_call) as i64).abs());
+    let fold = actions.iter().find(|a| a.action == ActionType::Fold);
+    let call = actions
+        .iter()
+        .find(|a| a.action == ActionType::Call)
+        .unwrap();
+    let (bet_equity, call_equity) = if history.street == PREFLOP {
+        (TAG_PREFLOP_EQUITY, TAG_PREFLOP_EQUITY)
+    } else {
+        (TAG_BET_EQUITY, pot_odds + TAG_CALL_MARGIN)
+    };
+    let action = match (pot_bet, fold) {
+        (Some(bet), _) if equity >= bet_equity => bet,
+        (_, Some(fold)) if equity < call_equity => fold,
+        _ => call,
+    };
+    pure_strategy(actions, action)
+}
+
+fn pure_strategy(actions: &[Action], action: &Action) -> Strategy {
+    actions
+        .iter()
+        .map(|a| (a.clone(), if a == action { 1.0 } else { 0.0 }))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct OpponentResult {
+    pub opponent: ScriptedOpponent,
+    // The winnings against the opponent and the half-width of their 95% confidence interval,
+    // in BB/h
+    pub winnings: f64,
+    pub confidence: f64,
+}
+
+// Plays the strategy against each scripted opponent
+pub fn evaluate_against_opponents<F>(get_strategy: &F, hands: usize) -> Vec<OpponentResult>
+where
+    F: Fn(&[Card], &[Card], &ActionHistory) -> Strategy + std::marker::Sync,
+{
+    SCRIPTED_OPPONENTS
+        .iter()
+        .map(|&opponent| {
+            println!("[INFO] Playing against {opponent}...");
+            let opponent_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
+                opponent.get_strategy(hole, board, history)
+            };
+            let (winnings, confidence) = head_to_head(get_strategy, &opponent_strategy, hands);
+            OpponentResult {
+                opponent,
+                winnings,
+                confidence,
+            }
+        })
+        .collect()
+}
+
+// A table of the results, flagging the opponents the strategy loses to
+pub fn format_evaluation(results: &[OpponentResult]) -> String {
+    let mut table = format!(
+        "{:<18} {:>10} {:>10}  {}\n",
+        "Opponent", "BB/h", "95% CI", "Result"
+    );
+    for result in results {
+        let verdict = if result.winnings - result.confidence > 0.0 {
+            "wins"
+        } else if result.winnings + result.confidence < 0.0 {
+            "LOSES"
+        } else {
+            "unclear"
+        };
+        writeln!(
+            table,
+            "{:<18} {:>10.3} {:>10.3}  {verdict}",
+            result.opponent.to_string(),
+            result.winnings,
+            result.confidence
+        )
+        .unwrap();
+    }
+    table
+}
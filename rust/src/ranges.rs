@@ -1,4 +1,5 @@
 use crate::card_utils::*;
+use crate::config::CONFIG;
 use crate::trainer_utils::*;
 use ahash::AHashMap as HashMap;
 use rand::distributions::WeightedIndex;
@@ -7,37 +8,53 @@ use rand::prelude::*;
 // If a hand's probability is below PROB_CUTOFF in the range, just skip it since it has a negligible
 // contribution to the range.
 pub const PROB_CUTOFF: f64 = 1e-12;
-const N_PREFLOP_HANDS: usize = 1326;
 
 #[derive(Debug, Clone)]
 pub struct Range {
-    // This is the full 1326 2 card preflop combinations, not isomorphic
-    pub hands: Vec<[Card; 2]>,
+    // All of the hole card combinations, not isomorphic. This is 1326 hands in hold'em, 630
+    // in short deck and 270,725 in Omaha.
+    pub hands: Vec<Hole>,
     pub probs: Vec<f64>,
 }
 
 impl Range {
     pub fn new() -> Range {
-        let mut hands: Vec<[Card; 2]> = Vec::new();
-        let deck = deck();
-        for i in 0..deck.len() {
-            for j in i + 1..deck.len() {
-                let hand = [deck[i], deck[j]];
-                hands.push(hand);
-            }
-        }
-        debug_assert!(hands.len() == N_PREFLOP_HANDS);
-        let probs = vec![1.0 / N_PREFLOP_HANDS as f64; N_PREFLOP_HANDS];
+        let hands = non_blocking_holes(&[]);
+        debug_assert!(hands.len() == n_choose_k(deck().len(), CONFIG.hole_cards()));
+        let probs = vec![1.0 / hands.len() as f64; hands.len()];
         Range {
             hands: hands,
             probs: probs,
         }
     }
 
+    // A range of just these holes, equally likely
+    pub fn from_holes(holes: &[Hole]) -> Range {
+        let mut range = Range::new();
+        let sorted = |hole: &[Card]| -> Hole {
+            let mut hole: Hole = hole.iter().cloned().collect();
+            hole.sort();
+            hole
+        };
+        let holes: Vec<Hole> = holes.iter().map(|h| sorted(h)).collect();
+        for (hand, prob) in range.hands.iter().zip(range.probs.iter_mut()) {
+            *prob = if holes.contains(&sorted(hand)) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        assert!(
+            range.probs.iter().any(|&p| p > 0.0),
+            "None of the holes are in the range"
+        );
+        range.normalize_range();
+        range
+    }
+
     pub fn remove_blockers(&mut self, blockers: &[Card]) {
         for i in 0..self.hands.len() {
-            let hand = self.hands[i];
-            if blockers.contains(&hand[0]) || blockers.contains(&hand[1]) {
+            if self.hands[i].iter().any(|c| blockers.contains(c)) {
                 self.probs[i] = 0.0;
             }
         }
@@ -50,13 +67,13 @@ impl Range {
         F: Fn(&[Card]) -> f64,
     {
         for i in 0..self.hands.len() {
-            let hand = self.hands[i];
+            let hand = &self.hands[i];
             let prob = self.probs[i];
             if prob < PROB_CUTOFF {
                 continue;
             }
 
-            let p = hand_likelihood(&hand);
+            let p = hand_likelihood(hand);
             let new_prob = prob * p;
             self.probs[i] = new_prob;
         }
@@ -75,8 +92,7 @@ impl Range {
     pub fn sample_hand(&self) -> Vec<Card> {
         let dist = WeightedIndex::new(&self.probs).unwrap();
         let index = dist.sample(&mut rand::thread_rng());
-        let hand = self.hands[index];
-        hand.to_vec()
+        self.hands[index].to_vec()
     }
 
     // In this function, it's our turn and we're trying to figure out the range of the opponent
@@ -114,7 +130,7 @@ impl Range {
     pub fn normalize_range(&mut self) {
         let sum: f64 = self.probs.iter().sum();
         if sum == 0.0 {
-            self.probs = vec![1.0 / N_PREFLOP_HANDS as f64; N_PREFLOP_HANDS];
+            self.probs = vec![1.0 / self.hands.len() as f64; self.hands.len()];
         } else {
             self.probs = self.probs.iter().map(|prob| prob / sum).collect();
         }
@@ -0,0 +1,207 @@
+// Training metrics. After every epoch, train() appends one JSON line to metrics_path, which
+// the server exposes at /api/metrics when metrics_endpoint is set, and one line for each spot
+// of the watchlist to watchlist_path.
+
+use crate::card_utils::*;
+use crate::config::{WatchedSpot, CONFIG};
+use crate::exploiter::blueprint_exploitability_interval;
+use crate::nodes::*;
+use crate::trainer_utils::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    pub epoch: usize,
+    pub iterations: usize,
+    pub iters_per_second: f64,
+    pub nodes: usize,
+    pub memory_mb: f64,
+    pub zero_t_fraction: f64,
+    pub average_t: f64,
+    // Mean over the visited nodes and their actions
+    pub average_positive_regret: f64,
+    // Mean entropy in bits of the average strategy at the visited nodes of each street
+    pub strategy_entropy: [f64; 4],
+    pub exploitability: f64,
+    pub exploitability_confidence: f64,
+    pub watched: Vec<WatchedStrategy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedStrategy {
+    pub epoch: usize,
+    pub iterations: usize,
+    pub spot: String,
+    pub actions: Vec<String>,
+    // All empty until training reaches the spot
+    pub average_strategy: Vec<f32>,
+    pub current_strategy: Vec<f32>,
+    pub regrets: Vec<f32>,
+    pub t: i32,
+}
+
+// Computes the metrics for the epoch that just finished, prints them and appends them to
+// metrics_path. iterations is the total so far, and the epoch ran epoch_iters of them in
+// elapsed.
+pub fn record_epoch(
+    nodes: &Nodes,
+    epoch: usize,
+    iterations: usize,
+    epoch_iters: usize,
+    elapsed: Duration,
+) -> EpochMetrics {
+    let mut total: u64 = 0;
+    let mut zero: u64 = 0;
+    let mut total_t: u64 = 0;
+    let mut positive_regret = 0.0;
+    let mut regret_count: u64 = 0;
+    let mut entropy = [0.0; 4];
+    let mut entropy_count = [0; 4];
+    for elem in &nodes.dashmap {
+        let street = elem.key().street.min(RIVER);
+        for n in elem.value() {
+            let node = n.lock().unwrap();
+            total += 1;
+            total_t += node.t as u64;
+            if node.t == 0 {
+                zero += 1;
+                continue;
+            }
+            let regrets = &node.regrets[..node.num_actions];
+            positive_regret += regrets.iter().map(|&r| r.max(0.0) as f64).sum::<f64>();
+            regret_count += node.num_actions as u64;
+            entropy[street] += strategy_entropy(&node.cumulative_strategy());
+            entropy_count[street] += 1;
+        }
+    }
+    for street in 0..4 {
+        entropy[street] /= entropy_count[street].max(1) as f64;
+    }
+
+    let (exploitability, exploitability_confidence) =
+        blueprint_exploitability_interval(nodes, CONFIG.lbr_iters);
+    let metrics = EpochMetrics {
+        epoch,
+        iterations,
+        iters_per_second: epoch_iters as f64 / elapsed.as_secs_f64(),
+        nodes: total as usize,
+        memory_mb: memory_mb(),
+        zero_t_fraction: zero as f64 / total.max(1) as f64,
+        average_t: total_t as f64 / total.max(1) as f64,
+        average_positive_regret: positive_regret / regret_count.max(1) as f64,
+        strategy_entropy: entropy,
+        exploitability,
+        exploitability_confidence,
+        watched: watched_strategies(nodes, &CONFIG.watchlist, epoch, iterations),
+    };
+
+    println!(
+        "[INFO] {:.1} iterations/s, {} nodes, {:.0} MB",
+        metrics.iters_per_second, metrics.nodes, metrics.memory_mb
+    );
+    println!("Fraction zeros: {}", metrics.zero_t_fraction);
+    println!("Average t across all infosets: {}", metrics.average_t);
+    for watched in &metrics.watched {
+        println!(
+            "{}: {:?} {:?}",
+            watched.spot, watched.actions, watched.average_strategy
+        );
+    }
+
+    append_lines(&CONFIG.metrics_path, &[&metrics]);
+    append_lines(&CONFIG.watchlist_path, &metrics.watched);
+    metrics
+}
+
+// Every epoch recorded in the metrics file so far, or none if there isn't one
+pub fn read_metrics(path: &str) -> Vec<EpochMetrics> {
+    read_lines(path)
+}
+
+// Every spot and epoch recorded in the watchlist file so far
+pub fn read_watchlist(path: &str) -> Vec<WatchedStrategy> {
+    read_lines(path)
+}
+
+fn append_lines<T: Serialize>(path: &str, values: &[T]) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    for value in values {
+        writeln!(file, "{}", serde_json::to_string(value).unwrap()).unwrap();
+    }
+}
+
+// Lines that don't parse, like a half-written last line, are skipped
+fn read_lines<T: DeserializeOwned>(path: &str) -> Vec<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn strategy_entropy(strategy: &[f32]) -> f64 {
+    strategy
+        .iter()
+        .filter(|&&p| p > 0.0)
+        .map(|&p| -p as f64 * (p as f64).log2())
+        .sum()
+}
+
+pub fn watched_strategies(
+    nodes: &Nodes,
+    watchlist: &[WatchedSpot],
+    epoch: usize,
+    iterations: usize,
+) -> Vec<WatchedStrategy> {
+    watchlist
+        .iter()
+        .filter_map(|spot| {
+            let history =
+                ActionHistory::from_strings(spot.history.iter().map(String::as_str).collect());
+            // Spots past last_street aren't in the game being trained
+            if history.hand_over() {
+                return None;
+            }
+            let board = str2cards(&spot.board);
+            let infoset = nodes.infoset(&str2cards(&spot.hole), &board, &history);
+            let actions = infoset.next_actions(&nodes.bet_abstraction);
+            let mut watched = WatchedStrategy {
+                epoch,
+                iterations,
+                spot: format!("{} {} {}", spot.hole, spot.board, history),
+                actions: actions.iter().map(|a| a.to_string()).collect(),
+                average_strategy: Vec::new(),
+                current_strategy: Vec::new(),
+                regrets: Vec::new(),
+                t: 0,
+            };
+            if let Some(node) = nodes.get(&infoset) {
+                watched.average_strategy = node.cumulative_strategy().to_vec();
+                watched.current_strategy = node.current_strategy().to_vec();
+                watched.regrets = node.regrets[..node.num_actions].to_vec();
+                watched.t = node.t;
+            }
+            Some(watched)
+        })
+        .collect()
+}
+
+// The resident memory of this process, or 0 where /proc isn't available
+fn memory_mb() -> f64 {
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .map_or(0.0, |kb| kb.parse::<f64>().unwrap() / 1024.0)
+}
@@ -1,21 +1,26 @@
 use crate::card_utils;
 use crate::card_utils::*;
 use crate::config::CONFIG;
-use crate::exploiter::*;
+use crate::game::{Game, Holdem};
 use crate::nodes::*;
-use crate::ranges::Range;
+use crate::telemetry::record_epoch;
 use crate::trainer_utils::*;
 use ahash::AHashMap as HashMap;
 use rand::prelude::*;
 use rayon::prelude::*;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufReader, BufWriter, Write};
+use std::time::Instant;
 
 pub fn train(iters: usize, eval_every: usize, warm_start: bool) {
+    CONFIG.require_two_hole_cards("The heads-up trainer");
     let deck = card_utils::deck();
     let nodes = if warm_start {
         load_nodes(&CONFIG.nodes_path)
     } else {
+        // A fresh blueprint starts fresh metrics logs
+        let _ = fs::remove_file(&CONFIG.metrics_path);
+        let _ = fs::remove_file(&CONFIG.watchlist_path);
         Nodes::new(&CONFIG.bet_abstraction)
     };
     println!("[INFO] Beginning training.");
@@ -23,51 +28,19 @@ pub fn train(iters: usize, eval_every: usize, warm_start: bool) {
     for epoch in 0..num_epochs {
         println!("[INFO] Training epoch {}/{}", epoch + 1, num_epochs);
         let bar = card_utils::pbar(eval_every as usize);
+        let epoch_start = Instant::now();
 
-        (0..eval_every).into_par_iter().for_each(|_| {
-            cfr_iteration(&deck, &ActionHistory::new(), &nodes, -1);
+        (0..eval_every).into_par_iter().for_each(|i| {
+            let prune = should_prune(epoch * eval_every + i);
+            cfr_iteration(&deck, &ActionHistory::new(), &nodes, -1, prune);
             bar.inc(1);
         });
         bar.finish_with_message("Done");
+        // Saving the nodes isn't training, so it doesn't count towards the iterations per second
+        let elapsed = epoch_start.elapsed();
         serialize_nodes(&nodes);
-        blueprint_exploitability(&nodes, CONFIG.lbr_iters);
-
-        let infoset = InfoSet::from_hand(
-            &str2cards("6h6d"),
-            &str2cards("2s3dAc6c2h"),
-            &ActionHistory::from_strings(vec![
-                "Bet 300", "Call 300", "Call 0", "Call 0", "Call 0", "Call 0", "Call 0",
-            ]),
-        );
-
-        let infoset = InfoSet::from_hand(&str2cards("2c7h"), &Vec::new(), &ActionHistory::new());
-        println!("InfoSet: {infoset}");
-        println!(
-            "Actions: {:?}",
-            infoset.next_actions(&CONFIG.bet_abstraction)
-        );
-        println!("Node: {:?}", nodes.get(&infoset));
-
-        // Check what percent of nodes have t = 0
-        let mut zero = 0;
-        let mut total: u64 = 0;
-        let mut total_t: u64 = 0;
-        for elem in &nodes.dashmap {
-            let history_nodes = elem.value();
-            for (card_bucket, n) in history_nodes.iter().enumerate() {
-                let node = n.lock().unwrap();
-                total += 1;
-                total_t += node.t as u64;
-                if node.t == 0 {
-                    zero += 1;
-                }
-            }
-        }
-        println!("Fraction zeros: {}", zero as f64 / total as f64);
-        println!(
-            "Average t across all infosets: {}",
-            total_t as f64 / total as f64
-        );
+        let iterations = (epoch + 1) * eval_every;
+        record_epoch(&nodes, epoch, iterations, eval_every, elapsed);
     }
     println!("{} nodes reached.", nodes.len());
 }
@@ -80,27 +53,47 @@ pub fn load_nodes(path: &str) -> Nodes {
 }
 
 pub fn serialize_nodes(nodes: &Nodes) {
-    let file = File::create(&CONFIG.nodes_path).unwrap();
+    save_nodes(nodes, &CONFIG.nodes_path);
+}
+
+pub fn save_nodes(nodes: &Nodes, path: &str) {
+    let file = create_file(path);
     let mut buf_writer = BufWriter::new(file);
     bincode::serialize_into(&mut buf_writer, &nodes).expect("Failed to serialize nodes");
     buf_writer.flush().unwrap();
     println!("[INFO] Saved strategy.");
 }
 
-pub fn cfr_iteration(deck: &[Card], history: &ActionHistory, nodes: &Nodes, depth_limit: i32) {
+// Pluribus-style pruning: nothing is pruned during the warm-up, and after that every
+// iteration prunes except for a few that traverse the whole tree.
+pub fn should_prune(iteration: usize) -> bool {
+    iteration >= CONFIG.prune_after && thread_rng().gen::<f64>() >= CONFIG.full_traversal_prob
+}
+
+// An action with regret below prune_threshold is pruned, except on one in every few visits
+// to its node. The interval between those visits grows with how negative the regret is.
+pub fn is_pruned(node: &Node, action: usize) -> bool {
+    let regret = node.regrets[action];
+    if regret >= CONFIG.prune_threshold {
+        return false;
+    }
+    let interval = (CONFIG.prune_interval as f32 * regret / CONFIG.prune_threshold) as i64;
+    node.t as i64 % interval.max(1) != 0
+}
+
+pub fn cfr_iteration(
+    deck: &[Card],
+    history: &ActionHistory,
+    nodes: &Nodes,
+    depth_limit: i32,
+    prune: bool,
+) {
+    CONFIG.require_two_hole_cards("The heads-up trainer");
     [DEALER, OPPONENT].iter().for_each(|&traverser| {
         let mut deck = deck.to_vec();
         deck.shuffle(&mut rand::thread_rng());
         let board = [deck[0], deck[1], deck[2], deck[3], deck[4]];
-        let mut range = Range::new();
-        range.remove_blockers(&board);
-        let mut preflop_hands = Vec::with_capacity(range.hands.len());
-        for hand_index in 0..range.hands.len() {
-            let prob = range.probs[hand_index];
-            if prob > 0.0 {
-                preflop_hands.push(range.hands[hand_index]);
-            }
-        }
+        let preflop_hands = Holdem::hands(&board);
 
         let traverser_reach_probs = vec![1.0; preflop_hands.len()];
         let opp_reach_probs = vec![1.0; preflop_hands.len()];
@@ -115,34 +108,52 @@ pub fn cfr_iteration(deck: &[Card], history: &ActionHistory, nodes: &Nodes, dept
             nodes,
             -1,
             None,
+            prune,
+        );
+    });
+}
+
+// One iteration of chance-sampled CFR on a game other than hold'em, like cfr_iteration: each
+// player traverses the tree with every hand that doesn't conflict with a random board.
+pub fn game_cfr_iteration<G: Game>(nodes: &Nodes<G>) {
+    [DEALER, OPPONENT].iter().for_each(|&traverser| {
+        let board = G::deal();
+        let hands = G::hands(&board);
+        let reach_probs = vec![1.0; hands.len()];
+        iterate(
+            traverser,
+            hands,
+            board,
+            &G::root(),
+            reach_probs.clone(),
+            reach_probs,
+            nodes,
+            -1,
+            None,
+            false,
         );
     });
 }
 
-pub fn iterate(
+pub fn iterate<G: Game>(
     traverser: usize,
-    preflop_hands: Vec<[Card; 2]>,
-    board: [Card; 5],
-    history: &ActionHistory,
+    preflop_hands: Vec<G::Hand>,
+    board: G::Board,
+    history: &G::History,
     traverser_reach_probs: Vec<f64>,
     opp_reach_probs: Vec<f64>,
-    nodes: &Nodes,
+    nodes: &Nodes<G>,
     depth_limit: i32,
-    depth_limit_nodes: Option<&Nodes>,
+    depth_limit_nodes: Option<&Nodes<G>>,
+    prune: bool,
 ) -> Vec<f64> {
     let N = preflop_hands.len();
     if N == 0 {
         return Vec::new();
     }
 
-    if history.hand_over() {
-        return terminal_utility_vectorized(
-            preflop_hands,
-            opp_reach_probs,
-            &board,
-            history,
-            traverser,
-        );
+    if G::hand_over(history) {
+        return G::terminal_utility(preflop_hands, opp_reach_probs, &board, history, traverser);
     }
 
     // TODO: Don't call this if you're training a blueprint.
@@ -163,31 +174,54 @@ pub fn iterate(
     // Look up the DCFR node for this information set, or make a new one if it
     // doesn't exist
     let history = history.clone();
-    let infosets: Vec<InfoSet> = preflop_hands
+    let infosets: Vec<InfoSet<G::History>> = preflop_hands
         .iter()
-        .map(|h| InfoSet::from_hand(h, &board, &history))
+        .map(|h| G::infoset(nodes, h, &board, &history))
         .collect();
 
     let strategies: Vec<SmallVecFloats> = nodes.get_current_strategy_vectorized(&infosets);
     let opponent = 1 - traverser;
-    if history.player == traverser {
+    let player = G::player(&history);
+    if player == traverser {
         nodes.update_strategy_sum_vectorized(&infosets, &traverser_reach_probs);
     }
 
-    let actions = history.next_actions(&nodes.bet_abstraction);
+    // Only the traverser's actions are pruned, and never on the last street, where the
+    // subtrees are small
+    let pruning_nodes: Option<Vec<Node>> =
+        if prune && player == traverser && !G::on_last_street(&history) {
+            Some(nodes.get_nodes_vectorized(&infosets))
+        } else {
+            None
+        };
+
+    let actions = G::next_actions(&history, &nodes.bet_abstraction);
     let mut node_utility: Vec<f64> = vec![0.0; N];
     // Recurse to further nodes in the game tree. Find the utilities for each action.
-    let action_utilities: Vec<Vec<f64>> = (0..actions.len())
-        .map(|i| -> Vec<f64> {
+    let action_utilities: Vec<(Vec<f64>, Vec<bool>)> = (0..actions.len())
+        .map(|i| -> (Vec<f64>, Vec<bool>) {
             // Maps traverser_preflop_hand to prob of taking this action
             let probs: Vec<f32> = strategies.iter().map(|s| s[i]).collect();
-            let mut next_history = history.clone();
-            next_history.add(&actions[i]);
+            let next_history = G::next_history(&history, &actions[i]);
+
+            // A hand can only prune an action it never takes. The action's subtree is skipped
+            // if every hand prunes it, since then none of its utilities are needed.
+            let pruned: Vec<bool> = match &pruning_nodes {
+                Some(pruning_nodes) if !G::hand_over(&next_history) => pruning_nodes
+                    .iter()
+                    .zip(probs.iter())
+                    .map(|(node, &prob)| prob == 0.0 && is_pruned(node, i))
+                    .collect(),
+                _ => vec![false; N],
+            };
+            if pruned.iter().all(|&p| p) {
+                return (vec![0.0; N], pruned);
+            }
 
             let mut traverser_reach_probs = traverser_reach_probs.clone();
             let mut opp_reach_probs = opp_reach_probs.clone();
 
-            if history.player == traverser {
+            if player == traverser {
                 for i in 0..N {
                     traverser_reach_probs[i] *= probs[i] as f64;
                 }
@@ -198,66 +232,50 @@ pub fn iterate(
                 }
             }
 
-            let mut nonzero_preflop_hands: Vec<[Card; 2]> = Vec::with_capacity(N);
-            let mut nonzero_traverser_reach_probs: Vec<f64> = Vec::with_capacity(N);
-            let mut nonzero_opp_reach_probs: Vec<f64> = Vec::with_capacity(N);
-            let mut zeros: Vec<usize> = Vec::with_capacity(N);
-            for i in 0..preflop_hands.len() {
-                if traverser_reach_probs[i] > 1e-10 || opp_reach_probs[i] > 1e-10 {
-                    nonzero_preflop_hands.push(preflop_hands[i]);
-                    nonzero_traverser_reach_probs.push(traverser_reach_probs[i]);
-                    nonzero_opp_reach_probs.push(opp_reach_probs[i]);
-                } else {
-                    zeros.push(i);
-                }
+            // Every hand's counterfactual utility is needed for its regrets, even when the
+            // traverser never takes this action with it, so hands can't be dropped one by one.
+            // The subtree is only skipped once neither player reaches it with any hand.
+            let reached = traverser_reach_probs
+                .iter()
+                .chain(opp_reach_probs.iter())
+                .any(|&prob| prob > 1e-10);
+            if !reached {
+                return (vec![0.0; N], pruned);
             }
 
-            let mut utility: Vec<f64> = iterate(
+            let utility: Vec<f64> = iterate(
                 traverser,
-                nonzero_preflop_hands,
+                preflop_hands.clone(),
                 board,
                 &next_history,
-                nonzero_traverser_reach_probs,
-                nonzero_opp_reach_probs,
+                traverser_reach_probs,
+                opp_reach_probs,
                 nodes,
                 depth_limit - 1,
                 depth_limit_nodes,
+                prune,
             );
 
-            // Hacky GPT-4 code sorry
-            let mut result_utilities: Vec<f64> = vec![0.0; preflop_hands.len()];
-            let mut utility_idx = 0;
-            let mut zeros_idx = 0;
-            for i in 0..preflop_hands.len() {
-                if zeros_idx < zeros.len() && zeros[zeros_idx] == i {
-                    // If the current index is in `zeros`, we just increment zeros_idx to move to the next zero
-                    zeros_idx += 1;
-                } else {
-                    // Otherwise, insert the utility value from the `utility` vector
-                    result_utilities[i] = utility[utility_idx];
-                    utility_idx += 1;
-                }
-            }
-            utility = result_utilities;
-            // End hacky GPT-4 code
-
             for n in 0..node_utility.len() {
-                let prob: f32 = if history.player == traverser {
-                    probs[n]
-                } else {
-                    1.0
-                };
+                let prob: f32 = if player == traverser { probs[n] } else { 1.0 };
                 node_utility[n] += prob as f64 * utility[n];
             }
-            utility
+            (utility, pruned)
         })
         .collect();
 
     // Update regrets for the traversing player
-    if history.player == traverser {
+    if player == traverser {
         // Action utilities is shape [actions, traverser_hands]
-        for (action_idx, action_utility) in action_utilities.iter().enumerate() {
-            nodes.add_regret_vectorized(&infosets, action_utility, &node_utility, action_idx);
+        for (action_idx, (action_utility, pruned)) in action_utilities.into_iter().enumerate() {
+            // Pruned actions weren't explored, so their regret isn't updated or discounted
+            nodes.add_regret_vectorized(
+                &infosets,
+                &action_utility,
+                &node_utility,
+                action_idx,
+                &pruned,
+            );
         }
     }
     node_utility
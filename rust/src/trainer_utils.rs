@@ -1,8 +1,9 @@
-use crate::card_abstraction::Abstraction;
+use crate::card_abstraction::{load_global_abstraction, Abstraction};
 use crate::card_utils::*;
 use crate::config::CONFIG;
 use crate::nodes::*;
 use ahash::AHashMap as HashMap;
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 use rand::{prelude::SliceRandom, thread_rng};
 use smallvec::SmallVec;
@@ -23,10 +24,11 @@ pub const FOLD: Action = Action {
 };
 pub const ALL_IN: f64 = -1.0;
 
-pub static ABSTRACTION: Lazy<Abstraction> = Lazy::new(Abstraction::new);
+pub static ABSTRACTION: Lazy<&Abstraction> =
+    Lazy::new(|| load_global_abstraction().unwrap_or_else(|error| panic!("{error}")));
 
 pub type Strategy = HashMap<Action, f64>;
-pub type Amount = u16;
+pub type Amount = u32;
 pub type SmallVecFloats = SmallVec<[f32; NUM_ACTIONS]>;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, serde::Serialize, serde::Deserialize)]
@@ -69,9 +71,9 @@ impl ActionHistory {
             history: SmallVec::with_capacity(10),
             last_action: None,
             current_street_length: 0,
-            stacks: [CONFIG.stack_size, CONFIG.stack_size],
+            stacks: [CONFIG.starting_stack(), CONFIG.starting_stack()],
             street: PREFLOP,
-            player: DEALER,
+            player: CONFIG.first_player(PREFLOP),
         }
     }
 
@@ -111,18 +113,8 @@ impl ActionHistory {
             return true;
         }
 
-        let last_street = match CONFIG.last_street.as_str() {
-            "flop" => FLOP,
-            "turn" => TURN,
-            "river" => RIVER,
-            _ => panic!(),
-        };
-        if self.street > last_street {
-            // Change to FLOP for flop holdem, RIVER for texas holdem
-            // Showdown
-            return true;
-        }
-        false
+        // Showdown
+        self.street == SHOWDOWN
     }
 
     // Add an new action to this history, and update the state
@@ -144,7 +136,10 @@ impl ActionHistory {
         if self.stacks[0] == self.stacks[1] && self.current_street_length >= 2 {
             self.street += 1;
             self.current_street_length = 0;
-            self.player = OPPONENT;
+            self.player = CONFIG.first_player(self.street);
+            if self.street > CONFIG.last_street() {
+                self.street = SHOWDOWN;
+            }
         }
         if self.stacks[0] == 0 && self.stacks[1] == 0 {
             self.street = SHOWDOWN;
@@ -171,28 +166,25 @@ impl ActionHistory {
 
     pub fn stack_sizes(&self) -> [Amount; 2] {
         let mut stacks = self.stacks;
-        if stacks[DEALER] == CONFIG.stack_size {
-            stacks[DEALER] -= CONFIG.small_blind;
-        }
-        if stacks[OPPONENT] == CONFIG.stack_size {
-            stacks[OPPONENT] -= CONFIG.big_blind;
+        for player in [DEALER, OPPONENT] {
+            if stacks[player] == CONFIG.starting_stack() {
+                stacks[player] -= CONFIG.blind(player);
+            }
         }
         stacks
     }
 
     pub fn pot(&self) -> Amount {
-        let pot = 2 * CONFIG.stack_size - self.stacks[0] - self.stacks[1];
-        if pot == 0 {
-            CONFIG.big_blind
-        } else {
-            pot
-        }
+        // The pot includes the antes, which are already taken out of the stacks, and the
+        // blinds and straddle of the players who haven't acted yet
+        let stacks = self.stack_sizes();
+        2 * CONFIG.stack_size - stacks[0] - stacks[1]
     }
 
     // Returns the amount needed to call, so 0 for checking
     pub fn to_call(&self) -> Amount {
         if self.street == PREFLOP && self.history.is_empty() {
-            CONFIG.big_blind
+            CONFIG.largest_blind()
         } else {
             self.stacks[self.player] - self.stacks[1 - self.player]
         }
@@ -200,7 +192,7 @@ impl ActionHistory {
 
     pub fn min_bet(&self) -> Amount {
         if self.history.is_empty() {
-            CONFIG.big_blind
+            CONFIG.largest_blind()
         } else if self.current_street_length == 0 {
             0
         } else {
@@ -215,7 +207,11 @@ impl ActionHistory {
     }
 
     pub fn max_bet(&self) -> Amount {
-        self.stacks[self.player]
+        if CONFIG.pot_limit() {
+            pot_limit_bet(self.pot(), self.to_call()).min(self.stacks[self.player])
+        } else {
+            self.stacks[self.player]
+        }
     }
 
     // Returns a vector of the possible next actions after this state, that are
@@ -223,7 +219,7 @@ impl ActionHistory {
     pub fn next_actions(&self, bet_abstraction: &[Vec<f64>]) -> SmallVec<[Action; NUM_ACTIONS]> {
         // Add all the potential bet sizes in the abstraction, and call and fold actions.
         // Then later we filter out the illegal actions.
-        debug_assert!(self.street <= RIVER + 1);
+        debug_assert!(self.street <= SHOWDOWN);
         if self.hand_over() {
             return smallvec![];
         }
@@ -233,7 +229,7 @@ impl ActionHistory {
             SmallVec::with_capacity(NUM_ACTIONS);
         for pot_fraction in bet_abstraction[self.street].iter() {
             let bet_size = if pot_fraction == &ALL_IN {
-                self.stacks[self.player]
+                self.max_bet()
             } else {
                 (pot_fraction * (pot as f64)) as Amount + self.to_call()
             };
@@ -401,22 +397,22 @@ fn find_closest_log(v: Vec<Amount>, n: Amount) -> Amount {
 pub fn get_hand(deck: &[Card], player: usize, street: usize) -> Vec<Card> {
     // In this implementation, the deck cards are defined as follows:
     // dealer1 dealer2 opponent1 opponent2 flop1 flop2 flop3 turn river
+    // In Omaha each player has four hole cards instead of two.
+    let n = CONFIG.hole_cards();
     let hole = match player {
-        DEALER => &deck[0..2],
-        OPPONENT => &deck[2..4],
+        DEALER => &deck[0..n],
+        OPPONENT => &deck[n..2 * n],
         _ => panic!("Bad player ID"),
     };
-    let board = match street {
-        PREFLOP => &[],
-        FLOP => &deck[4..7],
-        TURN => &deck[4..8],
-        RIVER => &deck[4..9],
-        _ => panic!("Invalid street"),
-    };
-
+    let board = &deck[2 * n..2 * n + board_length(street)];
     [hole, board].concat()
 }
 
+// The largest bet allowed in pot-limit: calling and then raising the size of the pot
+pub fn pot_limit_bet(pot: Amount, to_call: Amount) -> Amount {
+    to_call + pot + to_call
+}
+
 pub fn board_length(street: usize) -> usize {
     match street {
         PREFLOP => 0,
@@ -427,9 +423,11 @@ pub fn board_length(street: usize) -> usize {
     }
 }
 
+// A history and a bucket of the acting player's cards. Hold'em's histories unless it's given
+// another game's.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, serde::Serialize, serde::Deserialize)]
-pub struct InfoSet {
-    pub history: ActionHistory,
+pub struct InfoSet<H = ActionHistory> {
+    pub history: H,
     pub card_bucket: i32,
 }
 
@@ -446,12 +444,21 @@ impl InfoSet {
     }
 
     pub fn from_hand(hole: &[Card], board: &[Card], history: &ActionHistory) -> InfoSet {
-        debug_assert!(!board.contains(&hole[0]) && !board.contains(&hole[1]));
+        InfoSet::with_abstraction(hole, board, history, &ABSTRACTION)
+    }
+
+    pub fn with_abstraction(
+        hole: &[Card],
+        board: &[Card],
+        history: &ActionHistory,
+        abstraction: &Abstraction,
+    ) -> InfoSet {
+        debug_assert!(hole.iter().all(|c| !board.contains(c)));
         let board = &board[..board_length(history.street)];
         let hand: SmallVecHand = hole.iter().chain(board.iter()).cloned().collect();
         InfoSet {
             history: history.clone(),
-            card_bucket: ABSTRACTION.bin(&hand),
+            card_bucket: abstraction.bin(&hand),
         }
     }
 
@@ -476,9 +483,11 @@ fn hand_with_bucket(bucket: i32, street: usize) -> String {
     } else if street == RIVER {
         load_river_isomorphic()
     } else if street == PREFLOP {
-        isomorphic_preflop_hands()
+        // Every pair of cards, since the preflop buckets may be suit-aware
+        deck()
             .iter()
-            .map(|cards: &Vec<Card>| cards2hand(&cards))
+            .combinations(2)
+            .map(|cards| cards2hand(&deepcopy(&cards)))
             .collect()
     } else {
         panic!()
@@ -585,16 +594,12 @@ pub fn terminal_utility_vectorized_fast(
         return utils;
     }
 
+    let strengths = FAST_HAND_TABLE.hole_strengths(&preflop_hands, board);
     let mut hand_data: Vec<HandData> = (0..preflop_hands.len())
-        .map(|i| {
-            let h = preflop_hands[i];
-            let river_hand = [h[0], h[1], board[0], board[1], board[2], board[3], board[4]];
-            let strength = FAST_HAND_TABLE.hand_strength(&river_hand).unwrap().clone();
-            HandData {
-                hand: h,
-                strength,
-                prob: opp_reach_probs[i],
-            }
+        .map(|i| HandData {
+            hand: preflop_hands[i],
+            strength: strengths[i],
+            prob: opp_reach_probs[i],
         })
         .collect();
 
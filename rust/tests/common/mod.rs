@@ -0,0 +1,31 @@
+// CONFIG is read once per process from ../params.toml, so tests that need other settings get
+// their own test binary, which runs from a directory next to an edited copy of it. Processes
+// that the test starts read the copy too.
+
+use std::fs;
+
+// Copies params.toml with each of the named settings set to the given value, and moves into a
+// directory next to the copy. Must be called before anything reads CONFIG.
+pub fn use_params(name: &str, overrides: &[(&str, &str)]) {
+    let dir = std::env::temp_dir().join(format!("optimus_{name}_{}", std::process::id()));
+    fs::create_dir_all(dir.join("run")).unwrap();
+    let mut unused: Vec<&str> = overrides.iter().map(|(setting, _)| *setting).collect();
+    let params: String = fs::read_to_string("../params.toml")
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let setting = line.split('=').next().unwrap().trim();
+            match overrides.iter().find(|(name, _)| *name == setting) {
+                Some((name, value)) => {
+                    unused.retain(|unused| unused != name);
+                    format!("{name} = {value}")
+                }
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    assert!(unused.is_empty(), "params.toml has no settings {unused:?}");
+    fs::write(dir.join("params.toml"), params).unwrap();
+    std::env::set_current_dir(dir.join("run")).unwrap();
+}
@@ -0,0 +1,59 @@
+// Runs distributed training with the coordinator and workers as separate processes of the
+// distributed binary, the way they run on different machines, with a preflop-only game.
+
+mod common;
+
+use optimus::*;
+use std::net::TcpListener;
+use std::process::{Child, Command};
+
+const ITERATIONS: usize = 20;
+
+fn distributed(args: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_distributed"))
+        .args(args)
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn distributed_training_across_processes() {
+    let iterations = ITERATIONS.to_string();
+    common::use_params(
+        "distributed",
+        &[
+            ("last_street", "\"preflop\""),
+            ("train_iters", &iterations),
+            ("eval_every", "10"),
+            ("sync_every", "5"),
+            ("warm_start", "false"),
+        ],
+    );
+    assert_eq!(CONFIG.last_street, "preflop");
+
+    // Find a free port for the coordinator
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let mut coordinator = distributed(&["coordinator", &address, "2"]);
+    let workers: Vec<Child> = (0..2).map(|_| distributed(&["worker", &address])).collect();
+    assert!(coordinator.wait().unwrap().success());
+    for mut worker in workers {
+        assert!(worker.wait().unwrap().success());
+    }
+
+    // Every iteration deals a board and then updates the strategy sum of each of the 1081
+    // hands that don't block it at the root, once, so the root's nodes count every iteration
+    // that any worker ran.
+    let nodes = load_nodes(&CONFIG.nodes_path);
+    let root = nodes.dashmap.get(&ActionHistory::new()).unwrap();
+    let visits: i32 = root.iter().map(|n| n.lock().unwrap().t).sum();
+    assert_eq!(visits as usize, ITERATIONS * 1081);
+    let strategy_sum: f32 = root
+        .iter()
+        .map(|n| n.lock().unwrap().strategy_sum.iter().sum::<f32>())
+        .sum();
+    assert!(strategy_sum > 0.0);
+}
@@ -0,0 +1,44 @@
+// Pot-limit Omaha, which trains heads-up with the multi-way trainer
+
+mod common;
+
+use optimus::*;
+
+// Fewer flop buckets than Omaha hands have, which the hold'em abstraction doesn't use
+fn use_omaha_params() {
+    common::use_params(
+        "omaha",
+        &[
+            ("variant", "\"omaha\""),
+            ("flop_buckets", "50"),
+            ("lbr_opponent_samples", "20"),
+            // Short stacks keep the variance of LBR's winnings down
+            ("stack_size", "1000"),
+        ],
+    );
+}
+
+#[test]
+fn omaha_nodes_have_a_node_per_omaha_bucket() {
+    use_omaha_params();
+    let deck = deck();
+    let nodes = MultiwayNodes::new(&CONFIG.bet_abstraction);
+    for _ in 0..200 {
+        multiway_cfr_iteration(&deck, 2, &nodes);
+    }
+    assert_eq!(num_omaha_buckets(PREFLOP), 169);
+    assert_eq!(num_omaha_buckets(FLOP), 117);
+    for elem in nodes.dashmap.iter() {
+        assert_eq!(elem.value().len(), num_omaha_buckets(elem.key().street));
+    }
+    assert!(nodes.dashmap.iter().any(|elem| elem.key().street == RIVER));
+}
+
+#[test]
+fn lbr_exploits_an_untrained_omaha_blueprint() {
+    use_omaha_params();
+    // Every strategy is uniform, so the blueprint often folds to LBR's bets
+    let nodes = MultiwayNodes::new(&CONFIG.bet_abstraction);
+    let (exploitability, confidence) = multiway_exploitability(&nodes, 60);
+    assert!(exploitability > 0.0 && confidence > 0.0);
+}
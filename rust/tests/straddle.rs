@@ -0,0 +1,53 @@
+// Games with a straddle and antes
+
+mod common;
+
+use optimus::*;
+
+#[test]
+fn straddle_pot_and_to_call() {
+    common::use_params("straddle", &[("ante", "10"), ("straddle", "200")]);
+    assert_eq!((CONFIG.ante, CONFIG.straddle), (10, 200));
+
+    // The dealer straddles instead of posting the small blind, so the big blind acts first
+    // and has to call the straddle. Action amounts include the blind the player posted.
+    let mut history = ActionHistory::new();
+    assert_eq!(history.player, OPPONENT);
+    assert_eq!(history.pot(), 100 + 200 + 2 * 10);
+    assert_eq!(history.to_call(), 200);
+    assert_eq!(history.stack_sizes(), [20000 - 10 - 200, 20000 - 10 - 100]);
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: 200,
+    });
+    assert_eq!(history.pot(), 200 + 200 + 2 * 10);
+    // The straddler has the option to raise, or checks by calling their own straddle
+    assert_eq!(history.player, DEALER);
+    assert_eq!(history.to_call(), 200);
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: 200,
+    });
+    assert_eq!(history.street, FLOP);
+    assert_eq!(history.pot(), 420);
+    assert_eq!(history.to_call(), 0);
+
+    // The multi-way trainer plays heads-up games like that too, so the button straddles
+    // instead of posting the small blind, and the big blind acts first
+    let history = MultiwayHistory::new(2);
+    assert_eq!(history.player, 1);
+    assert_eq!(
+        (history.contribution(0), history.contribution(1)),
+        (200 + 10, 100 + 10)
+    );
+    assert_eq!(history.pot(), ActionHistory::new().pot());
+    assert_eq!(history.to_call(), 100);
+
+    // With three players, under the gun straddles and the small blind acts first
+    let history = MultiwayHistory::new(3);
+    assert_eq!(history.player, 1);
+    assert_eq!(history.pot(), 50 + 100 + 200 + 3 * 10);
+    assert_eq!(history.to_call(), 150);
+    // A raise has to be at least the size of the straddle
+    assert_eq!(history.min_bet(), 150 + 200);
+}
@@ -8,6 +8,7 @@ use rand::prelude::*;
 use rayon::prelude::*;
 use smallvec::*;
 use std::collections::HashSet;
+use std::sync::Mutex;
 
 static BOT: Lazy<Bot> = Lazy::new(|| {
     Bot::new(
@@ -91,7 +92,7 @@ fn uint_hands() {
 }
 
 fn fast_hand_strength(hand: Vec<&str>, table: &FastHandTable) -> i32 {
-    table.hand_strength(&strvec2cards(&hand)).unwrap()
+    table.hand_strength(&strvec2cards(&hand))
 }
 
 #[test]
@@ -755,6 +756,139 @@ fn all_preflop_hands_in_bucket() {
     assert!(bin_count.iter().all(|&x| x > 0));
 }
 
+#[test]
+fn preflop_abstractions_cover_their_buckets() {
+    let holes: Vec<Vec<Card>> = deck()
+        .iter()
+        .combinations(2)
+        .map(|cards| deepcopy(&cards))
+        .collect();
+    for (preflop_abstraction, n_buckets) in [
+        (PreflopAbstraction::Lossless, 169),
+        (PreflopAbstraction::SuitAware, 1326),
+        (PreflopAbstraction::Clustered, 8),
+    ] {
+        let buckets: HashSet<i32> = holes
+            .iter()
+            .map(|hole| Abstraction::preflop_bin(hole, preflop_abstraction))
+            .collect();
+        assert_eq!(buckets, (0..n_buckets).collect());
+    }
+    let cluster =
+        |hand: &str| Abstraction::preflop_bin(&str2cards(hand), PreflopAbstraction::Clustered);
+    assert_eq!(cluster("AsAh"), 7);
+    assert_eq!(cluster("7c2d"), 0);
+    assert_eq!(cluster("Td4d"), cluster("5h6h"));
+}
+
+#[test]
+fn board_textures() {
+    let texture = BoardTexture::new(&str2cards("Kh7c2d"));
+    assert_eq!(texture.suitedness, Suitedness::Rainbow);
+    assert_eq!(texture.pairing, Pairing::Unpaired);
+    assert_eq!(texture.connectivity, Connectivity::Disconnected);
+    assert_eq!(texture.high_card, HighCard::Broadway);
+
+    let texture = BoardTexture::new(&str2cards("JhTh9h"));
+    assert_eq!(texture.suitedness, Suitedness::Monotone);
+    assert_eq!(texture.connectivity, Connectivity::Connected);
+
+    let texture = BoardTexture::new(&str2cards("As5d4c3c"));
+    assert_eq!(texture.suitedness, Suitedness::TwoTone);
+    assert_eq!(texture.connectivity, Connectivity::HighlyConnected);
+    assert_eq!(texture.high_card, HighCard::Ace);
+
+    let texture = BoardTexture::new(&str2cards("8s8d3h3c9d"));
+    assert_eq!(texture.pairing, Pairing::DoublePaired);
+    assert_eq!(texture.high_card, HighCard::Low);
+
+    // Boards from the GUI can be anything, so those are checked
+    assert!(BoardTexture::try_new(&str2cards("Kh7c2d")).is_ok());
+    assert!(BoardTexture::try_new(&str2cards("Kh7c")).is_err());
+    assert!(BoardTexture::try_new(&str2cards("Kh7c2d3s4s5s")).is_err());
+    assert!(BoardTexture::try_new(&str2cards("Kh7cKh")).is_err());
+
+    // Different textures never share an id
+    let textures: HashSet<BoardTexture> = deck()
+        .iter()
+        .combinations(4)
+        .map(|board| BoardTexture::new(&deepcopy(&board)))
+        .collect();
+    let ids: HashSet<usize> = textures.iter().map(|t| t.id()).collect();
+    assert_eq!(ids.len(), textures.len());
+    assert!(ids.iter().all(|&id| id < NUM_BOARD_TEXTURES));
+}
+
+#[test]
+fn history_bucket_granularity() {
+    let rules = vec![
+        GranularityRule {
+            min_pot: 0.0,
+            min_bets: 0,
+            fraction: 0.5,
+        },
+        GranularityRule {
+            min_pot: 20.0,
+            min_bets: 0,
+            fraction: 1.0,
+        },
+        GranularityRule {
+            min_pot: 0.0,
+            min_bets: 4,
+            fraction: 0.1,
+        },
+    ];
+    let limped = ActionHistory::from_strings(vec!["Call 100", "Call 100"]);
+    let three_bet = ActionHistory::from_strings(vec!["Bet 200", "Bet 1000", "Call 800"]);
+    let four_bets = ActionHistory::from_strings(vec![
+        "Bet 200", "Bet 1000", "Call 800", "Call 0", "Bet 1000", "Bet 3000",
+    ]);
+    assert_eq!(history_buckets(&rules, &ActionHistory::new(), 169), 169);
+    assert_eq!(history_buckets(&rules, &limped, 1000), 500);
+    assert_eq!(history_buckets(&rules, &three_bet, 1000), 1000);
+    assert_eq!(history_buckets(&rules, &four_bets, 1000), 100);
+
+    // A history with fewer nodes than its street has buckets shares them between neighbouring
+    // buckets, and never looks past the last one
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    let n_actions = limped.next_actions(&CONFIG.bet_abstraction).len();
+    let history_nodes: Vec<Mutex<Node>> = (0..10)
+        .map(|t| {
+            let mut node = Node::new(n_actions);
+            node.t = t;
+            Mutex::new(node)
+        })
+        .collect();
+    nodes.dashmap.insert(limped.clone(), history_nodes);
+    let street_buckets = num_buckets(FLOP) as i32;
+    for (card_bucket, t) in [(0, 0), (street_buckets / 2, 5), (street_buckets - 1, 9)] {
+        let infoset = InfoSet {
+            history: limped.clone(),
+            card_bucket,
+        };
+        assert_eq!(nodes.get(&infoset).unwrap().t, t);
+    }
+}
+
+#[test]
+fn regret_pruning_intervals() {
+    let mut node = Node::new(3);
+    node.regrets = [
+        CONFIG.prune_threshold / 2.0,
+        CONFIG.prune_threshold * 3.0,
+        0.0,
+        0.0,
+        0.0,
+    ];
+    let interval = 3 * CONFIG.prune_interval as i32;
+    for t in [0, 1, interval - 1, interval, interval + 1] {
+        node.t = t;
+        assert!(!is_pruned(&node, 0));
+        assert_eq!(is_pruned(&node, 1), t % interval != 0);
+        assert!(!is_pruned(&node, 2));
+    }
+}
+
 #[test]
 fn all_in_showdown_street() {
     let history = ActionHistory::from_strings(vec!["Call 100", "Bet 20000", "Call 19900"]);
@@ -1094,3 +1228,919 @@ fn blueprint_contains_all_infosets() {
         }
     }
 }
+
+#[test]
+fn kuhn_converges_to_equilibrium() {
+    let nodes: Nodes<Kuhn> = Nodes::default();
+    for _ in 0..10_000 {
+        game_cfr_iteration(&nodes);
+    }
+    let value = game_value(&nodes);
+    assert!(
+        (value - KUHN_GAME_VALUE).abs() < 1e-3,
+        "Kuhn game value {value} should be close to -1/18"
+    );
+    assert!(exact_exploitability(&nodes) < 1e-2);
+}
+
+#[test]
+fn kuhn_best_response_to_uniform() {
+    // Untrained nodes pass and bet with equal probability, which is exploitable from both seats.
+    let uniform: Nodes<Kuhn> = Nodes::default();
+    let value = game_value(&uniform);
+    assert!(best_response_value(&uniform, DEALER) > value + 0.1);
+    assert!(best_response_value(&uniform, OPPONENT) > -value + 0.1);
+}
+
+#[test]
+fn leduc_converges_to_equilibrium() {
+    let nodes: Nodes<Leduc> = Nodes::default();
+    for _ in 0..10_000 {
+        game_cfr_iteration(&nodes);
+    }
+    let value = game_value(&nodes);
+    assert!(
+        (value - LEDUC_GAME_VALUE).abs() < 1e-2,
+        "Leduc game value {value} should be close to {LEDUC_GAME_VALUE}"
+    );
+    assert!(exact_exploitability(&nodes) < 0.05);
+}
+
+#[test]
+fn starting_pot_includes_blinds_and_antes() {
+    let history = ActionHistory::new();
+    let blinds = CONFIG.blind(DEALER) + CONFIG.blind(OPPONENT);
+    assert_eq!(history.pot(), blinds + 2 * CONFIG.ante);
+    assert_eq!(history.to_call(), CONFIG.largest_blind());
+    assert_eq!(history.player, CONFIG.first_player(PREFLOP));
+}
+
+#[test]
+fn checking_down_ends_after_last_street() {
+    let mut history = ActionHistory::new();
+    while !history.hand_over() {
+        let street = history.street;
+        assert!(street <= CONFIG.last_street());
+        history.add(&Action {
+            action: ActionType::Call,
+            amount: history.to_call(),
+        });
+    }
+    assert_eq!(history.street, SHOWDOWN);
+}
+
+#[test]
+fn short_deck_hand_rankings() {
+    let flush = short_deck_hand_strength(&str2cards("6h8hThQhKh"));
+    let full_house = short_deck_hand_strength(&str2cards("AcAdAhKsKd"));
+    let low_straight = short_deck_hand_strength(&str2cards("Ac6d7h8s9c"));
+    let straight = short_deck_hand_strength(&str2cards("6d7h8s9cTc"));
+    let trips = short_deck_hand_strength(&str2cards("9c9d9hKsQc"));
+    let ace_high = short_deck_hand_strength(&str2cards("Ac7d8h9sJc"));
+    let low_straight_flush = short_deck_hand_strength(&str2cards("Ah6h7h8h9hKcKd"));
+    let quads = short_deck_hand_strength(&str2cards("AcAdAhAsKd"));
+
+    assert!(flush > full_house);
+    assert!(low_straight > trips);
+    assert!(straight > low_straight);
+    assert!(low_straight > ace_high);
+    assert!(low_straight_flush > quads);
+    assert!(quads > flush);
+}
+
+#[test]
+fn short_deck_seven_card_hands() {
+    // The best five cards are used, including kickers
+    let two_pair_ace_kicker = short_deck_hand_strength(&str2cards("KcKd7h7s6cAdTh"));
+    let two_pair_ten_kicker = short_deck_hand_strength(&str2cards("KcKd7h7s6c9dTh"));
+    let three_pairs = short_deck_hand_strength(&str2cards("KcKd7h7s6c6dTh"));
+    assert!(two_pair_ace_kicker > two_pair_ten_kicker);
+    assert_eq!(three_pairs, two_pair_ten_kicker);
+}
+
+#[test]
+fn multiway_side_pots() {
+    let mut history = MultiwayHistory::with_stacks(&[1000, 5000, 20000]);
+    // The button shoves, the small blind calls and the big blind raises the small blind all-in
+    history.add(&Action {
+        action: ActionType::Bet,
+        amount: 1000,
+    });
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: 950,
+    });
+    history.add(&Action {
+        action: ActionType::Bet,
+        amount: 4900,
+    });
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: 4000,
+    });
+    assert!(history.hand_over());
+    assert_eq!(history.street, SHOWDOWN);
+
+    let pots = history.pots();
+    assert_eq!(pots.len(), 2);
+    assert_eq!(pots[0].0, 3000);
+    assert_eq!(pots[0].1.as_slice(), &[0, 1, 2]);
+    assert_eq!(pots[1].0, 8000);
+    assert_eq!(pots[1].1.as_slice(), &[1, 2]);
+
+    // The button has the best hand and wins the main pot, the small blind wins the side pot
+    let utility = history.utility(&[3, 2, 1]);
+    assert_eq!(utility.as_slice(), &[2000.0, 3000.0, -5000.0]);
+}
+
+#[test]
+fn multiway_folds_to_big_blind() {
+    let mut history = MultiwayHistory::new(3);
+    assert_eq!(history.player, 0);
+    history.add(&FOLD);
+    history.add(&FOLD);
+    assert!(history.hand_over());
+    let utility = history.utility(&[0, 0, 0]);
+    assert_eq!(utility[1], -(CONFIG.small_blind as f64));
+    assert_eq!(utility[2], CONFIG.small_blind as f64);
+}
+
+#[test]
+fn multiway_betting_order() {
+    let mut history = MultiwayHistory::new(6);
+    assert_eq!(position_name(history.player, 6), "UTG");
+    let mut streets = Vec::new();
+    while !history.hand_over() {
+        if history.to_call() == 0 && !streets.contains(&history.street) {
+            // The small blind acts first after the flop
+            streets.push(history.street);
+            if history.street != PREFLOP {
+                assert_eq!(position_name(history.player, 6), "SB");
+            }
+        }
+        history.add(&Action {
+            action: ActionType::Call,
+            amount: history.to_call(),
+        });
+    }
+    assert_eq!(history.street, SHOWDOWN);
+    assert_eq!(history.pot(), 6 * (CONFIG.big_blind + CONFIG.ante));
+    // Split six ways, nobody wins or loses anything
+    assert!(history.utility(&[7; 6]).iter().all(|&u| u.abs() < 1e-9));
+}
+
+#[test]
+fn omaha_uses_exactly_two_hole_cards() {
+    // Only one heart in the hand, so no flush even with four on the board
+    let board = str2cards("AhKhQhJh2c");
+    let one_heart = omaha_hand_strength(&str2cards("Th9c8d7s"), &board);
+    let two_hearts = omaha_hand_strength(&str2cards("3h4h5c6d"), &board);
+    assert!(two_hearts > one_heart);
+
+    // Quads on the board only play as a full house
+    let board = str2cards("AcAdAhAs2c");
+    let kings = omaha_hand_strength(&str2cards("KcKd3h4h"), &board);
+    let deuces = omaha_hand_strength(&str2cards("2h2s5c6d"), &board);
+    let no_pair = omaha_hand_strength(&str2cards("KcQd3h4h"), &board);
+    assert!(kings > deuces);
+    assert!(deuces > no_pair);
+}
+
+#[test]
+fn pot_limit_bet_sizes() {
+    // Calling the 100 big blind and raising the pot of 200 makes it 300
+    assert_eq!(pot_limit_bet(100, 100), 300);
+    // Facing a pot sized bet of 500 into 500, the pot is 1500 after calling
+    assert_eq!(pot_limit_bet(1000, 500), 2000);
+    assert_eq!(n_choose_k(52, 4), 270_725);
+}
+
+#[test]
+fn heads_up_multiway_history() {
+    let mut history = MultiwayHistory::new(2);
+    // The button posts the small blind and acts first preflop, and last after the flop
+    assert_eq!(history.player, 0);
+    assert_eq!(history.to_call(), CONFIG.big_blind - CONFIG.small_blind);
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: history.to_call(),
+    });
+    history.add(&Action {
+        action: ActionType::Call,
+        amount: 0,
+    });
+    assert_eq!(history.street, FLOP);
+    assert_eq!(history.player, 1);
+}
+
+#[test]
+fn ground_emd_moves_mass_to_nearest_buckets() {
+    // Three buckets on a line
+    let ground = GroundEmd::new(vec![
+        vec![0.0, 1.0, 2.0],
+        vec![1.0, 0.0, 1.0],
+        vec![2.0, 1.0, 0.0],
+    ]);
+    assert_eq!(ground.distance(&vec![(0, 1.0)], &[1.0, 0.0, 0.0]), 0.0);
+    assert_eq!(ground.distance(&vec![(0, 1.0)], &[0.0, 0.0, 1.0]), 2.0);
+    assert_eq!(
+        ground.distance(&vec![(0, 0.5), (2, 0.5)], &[0.0, 1.0, 0.0]),
+        1.0
+    );
+}
+
+#[test]
+fn k_means_separates_clusters() {
+    let ground = GroundEmd::new(vec![
+        vec![0.0, 1.0, 5.0, 6.0],
+        vec![1.0, 0.0, 4.0, 5.0],
+        vec![5.0, 4.0, 0.0, 1.0],
+        vec![6.0, 5.0, 1.0, 0.0],
+    ]);
+    let mut points: Vec<SparseHistogram> = Vec::new();
+    for i in 0..50 {
+        let p = i as f32 / 50.0;
+        points.push(vec![(0, p), (1, 1.0 - p)]);
+        points.push(vec![(2, p), (3, 1.0 - p)]);
+    }
+    let (clusters, centers) = k_means(&points, 2, &ground, 100);
+    assert_eq!(centers.len(), 2);
+    for i in 0..50 {
+        assert_eq!(clusters[2 * i], clusters[0]);
+        assert_eq!(clusters[2 * i + 1], clusters[1]);
+    }
+    assert_ne!(clusters[0], clusters[1]);
+}
+
+#[test]
+fn one_dimensional_emd() {
+    // Moving all the mass by two buckets costs 2, and L2 can't tell how far it moved
+    let a = [1.0, 0.0, 0.0];
+    let b = [0.0, 1.0, 0.0];
+    let c = [0.0, 0.0, 1.0];
+    assert_eq!(Emd1d.center_distance(&a, &b), 1.0);
+    assert_eq!(Emd1d.center_distance(&a, &c), 2.0);
+    assert_eq!(L2.center_distance(&a, &b), L2.center_distance(&a, &c));
+}
+
+#[test]
+fn hamerly_k_means_matches_brute_force_assignment() {
+    let mut rng = rand::thread_rng();
+    let points: Vec<Vec<f32>> = (0..2000)
+        .map(|_| (0..4).map(|_| rng.gen_range(0.0..1.0)).collect())
+        .collect();
+    let (clusters, centers) = k_means(&points, 20, &L2, 1000);
+    // At convergence, every point is assigned to its nearest center
+    for (point, &cluster) in points.iter().zip(clusters.iter()) {
+        let nearest = (0..centers.len())
+            .min_by(|&a, &b| {
+                let da = L2.distance(point, &centers[a]);
+                let db = L2.distance(point, &centers[b]);
+                da.partial_cmp(&db).unwrap()
+            })
+            .unwrap();
+        let gap =
+            L2.distance(point, &centers[cluster as usize]) - L2.distance(point, &centers[nearest]);
+        assert!(gap < 1e-4);
+    }
+}
+
+#[test]
+fn sorted_clusters_are_numbered_by_key() {
+    let mut clusters = vec![0, 1, 2, 1, 0];
+    let centers = vec![vec![0.5], vec![0.9], vec![0.1]];
+    let centers = sort_clusters(&mut clusters, centers, |c| c[0]);
+    assert_eq!(centers, vec![vec![0.1], vec![0.5], vec![0.9]]);
+    assert_eq!(clusters, vec![1, 2, 0, 2, 1]);
+}
+
+#[test]
+fn head_to_head_folder_loses_its_blinds() {
+    // One player always shoves and the other always folds, so the folder loses the small blind
+    // on the button and the big blind otherwise.
+    let folder = |_: &[Card], _: &[Card], history: &ActionHistory| {
+        let actions = history.next_actions(&CONFIG.bet_abstraction);
+        let action = if actions.contains(&FOLD) {
+            FOLD
+        } else {
+            actions[0].clone()
+        };
+        Strategy::from([(action, 1.0)])
+    };
+    let shover = |_: &[Card], _: &[Card], history: &ActionHistory| {
+        let actions = history.next_actions(&CONFIG.bet_abstraction);
+        let shove = actions.iter().max_by_key(|a| a.amount).unwrap().clone();
+        Strategy::from([(shove, 1.0)])
+    };
+    let (mean, confidence) = head_to_head(&folder, &shover, 1000);
+    let blinds = (CONFIG.small_blind + CONFIG.big_blind) as f64 / 2.0;
+    assert_eq!(mean, -blinds / CONFIG.big_blind as f64);
+    assert!(confidence > 0.0);
+}
+
+#[test]
+fn hand_indexer_sizes() {
+    // Waugh's published numbers of isomorphic hold'em hands
+    assert_eq!(HandIndexer::new(&[2], 2).size(), 169);
+    assert_eq!(HandIndexer::new(&[2, 3], 2).size(), 1_286_792);
+    assert_eq!(HandIndexer::new(&[2, 3, 1], 2).size(), 55_190_538);
+    assert_eq!(HandIndexer::new(&[2, 3, 1, 1], 2).size(), 2_428_287_420);
+    // With the whole board as one round, as in the abstractions
+    assert_eq!(HandIndexer::new(&[2, 4], 2).size(), 13_960_050);
+    assert_eq!(HandIndexer::new(&[2, 5], 2).size(), 123_156_254);
+}
+
+#[test]
+fn hand_indexer_round_trip() {
+    let indexer = HandIndexer::new(&[2, 3], 2);
+    (0..indexer.size()).into_par_iter().for_each(|index| {
+        assert_eq!(indexer.index(&indexer.unindex(index)), index);
+    });
+}
+
+#[test]
+fn hand_indexer_ignores_suits_and_order() {
+    let indexer = HandIndexer::new(&[2, 5], 2);
+    let mut rng = thread_rng();
+    let mut deck = deck();
+    for _ in 0..10_000 {
+        deck.shuffle(&mut rng);
+        let hand = &deck[..7];
+        let mut suits = [0, 1, 2, 3];
+        suits.shuffle(&mut rng);
+        let mut permuted: Vec<Card> = hand
+            .iter()
+            .map(|c| Card {
+                rank: c.rank,
+                suit: suits[c.suit as usize],
+            })
+            .collect();
+        permuted[2..].shuffle(&mut rng);
+        let index = indexer.index(hand);
+        assert!(index < indexer.size());
+        assert_eq!(indexer.index(&permuted), index);
+        assert_eq!(indexer.index(&indexer.unindex(index)), index);
+    }
+}
+
+#[test]
+fn validate_street_abstraction_finds_problems() {
+    let n_buckets = CONFIG.flop_buckets as usize;
+    let size = FLOP_INDEXER.size() as usize;
+    let mut table: Vec<u16> = (0..size).map(|i| (i % n_buckets) as u16).collect();
+    assert!(validate_street_abstraction(&table, FLOP).is_empty());
+
+    table[7] = u16::MAX;
+    assert_eq!(validate_street_abstraction(&table, FLOP).len(), 1);
+    table[7] = 0;
+
+    let too_few: Vec<u16> = table.iter().map(|&b| b / 2).collect();
+    assert_eq!(validate_street_abstraction(&too_few, FLOP).len(), 1);
+    assert_eq!(validate_street_abstraction(&table[1..], FLOP).len(), 1);
+}
+
+#[test]
+fn fast_hand_table_orders_hands_like_rs_poker() {
+    let mut rng = thread_rng();
+    let mut deck = deck();
+    for n in 5..=7 {
+        for _ in 0..20_000 {
+            deck.shuffle(&mut rng);
+            let a = &deck[..n];
+            let b = &deck[n..2 * n];
+            assert_eq!(
+                FAST_HAND_TABLE
+                    .hand_strength(a)
+                    .cmp(&FAST_HAND_TABLE.hand_strength(b)),
+                holdem_hand_strength(a).cmp(&holdem_hand_strength(b)),
+                "{} vs {}",
+                cards2str(a),
+                cards2str(b)
+            );
+        }
+    }
+}
+
+#[test]
+fn hole_strengths_match_hand_strength() {
+    let mut deck = deck();
+    deck.shuffle(&mut thread_rng());
+    let board = &deck[..5];
+    let holes: Vec<[Card; 2]> = deck[5..]
+        .iter()
+        .combinations(2)
+        .map(|h| [*h[0], *h[1]])
+        .collect();
+    let strengths = FAST_HAND_TABLE.hole_strengths(&holes, board);
+    for (hole, strength) in holes.iter().zip(strengths) {
+        let hand = [&hole[..], board].concat();
+        assert_eq!(strength, FAST_HAND_TABLE.hand_strength(&hand));
+    }
+}
+
+#[test]
+fn exact_hand_vs_hand_equity() {
+    let limit = EQUITY_TIME_LIMIT;
+    let aces = str2cards("AhAd");
+    let kings = str2cards("KcKs");
+    let river = hand_vs_hand(&aces, &kings, &str2cards("2c3d7h9sJd"), limit);
+    assert!(river.exact);
+    assert_eq!(river.win, 1.0);
+
+    let preflop = hand_vs_hand(&aces, &kings, &[], limit);
+    assert!(preflop.exact);
+    assert!(preflop.equity() > 0.81 && preflop.equity() < 0.83);
+    let reverse = hand_vs_hand(&kings, &aces, &[], limit);
+    assert!((preflop.equity() + reverse.equity() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn hand_vs_range_averages_hands() {
+    let limit = EQUITY_TIME_LIMIT;
+    let hero = str2cards("Th9h");
+    let board = str2cards("8h7c2hKs");
+    let villains = ["AhAs", "QdJd", "8c8d"];
+    let range = parse_range(&villains.join(",")).unwrap();
+    let equity = hand_vs_range(&hero, &range, &board, limit);
+    let average = villains
+        .iter()
+        .map(|v| hand_vs_hand(&hero, &str2cards(v), &board, limit).equity())
+        .sum::<f64>()
+        / 3.0;
+    assert!(equity.exact);
+    assert!((equity.equity() - average).abs() < 1e-9);
+
+    // Bad input is an error rather than a panic, so the equity endpoint can reject it
+    assert!(parse_range("AhAs,QdJ").is_err());
+    assert!(parse_range("AhAs,XdJd").is_err());
+    assert!(parse_range("AhAh").is_err());
+    assert!(check_equity_inputs(&range, &range, &board).is_ok());
+    assert!(check_equity_inputs(&range, &range, &str2cards("8h8h")).is_err());
+    let aces = parse_range("AhAs").unwrap();
+    assert!(check_equity_inputs(&aces, &aces, &[]).is_err());
+    assert!(check_equity_inputs(&aces, &parse_range("random").unwrap(), &[]).is_ok());
+}
+
+#[test]
+fn sampled_equity_against_random_hand() {
+    // Too many showdowns to enumerate, so this is sampled. Aces win about 85% against a
+    // random hand.
+    let equity = hand_vs_range(&str2cards("AhAd"), &Range::new(), &[], EQUITY_TIME_LIMIT);
+    assert!(!equity.exact);
+    assert!(equity.equity() > 0.84 && equity.equity() < 0.86);
+}
+
+#[test]
+fn distributed_training_on_localhost() {
+    // Each iteration counts itself in one node, and checks that it can see every iteration
+    // from earlier rounds, which all workers' changes should have been merged into.
+    let iteration = |nodes: &Nodes, i: usize| {
+        let history_nodes = nodes
+            .dashmap
+            .entry(ActionHistory::new())
+            .or_insert_with(|| vec![Mutex::new(Node::new(2))]);
+        let mut node = history_nodes[0].lock().unwrap();
+        assert!(node.t as usize >= 1000 + i - i % 10);
+        node.t += 1;
+        node.regrets[i % 2] += 1.0;
+    };
+
+    // Warm start from a blueprint that has already done 1000 iterations
+    let history = ActionHistory::new();
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    let mut node = Node::new(2);
+    node.t = 1000;
+    nodes
+        .dashmap
+        .insert(history.clone(), vec![Mutex::new(node)]);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let workers: Vec<_> = (0..3)
+        .map(|_| std::thread::spawn(move || run_worker(address, iteration)))
+        .collect();
+    let mut coordinator = Coordinator::new(listener, 3, nodes);
+    coordinator.train(50, 10);
+    coordinator.train(50, 10);
+    let nodes = coordinator.finish();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let node = nodes.dashmap.get(&history).unwrap()[0]
+        .lock()
+        .unwrap()
+        .clone();
+    assert_eq!(node.t, 1100);
+    // Each round adds 5 to both regrets across the workers, which the coordinator averages
+    for regret in &node.regrets[..2] {
+        assert!((regret - 50.0 / 3.0).abs() < 1e-3, "regret {regret}");
+    }
+}
+
+// Trains Leduc on n_workers worker threads. Each iteration's board comes from its index, so
+// every run deals the same boards however the iterations are split between the workers.
+fn train_leduc_distributed(n_workers: usize) -> Nodes<Leduc> {
+    let iteration = |nodes: &Nodes<Leduc>, i: usize| {
+        let board = (i % 6) as u8;
+        for traverser in [DEALER, OPPONENT] {
+            let hands = Leduc::hands(&board);
+            let reach_probs = vec![1.0; hands.len()];
+            iterate(
+                traverser,
+                hands,
+                board,
+                &Leduc::root(),
+                reach_probs.clone(),
+                reach_probs,
+                nodes,
+                -1,
+                None,
+                false,
+            );
+        }
+    };
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let workers: Vec<_> = (0..n_workers)
+        .map(|_| std::thread::spawn(move || run_worker(address, iteration)))
+        .collect();
+    let mut coordinator = Coordinator::new(listener, n_workers, Nodes::default());
+    coordinator.train(3000, 300);
+    let nodes = coordinator.finish();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    nodes
+}
+
+#[test]
+fn distributed_training_matches_one_worker() {
+    // Splitting the same iterations between three workers should train the same blueprint as
+    // one worker does, up to the order of the updates, so the regrets that are clearly away
+    // from zero should have the same signs.
+    let one = train_leduc_distributed(1);
+    let three = train_leduc_distributed(3);
+    let (mut compared, mut agreed) = (0, 0);
+    for elem in one.dashmap.iter() {
+        let other = three.dashmap.get(elem.key()).unwrap();
+        for (node, other) in elem.value().iter().zip(other.iter()) {
+            let (node, other) = (node.lock().unwrap(), other.lock().unwrap());
+            for a in 0..node.num_actions {
+                if node.regrets[a].abs() < 1.0 {
+                    continue;
+                }
+                compared += 1;
+                if (node.regrets[a] > 0.0) == (other.regrets[a] > 0.0) {
+                    agreed += 1;
+                }
+            }
+        }
+    }
+    assert!(
+        agreed as f64 > 0.9 * compared as f64,
+        "Only {agreed} of {compared} regrets have the same sign"
+    );
+    assert!(exact_exploitability(&three) < 0.15);
+}
+
+#[test]
+fn training_metrics_log() {
+    assert_eq!(strategy_entropy(&[1.0, 0.0, 0.0]), 0.0);
+    assert!((strategy_entropy(&[0.5, 0.5]) - 1.0).abs() < 1e-9);
+    assert!((strategy_entropy(&[0.25; 4]) - 2.0).abs() < 1e-9);
+
+    let metrics = EpochMetrics {
+        epoch: 3,
+        iterations: 4000,
+        iters_per_second: 250.0,
+        nodes: 100,
+        memory_mb: 12.5,
+        zero_t_fraction: 0.5,
+        average_t: 10.0,
+        average_positive_regret: 3.0,
+        strategy_entropy: [1.0, 0.8, 0.6, 0.4],
+        exploitability: 1.5,
+        exploitability_confidence: 0.2,
+        watched: Vec::new(),
+    };
+    let path = std::env::temp_dir().join("optimus_metrics_test.jsonl");
+    let line = serde_json::to_string(&metrics).unwrap();
+    // A half-written last line, as when the trainer is killed mid-write, is skipped
+    std::fs::write(&path, format!("{line}\n{line}\n{{\"epoch\":")).unwrap();
+    let read = read_metrics(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[1].iterations, 4000);
+    assert!(read_metrics("no_such_metrics.jsonl").is_empty());
+}
+
+#[test]
+fn watchlist_spots_parse() {
+    for spot in &CONFIG.watchlist {
+        assert_eq!(str2cards(&spot.hole).len(), 2);
+        assert!([0, 3, 4, 5].contains(&str2cards(&spot.board).len()));
+        ActionHistory::from_strings(spot.history.iter().map(String::as_str).collect());
+    }
+
+    let watched = WatchedStrategy {
+        epoch: 0,
+        iterations: 1000,
+        spot: "2c7h  ".to_string(),
+        actions: vec!["fold".to_string(), "call".to_string()],
+        average_strategy: vec![0.75, 0.25],
+        current_strategy: vec![1.0, 0.0],
+        regrets: vec![20.0, -5.0],
+        t: 12,
+    };
+    let path = std::env::temp_dir().join("optimus_watchlist_test.jsonl");
+    let line = serde_json::to_string(&watched).unwrap();
+    std::fs::write(&path, format!("{line}\n")).unwrap();
+    let read = read_watchlist(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].regrets, watched.regrets);
+}
+
+#[test]
+fn blueprint_diff_and_merge() {
+    let node = |strategy_sum: [f32; 2], t: i32| {
+        let mut node = Node::new(2);
+        node.strategy_sum[..2].copy_from_slice(&strategy_sum);
+        node.regrets[0] = t as f32;
+        node.t = t;
+        Mutex::new(node)
+    };
+    let preflop = ActionHistory::new();
+    let flop = ActionHistory::from_strings(vec!["Call 100", "Call 100"]);
+    let turn = ActionHistory::from_strings(vec!["Call 100", "Call 100", "Call 0", "Call 0"]);
+
+    let a = Nodes::new(&CONFIG.bet_abstraction);
+    a.dashmap.insert(
+        preflop.clone(),
+        vec![node([1.0, 0.0], 10), node([1.0, 1.0], 10)],
+    );
+    a.dashmap.insert(flop.clone(), vec![node([3.0, 1.0], 10)]);
+    // b has trained for ten times as long
+    let b = Nodes::new(&CONFIG.bet_abstraction);
+    b.dashmap.insert(
+        preflop.clone(),
+        vec![node([0.0, 10.0], 100), node([5.0, 5.0], 100)],
+    );
+    b.dashmap.insert(turn.clone(), vec![node([1.0, 1.0], 100)]);
+
+    let diff = diff_blueprints(&a, &b, 1);
+    assert_eq!(diff.streets[PREFLOP].nodes, 2);
+    assert!((diff.streets[PREFLOP].l1() - 1.0).abs() < 1e-6);
+    assert!((diff.streets[PREFLOP].total_variation() - 0.5).abs() < 1e-6);
+    assert_eq!(diff.streets[FLOP].nodes, 0);
+    assert_eq!(diff.only_in_a, vec![flop.clone()]);
+    assert_eq!(diff.only_in_b, vec![turn.clone()]);
+    assert_eq!(diff.biggest_changes.len(), 1);
+    assert_eq!(diff.biggest_changes[0].bucket, 0);
+    assert!((diff.biggest_changes[0].l1 - 2.0).abs() < 1e-6);
+
+    // Equal weights average the strategies, however long each blueprint trained
+    let merged = merge_blueprints(&a, &b, 0.5);
+    let merged_node = merged.dashmap.get(&preflop).unwrap()[0]
+        .lock()
+        .unwrap()
+        .clone();
+    assert_eq!(merged_node.cumulative_strategy().to_vec(), vec![0.5, 0.5]);
+    assert_eq!(merged_node.t, 55);
+    assert_eq!(merged_node.regrets[0], 55.0);
+    assert!(merged.dashmap.contains_key(&flop) && merged.dashmap.contains_key(&turn));
+    let merged_diff = diff_blueprints(&a, &merge_blueprints(&a, &b, 1.0), 1);
+    assert_eq!(merged_diff.streets[PREFLOP].l1(), 0.0);
+}
+
+#[test]
+fn strategy_browser_views() {
+    let grid = preflop_grid_hands();
+    assert_eq!(grid.len(), 13);
+    assert_eq!(grid[0][0].0, "AA");
+    assert_eq!(grid[0][1].0, "AKs");
+    assert_eq!(grid[1][0].0, "AKo");
+    assert_eq!(grid[12][12].0, "22");
+    let names: HashSet<&String> = grid.iter().flatten().map(|(name, _)| name).collect();
+    assert_eq!(names.len(), 169);
+    for (name, hole) in grid.iter().flatten() {
+        assert_ne!(hole[0], hole[1]);
+        assert_eq!(name.ends_with('s'), hole[0].suit == hole[1].suit);
+    }
+
+    let history = ActionHistory::from_strings(vec!["Call 100", "Call 100"]);
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    assert!(format_bucket_table(&nodes, &history).contains("never reached"));
+    let actions = history.next_actions(&CONFIG.bet_abstraction);
+    let mut node = Node::new(actions.len());
+    node.t = 7;
+    node.regrets[0] = 3.0;
+    node.regrets[1] = 1.0;
+    node.strategy_sum[1] = 2.0;
+    nodes.dashmap.insert(
+        history.clone(),
+        vec![
+            Mutex::new(Node::new(actions.len())),
+            Mutex::new(node.clone()),
+        ],
+    );
+    let table = format_bucket_table(&nodes, &history);
+    assert_eq!(table.lines().count(), 3);
+    assert!(table.lines().nth(2).unwrap().contains("1.000"));
+    let view = format_node(&actions, &node);
+    assert!(view.starts_with("t = 7"));
+    assert!(view.contains(&actions[0].to_string()));
+    assert!(view.contains("0.750"));
+}
+
+#[test]
+fn prune_rarely_reached_histories() {
+    let history_nodes = |reach: f32, n: usize| -> Vec<Mutex<Node>> {
+        (0..n)
+            .map(|i| {
+                let mut node = Node::new(2);
+                node.strategy_sum[0] = reach / n as f32;
+                node.t = i as i32;
+                Mutex::new(node)
+            })
+            .collect()
+    };
+    let root = ActionHistory::new();
+    let common = ActionHistory::from_strings(vec!["Call 100", "Call 100"]);
+    let uncommon = ActionHistory::from_strings(vec!["Call 100", "Bet 200"]);
+    let rare = ActionHistory::from_strings(vec!["Bet 200", "Fold 0"]);
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    nodes.dashmap.insert(root.clone(), history_nodes(0.0, 1));
+    nodes
+        .dashmap
+        .insert(common.clone(), history_nodes(1000.0, 10));
+    nodes
+        .dashmap
+        .insert(uncommon.clone(), history_nodes(50.0, 10));
+    nodes.dashmap.insert(rare.clone(), history_nodes(0.5, 10));
+
+    let stats = prune_blueprint(&nodes, 1.0);
+    assert_eq!(stats.dropped, 1);
+    assert_eq!((stats.nodes_before, stats.nodes_after), (31, 21));
+    // The root stays even though it's never reached
+    assert!(nodes.dashmap.contains_key(&root));
+    assert!(!nodes.dashmap.contains_key(&rare));
+    assert_eq!(nodes.dashmap.get(&common).unwrap().len(), 10);
+    assert_eq!(nodes.dashmap.get(&uncommon).unwrap().len(), 10);
+}
+
+#[test]
+fn fallback_strategy_keeps_action_types() {
+    let fold = Action {
+        action: ActionType::Fold,
+        amount: 0,
+    };
+    let call = Action {
+        action: ActionType::Call,
+        amount: 100,
+    };
+    let bet = |amount| Action {
+        action: ActionType::Bet,
+        amount,
+    };
+    let ancestor: Strategy = [(fold.clone(), 0.2), (call.clone(), 0.2), (bet(300), 0.6)]
+        .into_iter()
+        .collect();
+
+    let actions = vec![fold.clone(), call.clone(), bet(400), bet(1000)];
+    let strategy = strategy_like(&ancestor, &actions);
+    assert_eq!(strategy.len(), 4);
+    assert!((strategy[&fold] - 0.2).abs() < 1e-9);
+    assert!((strategy[&bet(400)] - 0.3).abs() < 1e-9);
+    assert!((strategy[&bet(1000)] - 0.3).abs() < 1e-9);
+
+    // All in already, so betting becomes calling
+    let strategy = strategy_like(&ancestor, &[fold.clone(), call.clone()]);
+    assert!((strategy[&call] - 0.8).abs() < 1e-9);
+    assert!((strategy.values().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn missing_nodes_fall_back_to_a_heuristic() {
+    let history = ActionHistory::new();
+    let actions = history.next_actions(&CONFIG.bet_abstraction);
+    let strategy_for = |equity: f64| -> Action {
+        let strategy = heuristic_strategy(equity, &history, &actions);
+        assert_eq!(strategy.len(), actions.len());
+        strategy.into_iter().find(|(_, p)| *p == 1.0).unwrap().0
+    };
+    assert_eq!(strategy_for(0.9).action, ActionType::Bet);
+    assert_eq!(strategy_for(0.5).action, ActionType::Call);
+    assert_eq!(strategy_for(0.1).action, ActionType::Fold);
+    // Checking is free, so weak hands check instead of folding
+    let checked_to = ActionHistory::from_strings(vec!["Call 100", "Call 100"]);
+    let actions = checked_to.next_actions(&CONFIG.bet_abstraction);
+    let strategy = heuristic_strategy(0.1, &checked_to, &actions);
+    assert_eq!(
+        strategy[&Action {
+            action: ActionType::Call,
+            amount: 0
+        }],
+        1.0
+    );
+
+    // An empty blueprint has nothing to fall back to but the heuristic
+    let heuristic_count = |counts: Vec<(Fallback, usize)>| counts[3].1;
+    let before = heuristic_count(fallback_counts());
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    let strategy = fallback_strategy(&nodes, &str2cards("AhAd"), &[], &history, None);
+    assert_eq!(heuristic_count(fallback_counts()), before + 1);
+    let (action, _) = strategy.iter().find(|(_, p)| **p == 1.0).unwrap();
+    assert_eq!(action.action, ActionType::Bet);
+
+    // Postflop the equity is the hand's strength on the board
+    let board = str2cards("AsAc7d");
+    assert_eq!(heuristic_equity(&str2cards("AhAd"), &board), 1.0);
+    assert!(heuristic_equity(&str2cards("2c3d"), &board) < 0.1);
+    assert!(heuristic_equity(&str2cards("7h7c"), &board) > HEURISTIC_BET_EQUITY);
+}
+
+#[test]
+fn parent_street_fallback_skips_the_opponents_nodes() {
+    let nodes = Nodes::new(&CONFIG.bet_abstraction);
+    // The dealer always folds at the root, and the opponent always checks after a limp
+    let add_pure_nodes = |history: &ActionHistory, action: ActionType| {
+        let actions = history.next_actions(&CONFIG.bet_abstraction);
+        let mut node = Node::new(actions.len());
+        node.strategy_sum[actions.iter().position(|a| a.action == action).unwrap()] = 1.0;
+        let history_nodes: Vec<Mutex<Node>> = (0..num_buckets(PREFLOP))
+            .map(|_| Mutex::new(node.clone()))
+            .collect();
+        nodes.dashmap.insert(history.clone(), history_nodes);
+    };
+    let limped = ActionHistory::from_strings(vec!["Call 100"]);
+    add_pure_nodes(&ActionHistory::new(), ActionType::Fold);
+    add_pure_nodes(&limped, ActionType::Call);
+
+    // The dealer faces a raise after limping, which the blueprint doesn't have. The nearest
+    // ancestor is the opponent's, so the dealer's own node at the root is used instead.
+    let raised = ActionHistory::from_strings(vec!["Call 100", "Bet 200"]);
+    assert_eq!(raised.player, ActionHistory::new().player);
+    let strategy = fallback_strategy(&nodes, &str2cards("AhAd"), &[], &raised, None);
+    let (action, _) = strategy.iter().find(|(_, p)| **p == 1.0).unwrap();
+    assert_eq!(action.action, ActionType::Fold);
+}
+
+#[test]
+fn scripted_opponents_decisions() {
+    let history = ActionHistory::new();
+    let actions = history.next_actions(&CONFIG.bet_abstraction);
+    let chosen = |strategy: Strategy| -> Action {
+        assert_eq!(strategy.len(), actions.len());
+        strategy.into_iter().find(|(_, p)| *p == 1.0).unwrap().0
+    };
+    assert_eq!(chosen(always_call(&actions)).action, ActionType::Call);
+    let raise = chosen(always_raise(&actions));
+    assert_eq!(raise.action, ActionType::Bet);
+    let all_in = actions.iter().map(|a| a.amount).max().unwrap();
+    assert!(raise.amount < all_in);
+    assert_eq!(chosen(always_shove(&actions)).amount, all_in);
+
+    assert_eq!(
+        chosen(tight_aggressive_strategy(0.7, &history, &actions)).action,
+        ActionType::Bet
+    );
+    assert_eq!(
+        chosen(tight_aggressive_strategy(0.4, &history, &actions)).action,
+        ActionType::Fold
+    );
+
+    let always_call_strategy = |hole: &[Card], board: &[Card], history: &ActionHistory| {
+        ScriptedOpponent::AlwaysCall.get_strategy(hole, board, history)
+    };
+    let results = evaluate_against_opponents(&always_call_strategy, 20);
+    assert_eq!(results.len(), SCRIPTED_OPPONENTS.len());
+    let report = format_evaluation(&results);
+    for opponent in SCRIPTED_OPPONENTS {
+        assert!(report.contains(&opponent.to_string()));
+    }
+}
+
+#[test]
+fn lbr_exact_river_equity() {
+    let hole = str2cards("AhAd");
+    let board = str2cards("2c7d9hJsKs");
+    // KK makes trips and beats the aces, and QQ loses to them
+    let range = Range::from_holes(&[
+        str2cards("KcKd").to_smallvec(),
+        str2cards("QcQd").to_smallvec(),
+    ]);
+    assert!((exact_river_win_probability(&range, &hole, &board).unwrap() - 0.5).abs() < 1e-9);
+    // Hands that share a card with the hole cards or board don't count
+    let range = Range::from_holes(&[
+        str2cards("AcAs").to_smallvec(),
+        str2cards("AhKc").to_smallvec(),
+        str2cards("2d2h").to_smallvec(),
+    ]);
+    assert!((exact_river_win_probability(&range, &hole, &board).unwrap() - 0.25).abs() < 1e-9);
+    // Every hand is blocked
+    let range = Range::from_holes(&[str2cards("AhKc").to_smallvec()]);
+    assert_eq!(exact_river_win_probability(&range, &hole, &board), None);
+    assert!(CONFIG.lbr_raise_from() <= RIVER);
+}